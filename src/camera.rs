@@ -102,4 +102,14 @@ impl Camera {
         self.projection.compute();
         self.matrix = self.projection.matrix * self.view.matrix;
     }
+
+    /// World space ray from the near plane through `ndc`, -1..1 with y up
+    pub fn ray_through(&self, ndc: Vec2) -> (Vec3, Vec3) {
+        let inverse = self.matrix.inverse();
+
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+
+        (near, (far - near).normalize())
+    }
 }
@@ -396,12 +396,24 @@ impl Canvas {
             .unwrap()
     }
 
+    /// Premultiplied RGBA with sRGB encoded color. It goes into an
+    /// `Rgba8Unorm` texture, so samples are filtered before any decoding
+    /// and shaders unpremultiply to decode, see `canvas_quad.wgsl`.
     pub fn as_bytes(&mut self) -> Result<Vec<u8>, &'static str> {
+        self.read_pixels(AlphaType::Premul)
+    }
+
+    /// Straight alpha RGBA, for encoders and image files
+    pub fn as_unpremul_bytes(&mut self) -> Result<Vec<u8>, &'static str> {
+        self.read_pixels(AlphaType::Unpremul)
+    }
+
+    fn read_pixels(&mut self, alpha_type: AlphaType) -> Result<Vec<u8>, &'static str> {
         let (width, height) = (self.surface.width(), self.surface.height());
         let image_info = ImageInfo::new(
             (width, height),
             ColorType::RGBA8888,
-            AlphaType::Premul,
+            alpha_type,
             None,
         );
         let image_row_size = (width * 4) as usize;
@@ -427,3 +439,116 @@ impl Canvas {
         self.surface.canvas()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_utils::{assert_pixels_close, render_offscreen, test_ctx};
+    use crate::gpu::{GPUTexture, OffscreenTarget};
+    use crate::pipelines::canvas_quad;
+    use egui_wgpu::wgpu;
+    use glam::Mat4;
+
+    /// One pixel each, drawn left to right
+    const COLORS: [Color; 5] = [
+        Color::RED,
+        Color::GREEN,
+        Color::BLUE,
+        Color::from_argb(128, 255, 255, 255),
+        Color::TRANSPARENT,
+    ];
+
+    /// What `COLORS` look like blended over transparent black into an
+    /// sRGB target, RGBA. Half white is linear 0.5, which encodes to 188.
+    const EXPECTED: [[u8; 4]; 5] = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [188, 188, 188, 128],
+        [0, 0, 0, 0],
+    ];
+
+    fn draw_strip() -> Canvas {
+        let mut canvas = Canvas::new(COLORS.len() as u32, 1, 1.0);
+        canvas.canvas().clear(Color::TRANSPARENT);
+
+        for (x, color) in COLORS.into_iter().enumerate() {
+            canvas.set_fill_color(color);
+            canvas.fill_rect(x as f32, 0.0, x as f32 + 1.0, 1.0);
+        }
+
+        canvas
+    }
+
+    /// Covers the whole target, one texel per pixel
+    fn fullscreen_quad() -> canvas_quad::ModelBundle {
+        canvas_quad::ModelBundle {
+            vertex_data: vec![
+                canvas_quad::Vertex::new([-1.0, -1.0, 0.5], [0, 1]),
+                canvas_quad::Vertex::new([-1.0, 1.0, 0.5], [0, 0]),
+                canvas_quad::Vertex::new([1.0, 1.0, 0.5], [1, 0]),
+                canvas_quad::Vertex::new([1.0, -1.0, 0.5], [1, 1]),
+            ],
+            index_data: vec![0, 1, 2, 2, 3, 0],
+        }
+    }
+
+    #[test]
+    fn canvas_pixels_are_premultiplied() {
+        let bytes = draw_strip().as_bytes().unwrap();
+
+        assert_eq!(&bytes[12..16], &[128, 128, 128, 128]);
+        assert_eq!(&bytes[16..20], &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn canvas_round_trips_through_the_gpu() {
+        let ctx = test_ctx();
+
+        let bytes = draw_strip().as_bytes().unwrap();
+        let width = COLORS.len() as u32;
+
+        // Same format and usages as the canvas texture in main
+        let texture = GPUTexture::create(
+            &ctx,
+            width,
+            1,
+            wgpu::TextureFormat::Rgba8Unorm,
+            [0u8; 4],
+            wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+
+        texture.update(&ctx, &bytes);
+
+        // The upload itself keeps the bytes as they are
+        assert_eq!(texture.read_to_vec_blocking(&ctx).unwrap(), bytes);
+
+        let pipeline = canvas_quad::Pipeline::create(
+            &ctx,
+            OffscreenTarget::FORMAT,
+            canvas_quad::shader_file().embedded(),
+        )
+        .unwrap();
+
+        let bind_group_0 = canvas_quad::BindGroup0::create(&ctx, &texture);
+        let bind_group_1 = canvas_quad::BindGroup1::create(&ctx, Mat4::IDENTITY);
+        let vertex_format = canvas_quad::VertexFormat::create(&ctx, &fullscreen_quad());
+
+        let rendered = render_offscreen(&ctx, width, 1, wgpu::Color::TRANSPARENT, |pass| {
+            pass.set_pipeline(&pipeline.pipeline);
+            pass.set_bind_group(0, &bind_group_0.bind_group, &[]);
+            pass.set_bind_group(1, &bind_group_1.bind_group, &[]);
+            pass.set_index_buffer(
+                vertex_format.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            );
+            pass.set_vertex_buffer(0, vertex_format.vertex_buffer.slice(..));
+            pass.draw_indexed(0..vertex_format.index_count, 0, 0..1);
+        });
+
+        assert_pixels_close(&rendered, &EXPECTED);
+    }
+}
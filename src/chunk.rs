@@ -1,29 +1,161 @@
-use crate::cube::generate_full_mesh;
 use crate::gpu::GPUCtx;
 use crate::pipelines::quad_mesh;
 use glam::*;
+use std::sync::Arc;
 
-pub struct Chunk {
+/// Blocks along each axis of a chunk
+pub const CHUNK_SIZE: i32 = 16;
+
+/// A block is two units wide, see `cube::add_faces`
+pub const CHUNK_WORLD_SIZE: f32 = CHUNK_SIZE as f32 * 2.0;
+
+/// The six chunks sharing a face with another, in `ChunkNeighbours` order
+pub const NEIGHBOUR_OFFSETS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Which blocks of a chunk are solid, indexed by their position inside it
+#[derive(Clone)]
+pub struct ChunkVoxels {
+    solid: Vec<bool>,
+}
+
+impl ChunkVoxels {
+    pub fn empty() -> Self {
+        Self {
+            solid: vec![false; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
+        }
+    }
+
+    pub fn contains(local: IVec3) -> bool {
+        local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(CHUNK_SIZE)).all()
+    }
+
+    fn index(local: IVec3) -> usize {
+        (local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    pub fn get(&self, local: IVec3) -> bool {
+        self.solid[Self::index(local)]
+    }
+
+    pub fn set(&mut self, local: IVec3, solid: bool) {
+        self.solid[Self::index(local)] = solid;
+    }
+
+    /// One bit per block, in index order
+    pub fn to_bits(&self) -> Vec<u8> {
+        self.solid
+            .chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0u8, |bits, (i, &solid)| bits | ((solid as u8) << i))
+            })
+            .collect()
+    }
+
+    /// Reverse of `to_bits`, `None` if `bits` is not the size of a chunk
+    pub fn from_bits(bits: &[u8]) -> Option<Self> {
+        let mut voxels = Self::empty();
+
+        if bits.len() * 8 != voxels.solid.len() {
+            return None;
+        }
+
+        for (i, solid) in voxels.solid.iter_mut().enumerate() {
+            *solid = bits[i / 8] & (1 << (i % 8)) != 0;
+        }
+
+        Some(voxels)
+    }
+}
+
+/// Voxels of the chunks around the one being meshed, `None` where
+/// nothing is loaded
+#[derive(Clone, Default)]
+pub struct ChunkNeighbours(pub [Option<Arc<ChunkVoxels>>; 6]);
+
+impl ChunkNeighbours {
+    /// `local` may be one block outside of `voxels`. Unloaded neighbours
+    /// count as air so the edge of the loaded terrain stays closed.
+    pub fn solid_at(&self, voxels: &ChunkVoxels, local: IVec3) -> bool {
+        if ChunkVoxels::contains(local) {
+            return voxels.get(local);
+        }
+
+        let offset = local.div_euclid(IVec3::splat(CHUNK_SIZE));
+
+        NEIGHBOUR_OFFSETS
+            .iter()
+            .position(|&neighbour| neighbour == offset)
+            .and_then(|i| self.0[i].as_ref())
+            .is_some_and(|neighbour| neighbour.get(local.rem_euclid(IVec3::splat(CHUNK_SIZE))))
+    }
+}
+
+pub struct ChunkMesh {
     pub vertex_format: quad_mesh::VertexFormat,
-    pub bind_group: quad_mesh::BindGroup1,
+    pub vertex_count: usize,
     pub index_count: usize,
 }
 
-impl Chunk {
-    pub fn new(ctx: &GPUCtx, x: i32, y: i32, z: i32) -> Self {
-        let model = generate_full_mesh(x * 16, y * 16, z * 16);
+pub struct Chunk {
+    pub coord: IVec3,
+    /// Where the chunk sits inside the terrain
+    pub offset: Vec3,
+    pub voxels: Arc<ChunkVoxels>,
+    /// Missing until the first mesh is built, or when there is nothing to draw
+    pub mesh: Option<ChunkMesh>,
+    pub bind_group: quad_mesh::BindGroup1,
+    /// Bumped on every remesh request, older meshes still in flight are dropped
+    pub mesh_revision: u64,
+}
 
-        let vertex_format = quad_mesh::VertexFormat::create(ctx, &model);
+impl Chunk {
+    /// `terrain` is the world transform of the terrain the chunk belongs to
+    pub fn new(ctx: &GPUCtx, coord: IVec3, voxels: Arc<ChunkVoxels>, terrain: Mat4) -> Self {
+        let offset = coord.as_vec3() * CHUNK_WORLD_SIZE;
 
-        let bind_group = quad_mesh::BindGroup1::create(
-            ctx,
-            Vec4::new(x as f32 * 32.0, y as f32 * 32.0, z as f32 * 32.0, 0.0),
-        );
+        let bind_group =
+            quad_mesh::BindGroup1::create(ctx, terrain * Mat4::from_translation(offset));
 
         Chunk {
-            vertex_format,
-            index_count: model.index_data.len(),
+            coord,
+            offset,
+            voxels,
+            mesh: None,
             bind_group,
+            mesh_revision: 0,
         }
     }
+
+    pub fn set_mesh(&mut self, ctx: &GPUCtx, model: &quad_mesh::ModelBundle) {
+        self.mesh = if model.index_data.is_empty() {
+            None
+        } else {
+            Some(ChunkMesh {
+                vertex_format: quad_mesh::VertexFormat::create(ctx, model),
+                vertex_count: model.vertex_data.len(),
+                index_count: model.index_data.len(),
+            })
+        };
+    }
+
+    pub fn set_terrain_transform(&self, ctx: &GPUCtx, terrain: Mat4) {
+        self.bind_group
+            .update_transform(ctx, terrain * Mat4::from_translation(self.offset));
+    }
+
+    /// What the vertex and index buffers take on the GPU
+    pub fn gpu_bytes(&self) -> u64 {
+        self.mesh.as_ref().map_or(0, |mesh| {
+            mesh.vertex_format.vertex_buffer.size() + mesh.vertex_format.index_buffer.size()
+        })
+    }
 }
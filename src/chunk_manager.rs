@@ -0,0 +1,851 @@
+use crate::chunk::{
+    Chunk, ChunkNeighbours, ChunkVoxels, CHUNK_SIZE, CHUNK_WORLD_SIZE, NEIGHBOUR_OFFSETS,
+};
+use crate::chunk_store::ChunkStore;
+use crate::cube::{generate_voxels, Mesher};
+use crate::gpu::GPUCtx;
+use crate::pipelines::quad_mesh;
+use crossbeam_channel::{Receiver, Sender};
+use glam::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Generation jobs queued at once, nearest first. Kept low so a
+/// camera that moved on doesn't wait for chunks it left behind.
+const MAX_PENDING_PER_WORKER: usize = 4;
+
+/// How far blocks can be picked, in the terrain's local units
+const MAX_PICK_DISTANCE: f32 = 512.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct StreamingSettings {
+    /// In chunks around the camera
+    pub radius: i32,
+    /// Chunk rows that hold terrain, the noise has no ground level
+    pub min_y: i32,
+    pub max_y: i32,
+    /// Vertex and index buffers of every loaded chunk together
+    pub max_gpu_bytes: u64,
+    /// Finished meshes uploaded per frame, the rest waits
+    pub uploads_per_frame: usize,
+    /// Changing it remeshes every loaded chunk
+    pub mesher: Mesher,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            radius: 6,
+            min_y: 0,
+            max_y: 5,
+            max_gpu_bytes: 512 * 1024 * 1024,
+            uploads_per_frame: 4,
+            mesher: Mesher::Greedy,
+        }
+    }
+}
+
+fn distance_squared(a: IVec3, b: IVec3) -> i32 {
+    (a - b).length_squared()
+}
+
+/// The chunk the camera is in, moved into the terrain rows. Above or
+/// below them the distance counts from the nearest row, otherwise a
+/// camera far enough up would unload everything and load nothing.
+fn streaming_center(position: Vec3, settings: &StreamingSettings) -> IVec3 {
+    let center = (position / CHUNK_WORLD_SIZE).floor().as_ivec3();
+
+    IVec3::new(
+        center.x,
+        center.y.clamp(settings.min_y, settings.max_y),
+        center.z,
+    )
+}
+
+/// Within `radius` of `center` and in the terrain rows, closer
+/// than `budget_limit` too once the memory cap evicted chunks
+fn in_range(
+    settings: &StreamingSettings,
+    budget_limit: Option<i32>,
+    center: IVec3,
+    coord: IVec3,
+    radius: i32,
+) -> bool {
+    let limit = budget_limit.map_or(radius * radius, |limit| limit.min(radius * radius));
+
+    coord.y >= settings.min_y
+        && coord.y <= settings.max_y
+        && distance_squared(center, coord) <= limit
+}
+
+/// Every chunk that should be loaded around `center`, nearest first
+fn chunks_in_range(
+    settings: &StreamingSettings,
+    budget_limit: Option<i32>,
+    center: IVec3,
+) -> Vec<IVec3> {
+    let radius = settings.radius;
+
+    let mut coords: Vec<IVec3> = (-radius..=radius)
+        .flat_map(|x| {
+            (settings.min_y..=settings.max_y).flat_map(move |y| {
+                (-radius..=radius).map(move |z| IVec3::new(center.x + x, y, center.z + z))
+            })
+        })
+        .filter(|&coord| in_range(settings, budget_limit, center, coord, radius))
+        .collect();
+
+    coords.sort_by_key(|&coord| distance_squared(center, coord));
+    coords
+}
+
+/// The chunks to drop, farthest first, so that the `loaded` ones
+/// (coord and GPU bytes) fit in `max_gpu_bytes` again
+fn over_budget(center: IVec3, mut loaded: Vec<(IVec3, u64)>, max_gpu_bytes: u64) -> Vec<IVec3> {
+    let mut gpu_bytes: u64 = loaded.iter().map(|&(_, bytes)| bytes).sum();
+
+    loaded.sort_by_key(|&(coord, _)| std::cmp::Reverse(distance_squared(center, coord)));
+
+    loaded
+        .into_iter()
+        .take_while(|&(_, bytes)| {
+            let evict = gpu_bytes > max_gpu_bytes;
+            gpu_bytes -= bytes;
+            evict
+        })
+        .map(|(coord, _)| coord)
+        .collect()
+}
+
+enum Job {
+    Generate(IVec3),
+    Mesh {
+        coord: IVec3,
+        revision: u64,
+        mesher: Mesher,
+        voxels: Arc<ChunkVoxels>,
+        neighbours: ChunkNeighbours,
+    },
+    Save(IVec3, Arc<ChunkVoxels>),
+}
+
+enum JobResult {
+    Generated(IVec3, ChunkVoxels),
+    Meshed(IVec3, u64, quad_mesh::ModelBundle),
+    Saved(IVec3),
+}
+
+fn run_generator(jobs: Receiver<Job>, results: Sender<JobResult>, store: ChunkStore) {
+    for job in jobs.iter() {
+        let result = match job {
+            Job::Generate(coord) => {
+                puffin::profile_scope!("Generate chunk");
+
+                // Edited chunks come back the way they were saved
+                let voxels = match store.load(coord) {
+                    Ok(Some(voxels)) => voxels,
+                    Ok(None) => generate_voxels(coord),
+                    Err(e) => {
+                        eprintln!("{e}, regenerating the chunk");
+                        generate_voxels(coord)
+                    }
+                };
+
+                JobResult::Generated(coord, voxels)
+            }
+            Job::Mesh {
+                coord,
+                revision,
+                mesher,
+                voxels,
+                neighbours,
+            } => {
+                puffin::profile_scope!("Mesh chunk");
+
+                JobResult::Meshed(coord, revision, mesher.generate(&voxels, &neighbours))
+            }
+            Job::Save(coord, voxels) => {
+                puffin::profile_scope!("Save chunk");
+
+                // Nothing to retry with, the edit stays in memory while loaded
+                if let Err(e) = store.save(coord, &voxels) {
+                    eprintln!("{e}");
+                }
+
+                JobResult::Saved(coord)
+            }
+        };
+
+        if results.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BlockEdit {
+    Remove,
+    /// Against the face of the block the ray hit
+    Place,
+}
+
+/// A block edit along a ray in world space, e.g. from the camera through the cursor
+#[derive(Clone, Copy, Debug)]
+pub struct BlockEditRequest {
+    pub edit: BlockEdit,
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VoxelHit {
+    /// In blocks from the terrain's origin
+    pub block: IVec3,
+    /// Side of the block the ray entered through, zero when it started inside
+    pub normal: IVec3,
+}
+
+/// First block along the ray for which `is_solid` holds, walked one block
+/// boundary at a time. `origin` and `direction` are in the terrain's local space.
+fn raycast_blocks(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    is_solid: impl Fn(IVec3) -> bool,
+) -> Option<VoxelHit> {
+    let direction = direction.normalize_or_zero();
+
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    // Block `b` spans b * 2 - 1 to b * 2 + 1, in block units it spans b to b + 1
+    let origin = (origin + 1.0) / 2.0;
+    let max_distance = max_distance / 2.0;
+
+    let mut block = origin.floor().as_ivec3();
+    let step = direction.signum().as_ivec3();
+    let delta = direction.recip().abs();
+
+    // Ray distance to the next boundary on each axis
+    let mut next = Vec3::ZERO;
+
+    for axis in 0..3 {
+        next[axis] = if direction[axis] > 0.0 {
+            (block[axis] as f32 + 1.0 - origin[axis]) * delta[axis]
+        } else if direction[axis] < 0.0 {
+            (origin[axis] - block[axis] as f32) * delta[axis]
+        } else {
+            f32::INFINITY
+        };
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.0;
+
+    while distance <= max_distance {
+        if is_solid(block) {
+            return Some(VoxelHit { block, normal });
+        }
+
+        let axis = if next.x < next.y && next.x < next.z {
+            0
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+
+        distance = next[axis];
+        block[axis] += step[axis];
+        next[axis] += delta[axis];
+
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
+
+/// Offsets of the neighbour chunks sharing a border with the block at
+/// `local`, their meshes show its faces. None for blocks inside the chunk.
+fn touched_neighbours(local: IVec3) -> impl Iterator<Item = IVec3> {
+    NEIGHBOUR_OFFSETS
+        .into_iter()
+        .filter(move |&offset| !ChunkVoxels::contains(local + offset))
+}
+
+/// Loaded chunks keyed by their integer coordinates, streamed in
+/// around the camera and dropped once it moves away.
+///
+/// Voxels and meshes are generated on background threads, the render
+/// thread only uploads the finished meshes. A chunk is meshed against its
+/// loaded neighbours, so loading or unloading one remeshes those around it.
+pub struct ChunkManager {
+    pub settings: StreamingSettings,
+    chunks: HashMap<IVec3, Chunk>,
+    /// Waiting on their voxels
+    pending: HashSet<IVec3>,
+    /// Loaded chunks whose mesh is out of date
+    dirty: HashSet<IVec3>,
+    job_sender: Option<Sender<Job>>,
+    /// Edited voxels waiting to be written, loaded or not
+    unsaved: HashMap<IVec3, Arc<ChunkVoxels>>,
+    /// Written by a worker right now, at most one save per chunk is in
+    /// flight so an older copy never lands after a newer one
+    saving: HashSet<IVec3>,
+    result_receiver: Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    center: Option<IVec3>,
+    /// What the loaded chunks were meshed with
+    mesher: Mesher,
+    /// Squared distance from the center past which the memory cap
+    /// evicted chunks, nothing that far is requested again until
+    /// the camera enters another chunk
+    budget_limit: Option<i32>,
+    gpu_bytes: u64,
+}
+
+impl ChunkManager {
+    pub fn new(settings: StreamingSettings, store: ChunkStore) -> Self {
+        let (job_sender, job_receiver) = crossbeam_channel::unbounded();
+        let (result_sender, result_receiver) = crossbeam_channel::unbounded();
+
+        // The decoders and the render thread need the other cores
+        let worker_count = std::thread::available_parallelism()
+            .map(|n| n.get() / 2)
+            .unwrap_or(1)
+            .clamp(1, 4);
+
+        let workers = (0..worker_count)
+            .map(|i| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                let store = store.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("chunk-generator-{i}"))
+                    .spawn(move || run_generator(jobs, results, store))
+                    .expect("Failed to spawn a chunk generator")
+            })
+            .collect();
+
+        Self {
+            settings,
+            chunks: HashMap::new(),
+            pending: HashSet::new(),
+            dirty: HashSet::new(),
+            job_sender: Some(job_sender),
+            unsaved: HashMap::new(),
+            saving: HashSet::new(),
+            result_receiver,
+            workers,
+            center: None,
+            mesher: settings.mesher,
+            budget_limit: None,
+            gpu_bytes: 0,
+        }
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    pub fn loaded_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn gpu_bytes(&self) -> u64 {
+        self.gpu_bytes
+    }
+
+    /// Vertices and indices of every uploaded mesh together
+    pub fn mesh_counts(&self) -> (usize, usize) {
+        self.chunks
+            .values()
+            .filter_map(|chunk| chunk.mesh.as_ref())
+            .fold((0, 0), |(vertices, indices), mesh| {
+                (vertices + mesh.vertex_count, indices + mesh.index_count)
+            })
+    }
+
+    fn is_solid(&self, block: IVec3) -> bool {
+        let size = IVec3::splat(CHUNK_SIZE);
+
+        self.chunks
+            .get(&block.div_euclid(size))
+            .is_some_and(|chunk| chunk.voxels.get(block.rem_euclid(size)))
+    }
+
+    /// First solid block of a loaded chunk along the ray, `origin`
+    /// and `direction` are in the terrain's local space
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<VoxelHit> {
+        raycast_blocks(origin, direction, max_distance, |block| self.is_solid(block))
+    }
+
+    /// Sets one block and queues its chunk for saving, remeshing the chunk
+    /// and the neighbours that share the edited border. False when the
+    /// block is not loaded or already that way.
+    pub fn set_block(&mut self, block: IVec3, solid: bool) -> bool {
+        let size = IVec3::splat(CHUNK_SIZE);
+        let coord = block.div_euclid(size);
+        let local = block.rem_euclid(size);
+
+        let Some(chunk) = self.chunks.get_mut(&coord) else {
+            return false;
+        };
+
+        if chunk.voxels.get(local) == solid {
+            return false;
+        }
+
+        // Copies the voxels only while a mesh job still holds them
+        Arc::make_mut(&mut chunk.voxels).set(local, solid);
+
+        self.unsaved.insert(coord, chunk.voxels.clone());
+        self.dirty.insert(coord);
+
+        for offset in touched_neighbours(local) {
+            let neighbour = coord + offset;
+
+            if self.chunks.contains_key(&neighbour) {
+                self.dirty.insert(neighbour);
+            }
+        }
+
+        true
+    }
+
+    /// Picks along the ray and applies `edit`, ray in the terrain's local space
+    pub fn edit_block(&mut self, edit: BlockEdit, origin: Vec3, direction: Vec3) -> bool {
+        let Some(hit) = self.raycast(origin, direction, MAX_PICK_DISTANCE) else {
+            return false;
+        };
+
+        match edit {
+            BlockEdit::Remove => self.set_block(hit.block, false),
+            // Not into the block the ray started in
+            BlockEdit::Place if hit.normal == IVec3::ZERO => false,
+            BlockEdit::Place => self.set_block(hit.block + hit.normal, true),
+        }
+    }
+
+    fn in_range(&self, center: IVec3, coord: IVec3, radius: i32) -> bool {
+        in_range(&self.settings, self.budget_limit, center, coord, radius)
+    }
+
+    fn unload(&mut self, coord: IVec3) {
+        if let Some(chunk) = self.chunks.remove(&coord) {
+            self.gpu_bytes -= chunk.gpu_bytes();
+            self.mark_neighbours_dirty(coord);
+        }
+    }
+
+    fn mark_neighbours_dirty(&mut self, coord: IVec3) {
+        for offset in NEIGHBOUR_OFFSETS {
+            if self.chunks.contains_key(&(coord + offset)) {
+                self.dirty.insert(coord + offset);
+            }
+        }
+    }
+
+    /// Loads around `position`, in the terrain's local space, and unloads
+    /// what fell out of range. Call once per frame.
+    pub fn update(&mut self, ctx: &GPUCtx, position: Vec3, terrain: Mat4) {
+        puffin::profile_function!();
+
+        let center = streaming_center(position, &self.settings);
+
+        if self.center != Some(center) {
+            self.center = Some(center);
+            self.budget_limit = None;
+        }
+
+        if self.mesher != self.settings.mesher {
+            self.mesher = self.settings.mesher;
+            self.dirty.extend(self.chunks.keys().copied());
+        }
+
+        // One chunk of slack so the border doesn't load and unload
+        // every frame while the camera moves along it
+        let keep_radius = self.settings.radius + 1;
+
+        let far: Vec<IVec3> = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&coord| !self.in_range(center, coord, keep_radius))
+            .collect();
+
+        for coord in far {
+            self.unload(coord);
+        }
+
+        self.receive_finished(ctx, center, keep_radius, terrain);
+        self.enforce_budget(center);
+        self.request_meshes();
+        self.request_saves();
+        self.request_missing(center);
+    }
+
+    fn receive_finished(&mut self, ctx: &GPUCtx, center: IVec3, keep_radius: i32, terrain: Mat4) {
+        let mut uploads = 0;
+
+        while uploads < self.settings.uploads_per_frame {
+            let Ok(result) = self.result_receiver.try_recv() else {
+                break;
+            };
+
+            match result {
+                JobResult::Generated(coord, voxels) => {
+                    self.pending.remove(&coord);
+
+                    // Finished after the camera moved on
+                    if !self.in_range(center, coord, keep_radius)
+                        || self.chunks.contains_key(&coord)
+                    {
+                        continue;
+                    }
+
+                    let chunk = Chunk::new(ctx, coord, Arc::new(voxels), terrain);
+
+                    self.chunks.insert(coord, chunk);
+                    self.dirty.insert(coord);
+                    self.mark_neighbours_dirty(coord);
+                }
+                JobResult::Meshed(coord, revision, model) => {
+                    // Unloaded, or remeshed again since
+                    let Some(chunk) = self.chunks.get_mut(&coord) else {
+                        continue;
+                    };
+
+                    if chunk.mesh_revision != revision {
+                        continue;
+                    }
+
+                    self.gpu_bytes -= chunk.gpu_bytes();
+                    chunk.set_mesh(ctx, &model);
+                    self.gpu_bytes += chunk.gpu_bytes();
+
+                    uploads += 1;
+                }
+                JobResult::Saved(coord) => {
+                    self.saving.remove(&coord);
+                }
+            }
+        }
+    }
+
+    /// Drops the farthest chunks until the loaded ones fit the memory cap
+    fn enforce_budget(&mut self, center: IVec3) {
+        if self.gpu_bytes <= self.settings.max_gpu_bytes {
+            return;
+        }
+
+        let loaded = self
+            .chunks
+            .iter()
+            .map(|(&coord, chunk)| (coord, chunk.gpu_bytes()))
+            .collect();
+
+        for coord in over_budget(center, loaded, self.settings.max_gpu_bytes) {
+            let limit = distance_squared(center, coord) - 1;
+            self.budget_limit = Some(self.budget_limit.map_or(limit, |l| l.min(limit)));
+
+            self.unload(coord);
+        }
+    }
+
+    fn request_meshes(&mut self) {
+        let Some(ref job_sender) = self.job_sender else {
+            return;
+        };
+
+        for coord in self.dirty.drain() {
+            let Some(chunk) = self.chunks.get_mut(&coord) else {
+                continue;
+            };
+
+            chunk.mesh_revision += 1;
+
+            let job = Job::Mesh {
+                coord,
+                revision: chunk.mesh_revision,
+                mesher: self.mesher,
+                voxels: chunk.voxels.clone(),
+                neighbours: ChunkNeighbours(NEIGHBOUR_OFFSETS.map(|offset| {
+                    self.chunks
+                        .get(&(coord + offset))
+                        .map(|neighbour| neighbour.voxels.clone())
+                })),
+            };
+
+            if job_sender.send(job).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Writes happen on the workers, the render thread never waits on the disk
+    fn request_saves(&mut self) {
+        let Some(ref job_sender) = self.job_sender else {
+            return;
+        };
+
+        let ready: Vec<IVec3> = self
+            .unsaved
+            .keys()
+            .copied()
+            .filter(|coord| !self.saving.contains(coord))
+            .collect();
+
+        for coord in ready {
+            let Some(voxels) = self.unsaved.remove(&coord) else {
+                continue;
+            };
+
+            if job_sender.send(Job::Save(coord, voxels)).is_err() {
+                return;
+            }
+
+            self.saving.insert(coord);
+        }
+    }
+
+    fn request_missing(&mut self, center: IVec3) {
+        let Some(ref job_sender) = self.job_sender else {
+            return;
+        };
+
+        let max_pending = self.workers.len() * MAX_PENDING_PER_WORKER;
+
+        if self.pending.len() >= max_pending {
+            return;
+        }
+
+        // Reloading before the edits hit the disk would read the old file
+        let missing: Vec<IVec3> = chunks_in_range(&self.settings, self.budget_limit, center)
+            .into_iter()
+            .filter(|coord| {
+                !self.chunks.contains_key(coord)
+                    && !self.pending.contains(coord)
+                    && !self.unsaved.contains_key(coord)
+                    && !self.saving.contains(coord)
+            })
+            .collect();
+
+        for coord in missing.into_iter().take(max_pending - self.pending.len()) {
+            if job_sender.send(Job::Generate(coord)).is_err() {
+                return;
+            }
+
+            self.pending.insert(coord);
+        }
+    }
+}
+
+impl Drop for ChunkManager {
+    fn drop(&mut self) {
+        // An older copy still being written has to land before the last one
+        while !self.saving.is_empty() {
+            match self.result_receiver.recv() {
+                Ok(JobResult::Saved(coord)) => {
+                    self.saving.remove(&coord);
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+
+        // Queued after everything else, the workers drain the channel before exiting
+        for (coord, voxels) in self.unsaved.drain() {
+            if let Some(ref job_sender) = self.job_sender {
+                let _ = job_sender.send(Job::Save(coord, voxels));
+            }
+        }
+
+        // Closing the job channel lets the generators finish their last mesh and exit
+        self.job_sender = None;
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_at_chunk(chunk: IVec3) -> Vec3 {
+        (chunk.as_vec3() + 0.5) * CHUNK_WORLD_SIZE
+    }
+
+    #[test]
+    fn loads_within_the_radius_nearest_first() {
+        let settings = StreamingSettings::default();
+        let center = streaming_center(camera_at_chunk(IVec3::new(3, 2, -4)), &settings);
+
+        let coords = chunks_in_range(&settings, None, center);
+
+        assert_eq!(coords.first(), Some(&IVec3::new(3, 2, -4)));
+        assert!(coords
+            .windows(2)
+            .all(|pair| distance_squared(center, pair[0]) <= distance_squared(center, pair[1])));
+
+        for coord in &coords {
+            assert!(distance_squared(center, *coord) <= settings.radius * settings.radius);
+            assert!((settings.min_y..=settings.max_y).contains(&coord.y));
+        }
+
+        // The whole column under the camera and the border along X
+        let column = settings.min_y..=settings.max_y;
+        assert!(column.clone().all(|y| coords.contains(&IVec3::new(3, y, -4))));
+        assert!(coords.contains(&IVec3::new(3 + settings.radius, 2, -4)));
+        assert!(!coords.contains(&IVec3::new(3 + settings.radius + 1, 2, -4)));
+    }
+
+    #[test]
+    fn loaded_chunks_are_kept_one_chunk_past_the_radius() {
+        let settings = StreamingSettings::default();
+        let center = IVec3::new(0, 2, 0);
+        let border = IVec3::new(settings.radius + 1, 2, 0);
+
+        assert!(!in_range(&settings, None, center, border, settings.radius));
+        assert!(in_range(&settings, None, center, border, settings.radius + 1));
+        assert!(!in_range(&settings, None, center, border + IVec3::X, settings.radius + 1));
+    }
+
+    #[test]
+    fn camera_outside_the_rows_still_loads_the_nearest_row() {
+        let settings = StreamingSettings::default();
+        let x = settings.radius * 4;
+
+        let above = streaming_center(camera_at_chunk(IVec3::new(x, 100, 0)), &settings);
+        assert_eq!(above, IVec3::new(x, settings.max_y, 0));
+        assert_eq!(chunks_in_range(&settings, None, above)[0], above);
+
+        let below = streaming_center(camera_at_chunk(IVec3::new(0, -100, 0)), &settings);
+        assert_eq!(below, IVec3::new(0, settings.min_y, 0));
+        assert_eq!(chunks_in_range(&settings, None, below)[0], below);
+
+        // Nothing the camera passes over gets unloaded for being too high
+        let under = IVec3::new(x + 1, settings.max_y, 0);
+        assert!(in_range(&settings, None, above, under, settings.radius));
+    }
+
+    #[test]
+    fn budget_evicts_the_farthest_chunks_until_it_fits() {
+        let center = IVec3::ZERO;
+        let loaded = vec![
+            (IVec3::new(1, 0, 0), 10),
+            (IVec3::new(0, 0, 3), 10),
+            (IVec3::new(0, 2, 0), 10),
+        ];
+
+        assert!(over_budget(center, loaded.clone(), 30).is_empty());
+        assert_eq!(over_budget(center, loaded.clone(), 25), [IVec3::new(0, 0, 3)]);
+        assert_eq!(
+            over_budget(center, loaded, 10),
+            [IVec3::new(0, 0, 3), IVec3::new(0, 2, 0)]
+        );
+    }
+
+    #[test]
+    fn budget_limit_stops_reloading_evicted_chunks() {
+        let settings = StreamingSettings::default();
+        let center = IVec3::new(0, 2, 0);
+        let evicted = IVec3::new(0, 2, 4);
+
+        // What `enforce_budget` keeps after evicting `evicted`
+        let limit = distance_squared(center, evicted) - 1;
+        let coords = chunks_in_range(&settings, Some(limit), center);
+
+        assert!(!coords.contains(&evicted));
+        assert!(coords.iter().all(|&coord| distance_squared(center, coord) <= limit));
+        assert!(coords.contains(&IVec3::new(0, 2, 3)));
+    }
+
+    /// Block `target` is the only solid one
+    fn hit(origin: Vec3, direction: Vec3, target: IVec3) -> Option<VoxelHit> {
+        raycast_blocks(origin, direction, MAX_PICK_DISTANCE, |block| block == target)
+    }
+
+    /// Center of block `block` in the terrain's local space
+    fn block_center(block: IVec3) -> Vec3 {
+        block.as_vec3() * 2.0
+    }
+
+    #[test]
+    fn rays_along_each_axis_hit_the_face_they_enter() {
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+            for sign in [1, -1] {
+                let direction = axis * sign;
+                let target = direction * 5;
+
+                let found = hit(Vec3::ZERO, direction.as_vec3(), target).unwrap();
+                assert_eq!(found.block, target);
+                assert_eq!(found.normal, -direction, "ray along {direction}");
+
+                // From the far side, entering through the opposite face
+                let origin = block_center(direction * 10);
+                let found = hit(origin, -direction.as_vec3(), target).unwrap();
+                assert_eq!(found.block, target);
+                assert_eq!(found.normal, direction, "ray along {}", -direction);
+            }
+        }
+    }
+
+    #[test]
+    fn diagonal_rays_hit_the_face_they_enter() {
+        // Starts 0.6 of the way up block 0, so Y boundaries come first
+        let found = hit(Vec3::new(0.0, 0.2, 0.0), Vec3::new(1.0, 1.0, 0.0), IVec3::new(3, 3, 0));
+        assert_eq!(
+            found.map(|found| (found.block, found.normal)),
+            Some((IVec3::new(3, 3, 0), IVec3::NEG_X))
+        );
+
+        // Z boundaries come first, the target is entered along -X
+        let found = hit(
+            Vec3::new(0.2, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, -1.0),
+            IVec3::new(-2, 0, -2),
+        );
+        assert_eq!(
+            found.map(|found| (found.block, found.normal)),
+            Some((IVec3::new(-2, 0, -2), IVec3::X))
+        );
+    }
+
+    #[test]
+    fn rays_stop_at_the_pick_distance() {
+        let target = IVec3::X * 5;
+
+        assert!(raycast_blocks(Vec3::ZERO, Vec3::X, 8.0, |block| block == target).is_none());
+        assert!(raycast_blocks(Vec3::ZERO, Vec3::X, 10.0, |block| block == target).is_some());
+
+        // Starting inside a solid block has no face to place against
+        let inside = hit(block_center(target), Vec3::X, target).unwrap();
+        assert_eq!(inside.normal, IVec3::ZERO);
+    }
+
+    #[test]
+    fn border_edits_touch_exactly_the_neighbours_sharing_the_border() {
+        let last = CHUNK_SIZE - 1;
+
+        let inside: Vec<IVec3> = touched_neighbours(IVec3::new(5, 6, 7)).collect();
+        assert!(inside.is_empty());
+
+        let face: Vec<IVec3> = touched_neighbours(IVec3::new(last, 6, 7)).collect();
+        assert_eq!(face, [IVec3::X]);
+
+        let mut corner: Vec<IVec3> = touched_neighbours(IVec3::new(0, last, 0)).collect();
+        corner.sort_by_key(|offset| offset.to_array());
+        assert_eq!(corner, [IVec3::NEG_X, IVec3::NEG_Z, IVec3::Y]);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::chunk::ChunkVoxels;
+use glam::IVec3;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkStoreError {
+    #[error("Failed to access {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("{0} is not a chunk file")]
+    Corrupt(PathBuf),
+}
+
+/// Edited chunks on disk, one file per chunk holding all of its voxels.
+/// Chunks never edited have no file and come from the noise.
+#[derive(Clone)]
+pub struct ChunkStore {
+    dir: PathBuf,
+}
+
+impl ChunkStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, coord: IVec3) -> PathBuf {
+        self.dir
+            .join(format!("{}_{}_{}.chunk", coord.x, coord.y, coord.z))
+    }
+
+    pub fn load(&self, coord: IVec3) -> Result<Option<ChunkVoxels>, ChunkStoreError> {
+        let path = self.path(coord);
+
+        let bits = match fs::read(&path) {
+            Ok(bits) => bits,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ChunkStoreError::Io(path, e)),
+        };
+
+        ChunkVoxels::from_bits(&bits)
+            .map(Some)
+            .ok_or(ChunkStoreError::Corrupt(path))
+    }
+
+    pub fn save(&self, coord: IVec3, voxels: &ChunkVoxels) -> Result<(), ChunkStoreError> {
+        fs::create_dir_all(&self.dir).map_err(|e| ChunkStoreError::Io(self.dir.clone(), e))?;
+
+        let path = self.path(coord);
+
+        fs::write(&path, voxels.to_bits()).map_err(|e| ChunkStoreError::Io(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store of its own in the temp directory, emptied first
+    fn temp_store(name: &str) -> ChunkStore {
+        let dir = std::env::temp_dir()
+            .join("pony-renderer-tests")
+            .join(name);
+
+        let _ = fs::remove_dir_all(&dir);
+
+        ChunkStore::new(dir)
+    }
+
+    #[test]
+    fn saved_chunks_load_back_the_same() {
+        let store = temp_store("chunk_round_trip");
+        let coord = IVec3::new(-3, 2, 7);
+
+        let mut voxels = ChunkVoxels::empty();
+        for local in [IVec3::ZERO, IVec3::new(15, 15, 15), IVec3::new(3, 9, 1)] {
+            voxels.set(local, true);
+        }
+
+        store.save(coord, &voxels).unwrap();
+
+        let loaded = store.load(coord).unwrap().expect("the saved chunk");
+        assert_eq!(loaded.to_bits(), voxels.to_bits());
+
+        // Only the edited chunk has a file
+        assert!(store.load(coord + IVec3::X).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_chunk_files_are_corrupt() {
+        let store = temp_store("chunk_truncated");
+        let coord = IVec3::ZERO;
+
+        store.save(coord, &ChunkVoxels::empty()).unwrap();
+
+        let path = store.path(coord);
+        let bits = fs::read(&path).unwrap();
+        fs::write(&path, &bits[..bits.len() / 2]).unwrap();
+
+        assert!(matches!(store.load(coord), Err(ChunkStoreError::Corrupt(_))));
+    }
+}
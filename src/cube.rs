@@ -1,6 +1,8 @@
 use bitflags::bitflags;
 use noise::{NoiseFn, Perlin};
+use crate::chunk::{ChunkNeighbours, ChunkVoxels, CHUNK_SIZE};
 use crate::pipelines::quad_mesh;
+use glam::IVec3;
 
 pub struct GenModel {
     pub vertex_data: Vec<quad_mesh::Vertex>,
@@ -20,6 +22,7 @@ impl GenModel {
 
 #[rustfmt::skip]
 bitflags! {
+    #[derive(Clone, Copy)]
     pub struct BlockFaces: u32 {
         const None    = 0b000000;
         const Top     = 0b000001;
@@ -37,15 +40,18 @@ bitflags! {
     }
 }
 
+/// Faces of the box covering `extent` blocks from the block at `min`. The
+/// texture coordinates count blocks, so the texture repeats once per block.
 #[rustfmt::skip]
-pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32) {
+pub fn add_faces(faces: BlockFaces, model: &mut GenModel, min: IVec3, extent: IVec3) {
 
     let block_size = 1.0;
     let world_scalar = 2.0;
 
-    let x = x * world_scalar;
-    let y = y * world_scalar;
-    let z = z * world_scalar;
+    let lo = min.as_vec3() * world_scalar - block_size;
+    let hi = (min + extent - 1).as_vec3() * world_scalar + block_size;
+
+    let [w_x, w_y, w_z] = extent.to_array().map(|e| e as i8);
 
     let mut push_quad = || {
         model.index_data.extend([
@@ -63,10 +69,10 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Right) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y,  block_size + z], [0, 0, 1], [0, 0]),
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y,  block_size + z], [0, 0, 1], [1, 0]),
-            quad_mesh::Vertex::new([ block_size + x,  block_size + y,  block_size + z], [0, 0, 1], [1, 1]),
-            quad_mesh::Vertex::new([-block_size + x,  block_size + y,  block_size + z], [0, 0, 1], [0, 1]),
+            quad_mesh::Vertex::new([lo.x, lo.y, hi.z], [0, 0, 1], [0,   0  ]),
+            quad_mesh::Vertex::new([hi.x, lo.y, hi.z], [0, 0, 1], [w_x, 0  ]),
+            quad_mesh::Vertex::new([hi.x, hi.y, hi.z], [0, 0, 1], [w_x, w_y]),
+            quad_mesh::Vertex::new([lo.x, hi.y, hi.z], [0, 0, 1], [0,   w_y]),
         ]);
 
         push_quad();
@@ -74,10 +80,10 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Left) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([-block_size + x,  block_size + y, -block_size + z], [0, 0, -1], [1, 0]),
-            quad_mesh::Vertex::new([ block_size + x,  block_size + y, -block_size + z], [0, 0, -1], [0, 0]),
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y, -block_size + z], [0, 0, -1], [0, 1]),
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y, -block_size + z], [0, 0, -1], [1, 1]),
+            quad_mesh::Vertex::new([lo.x, hi.y, lo.z], [0, 0, -1], [w_x, 0  ]),
+            quad_mesh::Vertex::new([hi.x, hi.y, lo.z], [0, 0, -1], [0,   0  ]),
+            quad_mesh::Vertex::new([hi.x, lo.y, lo.z], [0, 0, -1], [0,   w_y]),
+            quad_mesh::Vertex::new([lo.x, lo.y, lo.z], [0, 0, -1], [w_x, w_y]),
         ]);
 
         push_quad();
@@ -85,10 +91,10 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Back) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y, -block_size + z], [1, 0, 0], [0, 0]),
-            quad_mesh::Vertex::new([ block_size + x,  block_size + y, -block_size + z], [1, 0, 0], [1, 0]),
-            quad_mesh::Vertex::new([ block_size + x,  block_size + y,  block_size + z], [1, 0, 0], [1, 1]),
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y,  block_size + z], [1, 0, 0], [0, 1]),
+            quad_mesh::Vertex::new([hi.x, lo.y, lo.z], [1, 0, 0], [0,   0  ]),
+            quad_mesh::Vertex::new([hi.x, hi.y, lo.z], [1, 0, 0], [w_y, 0  ]),
+            quad_mesh::Vertex::new([hi.x, hi.y, hi.z], [1, 0, 0], [w_y, w_z]),
+            quad_mesh::Vertex::new([hi.x, lo.y, hi.z], [1, 0, 0], [0,   w_z]),
         ]);
 
         push_quad();
@@ -96,10 +102,10 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Front) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y,  block_size + z], [-1, 0, 0], [1, 0]),
-            quad_mesh::Vertex::new([-block_size + x,  block_size + y,  block_size + z], [-1, 0, 0], [0, 0]),
-            quad_mesh::Vertex::new([-block_size + x,  block_size + y, -block_size + z], [-1, 0, 0], [0, 1]),
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y, -block_size + z], [-1, 0, 0], [1, 1]),
+            quad_mesh::Vertex::new([lo.x, lo.y, hi.z], [-1, 0, 0], [w_y, 0  ]),
+            quad_mesh::Vertex::new([lo.x, hi.y, hi.z], [-1, 0, 0], [0,   0  ]),
+            quad_mesh::Vertex::new([lo.x, hi.y, lo.z], [-1, 0, 0], [0,   w_z]),
+            quad_mesh::Vertex::new([lo.x, lo.y, lo.z], [-1, 0, 0], [w_y, w_z]),
         ]);
 
         push_quad();
@@ -107,10 +113,10 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Top) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([ block_size + x, block_size + y, -block_size + z], [0, 1, 0], [1, 0]),
-            quad_mesh::Vertex::new([-block_size + x, block_size + y, -block_size + z], [0, 1, 0], [0, 0]),
-            quad_mesh::Vertex::new([-block_size + x, block_size + y,  block_size + z], [0, 1, 0], [0, 1]),
-            quad_mesh::Vertex::new([ block_size + x, block_size + y,  block_size + z], [0, 1, 0], [1, 1]),
+            quad_mesh::Vertex::new([hi.x, hi.y, lo.z], [0, 1, 0], [w_x, 0  ]),
+            quad_mesh::Vertex::new([lo.x, hi.y, lo.z], [0, 1, 0], [0,   0  ]),
+            quad_mesh::Vertex::new([lo.x, hi.y, hi.z], [0, 1, 0], [0,   w_z]),
+            quad_mesh::Vertex::new([hi.x, hi.y, hi.z], [0, 1, 0], [w_x, w_z]),
         ]);
 
         push_quad();
@@ -118,50 +124,51 @@ pub fn add_faces(faces: BlockFaces, model: &mut GenModel, x: f32, y: f32, z: f32
 
     if faces.contains(BlockFaces::Bottom) {
         model.vertex_data.extend([
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y,  block_size + z], [0, -1, 0], [0, 0]),
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y,  block_size + z], [0, -1, 0], [1, 0]),
-            quad_mesh::Vertex::new([-block_size + x, -block_size + y, -block_size + z], [0, -1, 0], [1, 1]),
-            quad_mesh::Vertex::new([ block_size + x, -block_size + y, -block_size + z], [0, -1, 0], [0, 1]),
+            quad_mesh::Vertex::new([hi.x, lo.y, hi.z], [0, -1, 0], [0,   0  ]),
+            quad_mesh::Vertex::new([lo.x, lo.y, hi.z], [0, -1, 0], [w_x, 0  ]),
+            quad_mesh::Vertex::new([lo.x, lo.y, lo.z], [0, -1, 0], [w_x, w_z]),
+            quad_mesh::Vertex::new([hi.x, lo.y, lo.z], [0, -1, 0], [0,   w_z]),
         ]);
 
         push_quad();
     }
 }
 
-pub fn generate_full_mesh(x: i32, y: i32, z: i32) -> quad_mesh::ModelBundle {
+/// Solid blocks of the chunk at `coord`, from the terrain noise
+pub fn generate_voxels(coord: IVec3) -> ChunkVoxels {
     let perlin = Perlin::default();
-    let size = 16;
-
-    let mut model = GenModel::new();
+    let origin = coord * CHUNK_SIZE;
 
-    let noise_cache_size = 16usize * 16usize * 16usize;
-    let mut data: Vec<bool> = vec![false; noise_cache_size];
+    let mut voxels = ChunkVoxels::empty();
 
-    let index = |x, y, z| x as usize + y as usize * 16usize + z as usize * 16usize * 16usize;
-
-    for i_x in 0..size {
-        for i_y in 0..size {
-            for i_z in 0..size {
+    for i_x in 0..CHUNK_SIZE {
+        for i_y in 0..CHUNK_SIZE {
+            for i_z in 0..CHUNK_SIZE {
                 let noise_scale = 0.1;
 
                 let noise = perlin.get([
-                    (i_x + x) as f64 * noise_scale,
-                    (i_y + y) as f64 * noise_scale,
-                    (i_z + z) as f64 * noise_scale,
+                    (i_x + origin.x) as f64 * noise_scale,
+                    (i_y + origin.y) as f64 * noise_scale,
+                    (i_z + origin.z) as f64 * noise_scale,
                 ]) > 0.2;
 
-                data[index(i_x, i_y, i_z)] = noise;
+                voxels.set(IVec3::new(i_x, i_y, i_z), noise);
             }
         }
     }
 
-    for i_x in 0..size {
-        for i_y in 0..size {
-            for i_z in 0..size {
-                let get_at = |x, y, z| {
-                    let val = data[index(x, y, z)];
-                    val
-                };
+    voxels
+}
+
+/// Faces of every solid block that touch air, blocks along the
+/// border are checked against the neighbouring chunks
+pub fn generate_mesh(voxels: &ChunkVoxels, neighbours: &ChunkNeighbours) -> quad_mesh::ModelBundle {
+    let mut model = GenModel::new();
+
+    for i_x in 0..CHUNK_SIZE {
+        for i_y in 0..CHUNK_SIZE {
+            for i_z in 0..CHUNK_SIZE {
+                let get_at = |x, y, z| neighbours.solid_at(voxels, IVec3::new(x, y, z));
 
                 let mut face = BlockFaces::None;
 
@@ -171,39 +178,92 @@ pub fn generate_full_mesh(x: i32, y: i32, z: i32) -> quad_mesh::ModelBundle {
                     continue;
                 }
 
-                // TODO: do chunk stitching
+                face.set(BlockFaces::Top, !get_at(i_x + 0, i_y + 1, i_z + 0));
+                face.set(BlockFaces::Bottom, !get_at(i_x + 0, i_y - 1, i_z + 0));
+                face.set(BlockFaces::Left, !get_at(i_x + 0, i_y + 0, i_z - 1));
+                face.set(BlockFaces::Right, !get_at(i_x + 0, i_y + 0, i_z + 1));
+                face.set(BlockFaces::Front, !get_at(i_x - 1, i_y + 0, i_z + 0));
+                face.set(BlockFaces::Back, !get_at(i_x + 1, i_y + 0, i_z + 0));
 
-                if i_y != 15 {
-                    face.set(BlockFaces::Top, !get_at(i_x + 0, i_y + 1, i_z + 0));
-                }
+                add_faces(face, &mut model, IVec3::new(i_x, i_y, i_z), IVec3::ONE);
+            }
+        }
+    }
 
-                if i_y != 0 {
-                    face.set(BlockFaces::Bottom, !get_at(i_x + 0, i_y - 1, i_z + 0));
-                }
+    quad_mesh::ModelBundle {
+        vertex_data: model.vertex_data,
+        index_data: model.index_data,
+    }
+}
 
-                if i_z != 0 {
-                    face.set(BlockFaces::Left, !get_at(i_x + 0, i_y + 0, i_z - 1));
-                }
+/// Each face direction, the way it points and the two axes spanning its plane
+const FACE_PLANES: [(BlockFaces, IVec3, IVec3, IVec3); 6] = [
+    (BlockFaces::Back, IVec3::X, IVec3::Y, IVec3::Z),
+    (BlockFaces::Front, IVec3::NEG_X, IVec3::Y, IVec3::Z),
+    (BlockFaces::Top, IVec3::Y, IVec3::X, IVec3::Z),
+    (BlockFaces::Bottom, IVec3::NEG_Y, IVec3::X, IVec3::Z),
+    (BlockFaces::Right, IVec3::Z, IVec3::X, IVec3::Y),
+    (BlockFaces::Left, IVec3::NEG_Z, IVec3::X, IVec3::Y),
+];
+
+/// Same faces as `generate_mesh`, but exposed faces sharing a plane are
+/// merged into rectangles. All blocks use the one texture, so any two
+/// neighbouring faces can merge.
+pub fn generate_greedy_mesh(
+    voxels: &ChunkVoxels,
+    neighbours: &ChunkNeighbours,
+) -> quad_mesh::ModelBundle {
+    let mut model = GenModel::new();
 
-                if i_z != 15 {
-                    face.set(BlockFaces::Right, !get_at(i_x + 0, i_y + 0, i_z + 1));
-                }
+    let size = CHUNK_SIZE as usize;
+    let mut mask = vec![false; size * size];
 
-                if i_x != 0 {
-                    face.set(BlockFaces::Front, !get_at(i_x - 1, i_y + 0, i_z + 0));
-                }
+    for (face, normal, u_axis, v_axis) in FACE_PLANES {
+        let depth_axis = normal.abs();
+
+        for depth in 0..CHUNK_SIZE {
+            // Which faces of this layer touch air
+            for v in 0..CHUNK_SIZE {
+                for u in 0..CHUNK_SIZE {
+                    let block = depth_axis * depth + u_axis * u + v_axis * v;
 
-                if i_x != 15 {
-                    face.set(BlockFaces::Back, !get_at(i_x + 1, i_y + 0, i_z + 0));
+                    mask[u as usize + v as usize * size] = neighbours.solid_at(voxels, block)
+                        && !neighbours.solid_at(voxels, block + normal);
                 }
+            }
 
-                add_faces(
-                    face,
-                    &mut model,
-                    i_x as f32,
-                    i_y as f32,
-                    i_z as f32,
-                );
+            for v in 0..size {
+                let mut u = 0;
+
+                while u < size {
+                    if !mask[u + v * size] {
+                        u += 1;
+                        continue;
+                    }
+
+                    // Widest run along u, then as many rows of it as fit along v
+                    let mut width = 1;
+                    while u + width < size && mask[u + width + v * size] {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    while v + height < size && (u..u + width).all(|i| mask[i + (v + height) * size])
+                    {
+                        height += 1;
+                    }
+
+                    for row in v..v + height {
+                        mask[u + row * size..u + width + row * size].fill(false);
+                    }
+
+                    let min = depth_axis * depth + u_axis * u as i32 + v_axis * v as i32;
+                    let extent = depth_axis + u_axis * width as i32 + v_axis * height as i32;
+
+                    add_faces(face, &mut model, min, extent);
+
+                    u += width;
+                }
             }
         }
     }
@@ -213,3 +273,165 @@ pub fn generate_full_mesh(x: i32, y: i32, z: i32) -> quad_mesh::ModelBundle {
         index_data: model.index_data,
     }
 }
+
+/// How chunk voxels are turned into triangles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mesher {
+    /// One quad per exposed block face
+    PerFace,
+    Greedy,
+}
+
+impl Mesher {
+    pub fn generate(
+        self,
+        voxels: &ChunkVoxels,
+        neighbours: &ChunkNeighbours,
+    ) -> quad_mesh::ModelBundle {
+        match self {
+            Mesher::PerFace => generate_mesh(voxels, neighbours),
+            Mesher::Greedy => generate_greedy_mesh(voxels, neighbours),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::NEIGHBOUR_OFFSETS;
+    use std::sync::Arc;
+
+    fn full_chunk() -> Arc<ChunkVoxels> {
+        let mut voxels = ChunkVoxels::empty();
+
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    voxels.set(IVec3::new(x, y, z), true);
+                }
+            }
+        }
+
+        Arc::new(voxels)
+    }
+
+    /// Blocks a quad spans, merged quads count every face they replace
+    fn quad_area(quad: &[quad_mesh::Vertex]) -> f32 {
+        let [x, y, z] = [0, 1, 2].map(|axis| {
+            let (min, max) = quad.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v.pos[axis]), max.max(v.pos[axis]))
+            });
+
+            (max - min) / 2.0
+        });
+
+        // One of the three is zero, the quad lies flat in that axis
+        x * y + y * z + z * x
+    }
+
+    fn area_where(model: &quad_mesh::ModelBundle, f: impl Fn(&quad_mesh::Vertex) -> bool) -> f32 {
+        model
+            .vertex_data
+            .chunks_exact(4)
+            .filter(|quad| quad.iter().all(&f))
+            .map(quad_area)
+            .sum()
+    }
+
+    /// Blocks covered by the faces on the +X border of the chunk
+    fn border_area(model: &quad_mesh::ModelBundle) -> f32 {
+        let border = (CHUNK_SIZE - 1) as f32 * 2.0 + 1.0;
+
+        area_where(model, |v| v.normal[0] == 1.0 && v.pos[0] == border)
+    }
+
+    #[test]
+    fn loaded_neighbour_hides_the_border_faces() {
+        let voxels = full_chunk();
+
+        let mut neighbours = ChunkNeighbours::default();
+        neighbours.0[0] = Some(full_chunk());
+
+        for mesher in [Mesher::PerFace, Mesher::Greedy] {
+            let model = mesher.generate(&voxels, &neighbours);
+
+            assert_eq!(border_area(&model), 0.0, "{mesher:?}");
+        }
+    }
+
+    #[test]
+    fn unloaded_neighbour_keeps_the_border_faces() {
+        let voxels = full_chunk();
+        let neighbours = ChunkNeighbours::default();
+
+        for mesher in [Mesher::PerFace, Mesher::Greedy] {
+            let model = mesher.generate(&voxels, &neighbours);
+
+            assert_eq!(
+                border_area(&model),
+                (CHUNK_SIZE * CHUNK_SIZE) as f32,
+                "{mesher:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_mesh_covers_the_same_faces_with_fewer_quads() {
+        let coords = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(1, 0, -2),
+            IVec3::new(-3, 0, 4),
+            IVec3::new(5, 1, 5),
+            IVec3::new(-7, -1, -7),
+        ];
+
+        let mut per_face_total = (0, 0);
+        let mut greedy_total = (0, 0);
+
+        for coord in coords {
+            let voxels = generate_voxels(coord);
+
+            let mut neighbours = ChunkNeighbours::default();
+            for (slot, offset) in neighbours.0.iter_mut().zip(NEIGHBOUR_OFFSETS) {
+                *slot = Some(Arc::new(generate_voxels(coord + offset)));
+            }
+
+            let per_face = Mesher::PerFace.generate(&voxels, &neighbours);
+            let greedy = Mesher::Greedy.generate(&voxels, &neighbours);
+
+            per_face_total.0 += per_face.vertex_data.len();
+            per_face_total.1 += per_face.index_data.len();
+            greedy_total.0 += greedy.vertex_data.len();
+            greedy_total.1 += greedy.index_data.len();
+
+            // Noise terrain always has flat runs to merge
+            if !per_face.vertex_data.is_empty() {
+                assert!(
+                    greedy.vertex_data.len() < per_face.vertex_data.len(),
+                    "chunk {coord}: nothing merged"
+                );
+            }
+
+            for normal in NEIGHBOUR_OFFSETS {
+                let facing = |v: &quad_mesh::Vertex| v.normal[..3] == normal.as_vec3().to_array();
+
+                assert_eq!(
+                    area_where(&greedy, facing),
+                    area_where(&per_face, facing),
+                    "chunk {coord}, faces along {normal}"
+                );
+            }
+        }
+
+        // Vertices and indices at least halve over the sampled terrain
+        assert!(per_face_total.0 > 0, "the sampled chunks have no faces");
+        assert!(
+            greedy_total.0 * 2 <= per_face_total.0,
+            "vertices: {greedy_total:?} greedy against {per_face_total:?} per face"
+        );
+        assert!(
+            greedy_total.1 * 2 <= per_face_total.1,
+            "indices: {greedy_total:?} greedy against {per_face_total:?} per face"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::camera::Camera;
+use crate::gpu::{GPUCtx, GPUTexture, ShaderLibrary};
+use crate::pipelines::canvas_quad;
+use egui_wgpu::wgpu;
+use glam::*;
+
+/// Half the height of the quad in world units, the width follows the canvas
+const QUAD_HALF_HEIGHT: f32 = 50.0;
+
+/// Faces the same way as the video quads, centered on the entity
+fn generate_quad(width: u32, height: u32) -> canvas_quad::ModelBundle {
+    let h = QUAD_HALF_HEIGHT;
+    let w = h * width as f32 / height as f32;
+
+    canvas_quad::ModelBundle {
+        vertex_data: vec![
+            canvas_quad::Vertex::new([0.0, -h, w], [1, 1]),
+            canvas_quad::Vertex::new([0.0, h, w], [1, 0]),
+            canvas_quad::Vertex::new([0.0, h, -w], [0, 0]),
+            canvas_quad::Vertex::new([0.0, -h, -w], [0, 1]),
+        ],
+        index_data: vec![0, 1, 2, 2, 3, 0],
+    }
+}
+
+/// The Skia canvas texture on a quad in the world
+pub struct CanvasQuadDemo {
+    bind_group_0: canvas_quad::BindGroup0,
+    bind_group_1: canvas_quad::BindGroup1,
+    pipeline: canvas_quad::Pipeline,
+    format: wgpu::TextureFormat,
+    vertex_format: canvas_quad::VertexFormat,
+}
+
+impl CanvasQuadDemo {
+    pub fn create(
+        ctx: &GPUCtx,
+        config: &wgpu::SurfaceConfiguration,
+        shaders: &mut ShaderLibrary,
+        texture: &GPUTexture,
+    ) -> Self {
+        let bind_group_0 = canvas_quad::BindGroup0::create(ctx, texture);
+        let bind_group_1 = canvas_quad::BindGroup1::create(ctx, Mat4::IDENTITY);
+        let pipeline = shaders.build(canvas_quad::SHADER, |source| {
+            canvas_quad::Pipeline::create(ctx, config.format, source)
+        });
+
+        let size = texture.size();
+        let vertex_format =
+            canvas_quad::VertexFormat::create(ctx, &generate_quad(size.width, size.height));
+
+        Self {
+            bind_group_0,
+            bind_group_1,
+            pipeline,
+            format: config.format,
+            vertex_format,
+        }
+    }
+
+    pub fn on_shaders_changed(
+        &mut self,
+        ctx: &GPUCtx,
+        shaders: &mut ShaderLibrary,
+        changed: &[&str],
+    ) {
+        if !changed.contains(&canvas_quad::SHADER) {
+            return;
+        }
+
+        shaders.rebuild(canvas_quad::SHADER, &mut self.pipeline, |source| {
+            canvas_quad::Pipeline::create(ctx, self.format, source)
+        });
+    }
+
+    pub fn update_location(&self, ctx: &GPUCtx, mat: Mat4) {
+        self.bind_group_1.update_transform(ctx, mat);
+    }
+
+    pub fn setup_dynamic_camera(&self, ctx: &GPUCtx, camera: &Camera) {
+        self.bind_group_0.update_globals(ctx, camera);
+    }
+
+    pub fn render_static(&self, pass: &mut wgpu::RenderPass<'static>) {
+        pass.set_pipeline(&self.pipeline.pipeline);
+        pass.set_bind_group(0, &self.bind_group_0.bind_group, &[]);
+        pass.set_bind_group(1, &self.bind_group_1.bind_group, &[]);
+        pass.set_index_buffer(
+            self.vertex_format.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.set_vertex_buffer(0, self.vertex_format.vertex_buffer.slice(..));
+        pass.draw_indexed(0..self.vertex_format.index_count, 0, 0..1);
+    }
+}
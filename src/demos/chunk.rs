@@ -1,72 +1,127 @@
 use crate::camera::Camera;
-use crate::chunk::Chunk;
-use crate::gpu::GPUCtx;
+use crate::chunk_manager::{BlockEditRequest, ChunkManager, StreamingSettings};
+use crate::chunk_store::ChunkStore;
+use crate::fs_utils::data_dir;
+use crate::gpu::{GPUCtx, ShaderLibrary};
 use crate::paint_utils::create_texels;
 use crate::pipelines::quad_mesh;
 use egui_wgpu::wgpu;
+use glam::{Mat4, Vec3};
 
 pub struct ChunksDemo {
-    chunks: Vec<Chunk>,
+    pub manager: ChunkManager,
     bind_group: quad_mesh::BindGroup0,
     pipeline: quad_mesh::Pipeline,
+    format: wgpu::TextureFormat,
+    transform: Mat4,
 }
 
 impl ChunksDemo {
-    pub fn create(config: &wgpu::SurfaceConfiguration, ctx: &GPUCtx) -> Self {
+    pub fn create(
+        config: &wgpu::SurfaceConfiguration,
+        ctx: &GPUCtx,
+        shaders: &mut ShaderLibrary,
+    ) -> Self {
         let fractal_size = 256u32;
         let texels = create_texels(fractal_size as usize);
 
         let bind_group = quad_mesh::BindGroup0::create(ctx, fractal_size, texels);
-        let pipeline = quad_mesh::Pipeline::create(ctx, config.format);
+        let pipeline = shaders.build(quad_mesh::SHADER, |source| {
+            quad_mesh::Pipeline::create(ctx, config.format, source)
+        });
 
         ChunksDemo {
-            chunks: vec![],
+            manager: ChunkManager::new(
+                StreamingSettings::default(),
+                ChunkStore::new(data_dir().join("chunks")),
+            ),
             bind_group,
             pipeline,
+            format: config.format,
+            transform: Mat4::IDENTITY,
         }
     }
 
-    pub fn spawn_chunk(&mut self, ctx: &GPUCtx) {
-        for x in 0..8 {
-            for y in 0..6 {
-                for z in 0..8 {
-                    let chunk = Chunk::new(ctx, x, y, z);
-                    self.chunks.push(chunk);
-                }
-            }
+    /// Moves every chunk with the terrain, see `scene::Render`
+    pub fn set_transform(&mut self, ctx: &GPUCtx, transform: Mat4) {
+        self.transform = transform;
+
+        for chunk in self.manager.chunks() {
+            chunk.set_terrain_transform(ctx, transform);
         }
     }
 
+    /// Rebuilds the pipeline when its shader is among `changed`
+    pub fn on_shaders_changed(
+        &mut self,
+        ctx: &GPUCtx,
+        shaders: &mut ShaderLibrary,
+        changed: &[&str],
+    ) {
+        if !changed.contains(&quad_mesh::SHADER) {
+            return;
+        }
+
+        shaders.rebuild(quad_mesh::SHADER, &mut self.pipeline, |source| {
+            quad_mesh::Pipeline::create(ctx, self.format, source)
+        });
+    }
+
+    /// Streams chunks around `camera_position`, given in world space
+    pub fn update(&mut self, ctx: &GPUCtx, camera_position: Vec3) {
+        let local = self.transform.inverse().transform_point3(camera_position);
+
+        self.manager.update(ctx, local, self.transform);
+    }
+
+    pub fn edit_block(&mut self, request: BlockEditRequest) {
+        let inverse = self.transform.inverse();
+
+        self.manager.edit_block(
+            request.edit,
+            inverse.transform_point3(request.origin),
+            inverse.transform_vector3(request.direction),
+        );
+    }
+
     pub fn setup_dynamic_camera(&self, ctx: &GPUCtx, camera: &Camera) {
         self.bind_group.update_globals(ctx, camera);
     }
 
     #[allow(unused)]
     pub fn render<'a>(&'a mut self, pass: &mut wgpu::RenderPass<'a>) {
-        for chunk in &self.chunks {
+        for chunk in self.manager.chunks() {
+            let Some(ref mesh) = chunk.mesh else {
+                continue;
+            };
+
             pass.set_pipeline(&self.pipeline.pipeline);
             pass.set_bind_group(0, &self.bind_group.bind_group, &[]);
             pass.set_bind_group(1, &chunk.bind_group.bind_group, &[]);
             pass.set_index_buffer(
-                chunk.vertex_format.index_buffer.slice(..),
+                mesh.vertex_format.index_buffer.slice(..),
                 wgpu::IndexFormat::Uint32,
             );
-            pass.set_vertex_buffer(0, chunk.vertex_format.vertex_buffer.slice(..));
-            pass.draw_indexed(0..chunk.index_count as u32, 0, 0..1);
+            pass.set_vertex_buffer(0, mesh.vertex_format.vertex_buffer.slice(..));
+            pass.draw_indexed(0..mesh.index_count as u32, 0, 0..1);
         }
     }
 
     pub fn render_static(&mut self, pass: &mut wgpu::RenderPass<'static>) {
-        for chunk in &self.chunks {
+        for chunk in self.manager.chunks() {
+            let Some(ref mesh) = chunk.mesh else {
+                continue;
+            };
+
             pass.set_pipeline(&self.pipeline.pipeline);
             pass.set_bind_group(0, &self.bind_group.bind_group, &[]);
             pass.set_bind_group(1, &chunk.bind_group.bind_group, &[]);
             pass.set_index_buffer(
-                chunk.vertex_format.index_buffer.slice(..),
+                mesh.vertex_format.index_buffer.slice(..),
                 wgpu::IndexFormat::Uint32,
             );
-            pass.set_vertex_buffer(0, chunk.vertex_format.vertex_buffer.slice(..));
-            pass.draw_indexed(0..chunk.index_count as u32, 0, 0..1);
+            pass.set_vertex_buffer(0, mesh.vertex_format.vertex_buffer.slice(..));
+            pass.draw_indexed(0..mesh.index_count as u32, 0, 0..1);
         }
     }
 }
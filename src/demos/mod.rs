@@ -1,4 +1,6 @@
+mod canvas;
 mod chunk;
 mod video;
+pub use canvas::*;
 pub use chunk::*;
 pub use video::*;
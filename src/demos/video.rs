@@ -1,26 +1,37 @@
 use crate::camera::Camera;
-use crate::gpu::GPUCtx;
+use crate::frontend::{Filmstrip, QuickView};
+use crate::gpu::{GPUCtx, ShaderLibrary, StagingUpload};
 use crate::pipelines::video;
-use bytemuck::NoUninit;
+use crate::shared::Shared;
 use egui_wgpu::wgpu;
 use glam::*;
-use crate::video::Resolution;
+use std::path::PathBuf;
+use crate::video::{
+    AudioOutput, ColorInfo, DecoderPool, NullSink, Resolution, VideoError, VideoHandle,
+};
 
 pub struct VideoDemo {
     bind_group_0: video::BindGroup0,
     bind_group_1: video::BindGroup1,
     pipeline: video::Pipeline,
+    format: wgpu::TextureFormat,
     vertex_format: video::VertexFormat,
     width: u32,
     height: u32,
+    chroma_resolution: Resolution,
+    color: ColorInfo,
+    upload: StagingUpload,
 }
 
-fn generate_quad() -> video::ModelBundle {
+/// Gap between two quads placed side by side
+const QUAD_SPACING: f32 = 8.0;
+
+fn generate_quad(slot: usize) -> video::ModelBundle {
     let s = 0.05;
     let h = 1080.0 * s;
     let w = 1920.0 * s;
 
-    let (x, y, z) = (3.0, 0.0, 0.0);
+    let (x, y, z) = (3.0, 0.0, slot as f32 * (2.0 * w + QUAD_SPACING));
 
     video::ModelBundle {
         vertex_data: vec![
@@ -39,44 +50,86 @@ impl VideoDemo {
         self.bind_group_1.update_transform(ctx, mat);
     }
 
-    pub fn create(ctx: &GPUCtx, config: &wgpu::SurfaceConfiguration) -> Self {
+    /// `slot` places the quad next to the ones created before it
+    pub fn create(
+        ctx: &GPUCtx,
+        config: &wgpu::SurfaceConfiguration,
+        shaders: &mut ShaderLibrary,
+        slot: usize,
+    ) -> Self {
         let bind_group_0 = video::BindGroup0::create(ctx, 512, 521);
         let bind_group_1 = video::BindGroup1::create(ctx, Mat4::IDENTITY);
-        let pipeline = video::Pipeline::create(ctx, config.format);
+        let pipeline = shaders.build(video::SHADER, |source| {
+            video::Pipeline::create(ctx, config.format, source)
+        });
 
-        let vertex_format = video::VertexFormat::create(ctx, &generate_quad());
+        let vertex_format = video::VertexFormat::create(ctx, &generate_quad(slot));
 
         Self {
             width: 512,
             height: 512,
+            chroma_resolution: Resolution {
+                width: 256,
+                height: 256,
+            },
+            color: ColorInfo::default(),
+            upload: StagingUpload::new(),
             bind_group_0,
             bind_group_1,
             pipeline,
+            format: config.format,
             vertex_format,
         }
     }
 
-    pub fn update_texture<T: NoUninit>(
-        &self,
+    pub fn on_shaders_changed(
+        &mut self,
         ctx: &GPUCtx,
-        y_data: &[T],
-        u_data: &[T],
-        v_data: &[T],
+        shaders: &mut ShaderLibrary,
+        changed: &[&str],
     ) {
+        if !changed.contains(&video::SHADER) {
+            return;
+        }
+
+        shaders.rebuild(video::SHADER, &mut self.pipeline, |source| {
+            video::Pipeline::create(ctx, self.format, source)
+        });
+    }
+
+    pub fn update_texture(&mut self, ctx: &GPUCtx, y_data: &[u8], u_data: &[u8], v_data: &[u8]) {
         self.bind_group_0
-            .update_texture(ctx, y_data, u_data, v_data);
+            .update_texture(ctx, &mut self.upload, y_data, u_data, v_data);
     }
 
-    pub(crate) fn check_resize(&mut self, ctx: &GPUCtx, resolution: Resolution) {
-        if (resolution.width != self.width as usize) || (resolution.height != self.height as usize)
+    pub(crate) fn check_resize(
+        &mut self,
+        ctx: &GPUCtx,
+        resolution: Resolution,
+        chroma_resolution: Resolution,
+    ) {
+        if (resolution.width != self.width as usize)
+            || (resolution.height != self.height as usize)
+            || (chroma_resolution != self.chroma_resolution)
         {
             self.bind_group_0.resize_textures(
                 ctx,
                 resolution.width as u32,
                 resolution.height as u32,
+                chroma_resolution.width as u32,
+                chroma_resolution.height as u32,
             );
             self.width = resolution.width as u32;
             self.height = resolution.height as u32;
+            self.chroma_resolution = chroma_resolution;
+        }
+    }
+
+    /// Streams can change colorimetry mid way, the uniform follows the frames
+    pub fn check_color(&mut self, ctx: &GPUCtx, color: ColorInfo) {
+        if color != self.color {
+            self.bind_group_0.update_color(ctx, &color);
+            self.color = color;
         }
     }
 
@@ -109,3 +162,73 @@ impl VideoDemo {
         pass.draw_indexed(0..self.vertex_format.index_count, 0, 0..1);
     }
 }
+
+/// A video file, the quad it plays on and the tab with its controls
+pub struct VideoSource {
+    pub path: PathBuf,
+    pub handle: Result<Shared<VideoHandle>, VideoError>,
+    pub demo: Shared<VideoDemo>,
+    pub view: Shared<QuickView>,
+    pub filmstrip: Shared<Filmstrip>,
+}
+
+impl VideoSource {
+    pub fn open(
+        ctx: &GPUCtx,
+        config: &wgpu::SurfaceConfiguration,
+        pool: &DecoderPool,
+        shaders: &mut ShaderLibrary,
+        path: PathBuf,
+        slot: usize,
+        audio_output: &AudioOutput,
+    ) -> Self {
+        // A sink that can't be created mutes the video instead of failing it
+        let sink = audio_output.create_sink(&path).unwrap_or_else(|e| {
+            eprintln!(
+                "Failed to open the audio output for {}: {e}",
+                path.display()
+            );
+            Box::new(NullSink)
+        });
+
+        Self {
+            handle: VideoHandle::create_with_sink(pool, path.clone(), sink),
+            path,
+            demo: Shared::new(VideoDemo::create(ctx, config, shaders, slot)),
+            view: QuickView::new(),
+            filmstrip: Shared::new(Filmstrip::default()),
+        }
+    }
+
+    pub fn tick(&self) {
+        if let Ok(ref handle) = self.handle {
+            handle.tick();
+        }
+    }
+
+    /// Uploads the frame due for presentation, if the handle has a new one
+    pub fn upload_current_frame(&self, ctx: &GPUCtx) {
+        let Some(frame) = self
+            .handle
+            .as_ref()
+            .ok()
+            .and_then(|handle| handle.get_current_frame())
+        else {
+            return;
+        };
+
+        self.demo.with(|demo| {
+            demo.check_resize(
+                ctx,
+                frame.resolution,
+                frame.data.chroma_resolution(frame.resolution),
+            );
+
+            demo.check_color(ctx, frame.color);
+
+            let planes = frame.data.planes();
+
+            demo.update_texture(ctx, &planes.y_plane, &planes.u_plane, &planes.v_plane);
+        });
+    }
+}
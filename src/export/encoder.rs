@@ -0,0 +1,309 @@
+use ffmpeg_next::{
+    codec, encoder, format, format::Pixel, frame, software::scaling, Dictionary, Packet, Rational,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Cannot create {0}: {1}")]
+    Output(PathBuf, ffmpeg_next::Error),
+    #[error("No {0:?} encoder in this ffmpeg build")]
+    EncoderNotFound(codec::Id),
+    #[error("Failed to create the encoder: {0}")]
+    Encoder(ffmpeg_next::Error),
+    #[error("Failed to encode a frame: {0}")]
+    Encode(ffmpeg_next::Error),
+    #[error("Failed to convert the frame: {0}")]
+    Scaler(ffmpeg_next::Error),
+    #[error("Failed to write the output: {0}")]
+    Mux(ffmpeg_next::Error),
+    #[error("Expected a frame of {expected} bytes, got {got}")]
+    FrameSize { expected: usize, got: usize },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Failed to spawn the export thread: {0}")]
+    Spawn(String),
+    #[error("The export thread stopped")]
+    Disconnected,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// H.264 in an MP4 container
+    Mp4,
+    /// VP9 in a WebM container
+    WebM,
+    /// One PNG per frame, keeps the alpha channel
+    PngSequence,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Mp4,
+        ExportFormat::WebM,
+        ExportFormat::PngSequence,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Mp4 => "MP4 (H.264)",
+            ExportFormat::WebM => "WebM (VP9)",
+            ExportFormat::PngSequence => "PNG sequence",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Mp4 => "mp4",
+            ExportFormat::WebM => "webm",
+            ExportFormat::PngSequence => "png",
+        }
+    }
+
+    fn codec_id(&self) -> codec::Id {
+        match self {
+            ExportFormat::Mp4 => codec::Id::H264,
+            ExportFormat::WebM => codec::Id::VP9,
+            ExportFormat::PngSequence => codec::Id::PNG,
+        }
+    }
+
+    fn pixel_format(&self) -> Pixel {
+        match self {
+            ExportFormat::Mp4 | ExportFormat::WebM => Pixel::YUV420P,
+            ExportFormat::PngSequence => Pixel::RGBA,
+        }
+    }
+
+    /// Highest CRF of the codec, the worst quality it accepts
+    fn max_crf(&self) -> u32 {
+        match self {
+            ExportFormat::Mp4 => 51,
+            ExportFormat::WebM => 63,
+            ExportFormat::PngSequence => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// From 0, the smallest file, to 100, visually lossless
+    pub quality: u8,
+}
+
+impl ExportSettings {
+    fn encoder_options(&self) -> Dictionary<'static> {
+        let mut options = Dictionary::new();
+
+        let quality = self.quality.min(100) as u32;
+        let crf = self.format.max_crf() * (100 - quality) / 100;
+
+        match self.format {
+            ExportFormat::Mp4 => {
+                options.set("preset", "medium");
+                options.set("crf", &crf.to_string());
+            }
+            ExportFormat::WebM => {
+                // Constant quality mode, the bitrate is only a cap
+                options.set("crf", &crf.to_string());
+                options.set("b", "0");
+            }
+            ExportFormat::PngSequence => {}
+        }
+
+        options
+    }
+
+    /// 4:2:0 formats need even dimensions
+    fn encoded_size(&self) -> (u32, u32) {
+        match self.format.pixel_format() {
+            Pixel::YUV420P => ((self.width & !1).max(2), (self.height & !1).max(2)),
+            _ => (self.width, self.height),
+        }
+    }
+}
+
+/// Encodes straight RGBA frames into a file with ffmpeg.
+///
+/// `path` is the output file, or the directory for a PNG sequence.
+pub struct VideoEncoder {
+    octx: format::context::Output,
+    encoder: encoder::Video,
+    scaler: Option<scaling::Context>,
+    rgba: frame::Video,
+    converted: frame::Video,
+    settings: ExportSettings,
+    time_base: Rational,
+    stream_time_base: Rational,
+    frame_index: i64,
+}
+
+impl VideoEncoder {
+    pub fn create(path: &Path, settings: ExportSettings) -> Result<Self, ExportError> {
+        let export_format = settings.format;
+
+        let mut octx = match export_format {
+            ExportFormat::PngSequence => {
+                std::fs::create_dir_all(path)?;
+
+                let pattern = path.join("frame_%05d.png");
+
+                format::output_as(&pattern, "image2")
+                    .map_err(|e| ExportError::Output(pattern.clone(), e))?
+            }
+            _ => format::output(path).map_err(|e| ExportError::Output(path.to_path_buf(), e))?,
+        };
+
+        let codec = encoder::find(export_format.codec_id())
+            .ok_or(ExportError::EncoderNotFound(export_format.codec_id()))?;
+
+        let global_header = octx.format().flags().contains(format::Flags::GLOBAL_HEADER);
+
+        let time_base = Rational(1, settings.fps.max(1) as i32);
+        let (width, height) = settings.encoded_size();
+
+        let mut encoder = codec::context::Context::new_with_codec(codec)
+            .encoder()
+            .video()
+            .map_err(ExportError::Encoder)?;
+
+        encoder.set_width(width);
+        encoder.set_height(height);
+        encoder.set_format(export_format.pixel_format());
+        encoder.set_time_base(time_base);
+        encoder.set_frame_rate(Some(time_base.invert()));
+
+        if global_header {
+            encoder.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+
+        let encoder = encoder
+            .open_with(settings.encoder_options())
+            .map_err(ExportError::Encoder)?;
+
+        {
+            let mut stream = octx.add_stream(codec).map_err(ExportError::Encoder)?;
+            stream.set_parameters(&encoder);
+            stream.set_time_base(time_base);
+        }
+
+        octx.write_header().map_err(ExportError::Mux)?;
+
+        // The muxer is free to pick another time base in write_header
+        let stream_time_base = octx
+            .stream(0)
+            .map_or(time_base, |stream| stream.time_base());
+
+        let scaler = if export_format.pixel_format() == Pixel::RGBA
+            && (width, height) == (settings.width, settings.height)
+        {
+            None
+        } else {
+            Some(
+                scaling::Context::get(
+                    Pixel::RGBA,
+                    settings.width,
+                    settings.height,
+                    export_format.pixel_format(),
+                    width,
+                    height,
+                    scaling::Flags::BICUBIC,
+                )
+                .map_err(ExportError::Scaler)?,
+            )
+        };
+
+        Ok(Self {
+            octx,
+            encoder,
+            scaler,
+            rgba: frame::Video::new(Pixel::RGBA, settings.width, settings.height),
+            converted: frame::Video::empty(),
+            settings,
+            time_base,
+            stream_time_base,
+            frame_index: 0,
+        })
+    }
+
+    /// Encodes one frame of `width * height` RGBA pixels, rows tightly packed
+    pub fn push_frame(&mut self, rgba: &[u8]) -> Result<(), ExportError> {
+        puffin::profile_function!();
+
+        let row = self.settings.width as usize * 4;
+        let expected = row * self.settings.height as usize;
+
+        if rgba.len() != expected {
+            return Err(ExportError::FrameSize {
+                expected,
+                got: rgba.len(),
+            });
+        }
+
+        let stride = self.rgba.stride(0);
+        let data = self.rgba.data_mut(0);
+
+        for (y, source) in rgba.chunks_exact(row).enumerate() {
+            data[y * stride..y * stride + row].copy_from_slice(source);
+        }
+
+        let frame = match self.scaler {
+            Some(ref mut scaler) => {
+                scaler
+                    .run(&self.rgba, &mut self.converted)
+                    .map_err(ExportError::Scaler)?;
+                &mut self.converted
+            }
+            None => &mut self.rgba,
+        };
+
+        frame.set_pts(Some(self.frame_index));
+        self.frame_index += 1;
+
+        self.encoder
+            .send_frame(frame)
+            .map_err(ExportError::Encode)?;
+
+        self.write_packets()
+    }
+
+    /// Flushes the encoder and finalizes the file
+    pub fn finish(mut self) -> Result<u64, ExportError> {
+        self.encoder.send_eof().map_err(ExportError::Encode)?;
+        self.write_packets()?;
+
+        self.octx.write_trailer().map_err(ExportError::Mux)?;
+
+        Ok(self.frame_index as u64)
+    }
+
+    /// Writes every packet the encoder has ready. EAGAIN means it wants
+    /// another frame first and EOF that it was drained, anything else failed.
+    fn write_packets(&mut self) -> Result<(), ExportError> {
+        let mut packet = Packet::empty();
+
+        loop {
+            match self.encoder.receive_packet(&mut packet) {
+                Ok(()) => {}
+                Err(ffmpeg_next::Error::Other {
+                    errno: ffmpeg_next::error::EAGAIN,
+                })
+                | Err(ffmpeg_next::Error::Eof) => return Ok(()),
+                Err(e) => return Err(ExportError::Encode(e)),
+            }
+
+            packet.set_stream(0);
+            packet.rescale_ts(self.time_base, self.stream_time_base);
+            packet
+                .write_interleaved(&mut self.octx)
+                .map_err(ExportError::Mux)?;
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod encoder;
+pub use encoder::*;
+mod recorder;
+pub use recorder::*;
@@ -0,0 +1,125 @@
+use crossbeam_channel::Sender;
+use std::{path::PathBuf, thread::JoinHandle, time::Instant};
+
+use super::{ExportError, ExportSettings, VideoEncoder};
+
+/// Frames waiting for the encoder before `push_frame` blocks
+const QUEUED_FRAMES: usize = 8;
+
+/// Runs a `VideoEncoder` on its own thread, so the render
+/// thread only pays for copying the frames it hands over.
+///
+/// Frames are placed on the export timeline by when they were
+/// captured, so the file plays back at wall-clock speed whatever
+/// the render rate was.
+pub struct ExportRecorder {
+    pub path: PathBuf,
+    /// Each frame with how many export frames it fills
+    frame_sender: Option<Sender<(Vec<u8>, u64)>>,
+    thread: Option<JoinHandle<Result<u64, ExportError>>>,
+    frames_sent: u64,
+    started: Instant,
+    fps: u32,
+}
+
+impl ExportRecorder {
+    pub fn start(path: PathBuf, settings: ExportSettings) -> Result<Self, ExportError> {
+        let (frame_sender, frame_receiver) =
+            crossbeam_channel::bounded::<(Vec<u8>, u64)>(QUEUED_FRAMES);
+
+        let (init_result_sender, init_result_receiver) =
+            crossbeam_channel::bounded::<Result<(), ExportError>>(1);
+
+        let thread_path = path.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("video-export".into())
+            .spawn(move || {
+                let mut encoder = match VideoEncoder::create(&thread_path, settings) {
+                    Ok(encoder) => encoder,
+                    Err(e) => {
+                        let _ = init_result_sender.send(Err(e));
+                        return Ok(0);
+                    }
+                };
+
+                let _ = init_result_sender.send(Ok(()));
+
+                for (frame, count) in frame_receiver.iter() {
+                    for _ in 0..count {
+                        encoder.push_frame(&frame)?;
+                    }
+                }
+
+                encoder.finish()
+            })
+            .map_err(|e| ExportError::Spawn(e.to_string()))?;
+
+        init_result_receiver
+            .recv()
+            .map_err(|_| ExportError::Disconnected)??;
+
+        Ok(Self {
+            path,
+            frame_sender: Some(frame_sender),
+            thread: Some(thread),
+            frames_sent: 0,
+            started: Instant::now(),
+            fps: settings.fps.max(1),
+        })
+    }
+
+    pub fn frames_sent(&self) -> u64 {
+        self.frames_sent
+    }
+
+    /// Queues a frame of straight RGBA pixels, `settings.width` by `settings.height`.
+    ///
+    /// The frame fills every export frame due since the previous one, it is
+    /// repeated when rendering is slower than `fps` and skipped when faster.
+    /// Repeats are made on the encoder thread, after a long hitch the render
+    /// thread still sends a single frame.
+    pub fn push_frame(&mut self, rgba: Vec<u8>, captured_at: Instant) -> Result<(), ExportError> {
+        let elapsed = captured_at.saturating_duration_since(self.started);
+
+        // The first export frame is due right away
+        let due = (elapsed.as_secs_f64() * self.fps as f64) as u64 + 1;
+
+        if due <= self.frames_sent {
+            return Ok(());
+        }
+
+        self.send(rgba, due - self.frames_sent)
+    }
+
+    fn send(&mut self, rgba: Vec<u8>, count: u64) -> Result<(), ExportError> {
+        let Some(ref sender) = self.frame_sender else {
+            return Err(ExportError::Disconnected);
+        };
+
+        if sender.send((rgba, count)).is_err() {
+            // The encoder gave up, its error says why
+            self.frame_sender = None;
+            return Err(self.join().err().unwrap_or(ExportError::Disconnected));
+        }
+
+        self.frames_sent += count;
+
+        Ok(())
+    }
+
+    /// Waits for the encoder to write the queued frames and close
+    /// the file, returns how many frames ended up in it
+    pub fn finish(mut self) -> Result<u64, ExportError> {
+        self.frame_sender = None;
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<u64, ExportError> {
+        let Some(thread) = self.thread.take() else {
+            return Err(ExportError::Disconnected);
+        };
+
+        thread.join().map_err(|_| ExportError::Disconnected)?
+    }
+}
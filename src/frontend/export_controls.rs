@@ -0,0 +1,142 @@
+use std::{
+    path::PathBuf,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use egui::{ComboBox, DragValue, Slider, Ui};
+
+use crate::export::{ExportFormat, ExportRecorder, ExportSettings};
+
+/// Export settings plus a start/stop button, the owner
+/// feeds frames with `push_frame` while recording
+pub struct ExportControls {
+    pub settings: ExportSettings,
+    output_dir: PathBuf,
+    recorder: Option<ExportRecorder>,
+    status: Option<Result<String, String>>,
+}
+
+impl ExportControls {
+    pub fn new(width: u32, height: u32, output_dir: PathBuf) -> Self {
+        Self {
+            settings: ExportSettings {
+                format: ExportFormat::Mp4,
+                width,
+                height,
+                fps: 30,
+                quality: 80,
+            },
+            output_dir,
+            recorder: None,
+            status: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Hands a frame captured at `captured_at` to the encoder,
+    /// an encoder error stops the recording
+    pub fn push_frame(&mut self, rgba: Vec<u8>, captured_at: Instant) {
+        let Some(ref mut recorder) = self.recorder else {
+            return;
+        };
+
+        if let Err(e) = recorder.push_frame(rgba, captured_at) {
+            self.recorder = None;
+            self.status = Some(Err(e.to_string()));
+        }
+    }
+
+    fn output_path(&self) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_secs())
+            .unwrap_or(0);
+
+        // A PNG sequence goes into a directory of its own
+        match self.settings.format {
+            ExportFormat::PngSequence => self.output_dir.join(format!("export_{timestamp}")),
+            format => self
+                .output_dir
+                .join(format!("export_{timestamp}.{}", format.extension())),
+        }
+    }
+
+    fn start(&mut self) {
+        if let Err(e) = std::fs::create_dir_all(&self.output_dir) {
+            self.status = Some(Err(e.to_string()));
+            return;
+        }
+
+        match ExportRecorder::start(self.output_path(), self.settings) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                self.status = None;
+            }
+            Err(e) => self.status = Some(Err(e.to_string())),
+        }
+    }
+
+    fn stop(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+
+        let path = recorder.path.clone();
+
+        self.status = Some(
+            recorder
+                .finish()
+                .map(|frames| format!("Wrote {frames} frames to {}", path.display()))
+                .map_err(|e| e.to_string()),
+        );
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.add_enabled_ui(!self.is_recording(), |ui| {
+            ComboBox::from_label("Format")
+                .selected_text(self.settings.format.label())
+                .show_ui(ui, |ui| {
+                    for format in ExportFormat::ALL {
+                        ui.selectable_value(&mut self.settings.format, format, format.label());
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                ui.label("FPS");
+                ui.add(DragValue::new(&mut self.settings.fps).range(1..=120));
+            });
+
+            if self.settings.format != ExportFormat::PngSequence {
+                ui.add(Slider::new(&mut self.settings.quality, 0..=100).text("Quality"));
+            }
+        });
+
+        match self.recorder {
+            Some(ref recorder) => {
+                ui.label(format!("Recording, {} frames", recorder.frames_sent()));
+
+                if ui.button("Stop recording").clicked() {
+                    self.stop();
+                }
+            }
+            None => {
+                if ui.button("Start recording").clicked() {
+                    self.start();
+                }
+            }
+        }
+
+        match self.status {
+            Some(Ok(ref message)) => {
+                ui.label(message);
+            }
+            Some(Err(ref error)) => {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+            None => {}
+        }
+    }
+}
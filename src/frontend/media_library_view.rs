@@ -0,0 +1,199 @@
+use crate::frontend::{TabHandle, TabView};
+use crate::shared::Shared;
+use crate::video::{AudioOutput, MediaInfo, MediaLibrary};
+use egui::{ColorImage, ComboBox, TextureHandle, TextureOptions, Ui};
+use fancy_duration::FancyDuration;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Where `AudioOutput::WavFiles` writes when picked in the view
+const WAV_OUTPUT_DIR: &str = "audio_out";
+
+pub struct MediaLibraryView {
+    library: Shared<MediaLibrary>,
+    thumbnails: HashMap<PathBuf, TextureHandle>,
+    new_directory: String,
+}
+
+impl MediaLibraryView {
+    pub fn new(library: Shared<MediaLibrary>) -> Shared<Self> {
+        Self {
+            library,
+            thumbnails: HashMap::new(),
+            new_directory: String::new(),
+        }
+        .into()
+    }
+
+    /// Uploads the thumbnail of `info` the first time it is shown
+    fn thumbnail(&mut self, ui: &Ui, info: &MediaInfo) -> Option<TextureHandle> {
+        if let Some(texture) = self.thumbnails.get(&info.path) {
+            return Some(texture.clone());
+        }
+
+        let thumbnail = info.thumbnail.as_ref()?;
+
+        let texture = ui.ctx().load_texture(
+            info.path.to_string_lossy(),
+            ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width, thumbnail.height],
+                &thumbnail.rgba,
+            ),
+            TextureOptions::LINEAR,
+        );
+
+        self.thumbnails.insert(info.path.clone(), texture.clone());
+
+        Some(texture)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+impl TabView for Shared<MediaLibraryView> {
+    fn title(&self, tab: &TabHandle) -> String {
+        format!("Media Library {}", tab.node.0)
+    }
+
+    fn content(&mut self, ui: &mut Ui) {
+        self.with(|this| {
+            let library = this.library.clone();
+
+            library.with(|library| library.poll());
+
+            ui.collapsing("Directories", |ui| {
+                let mut removed = None;
+
+                library.with_ref(|library| {
+                    for (index, directory) in library.directories().iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("x").clicked() {
+                                removed = Some(index);
+                            }
+                            ui.label(directory.display().to_string());
+                        });
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut this.new_directory);
+
+                    if ui.button("Add").clicked() && !this.new_directory.trim().is_empty() {
+                        let directory = PathBuf::from(this.new_directory.trim());
+                        this.new_directory.clear();
+
+                        library.with(|library| library.add_directory(directory));
+                    }
+                });
+
+                if let Some(index) = removed {
+                    library.with(|library| library.remove_directory(index));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Rescan").clicked() {
+                    library.with(|library| library.rescan());
+                }
+
+                if library.with_ref(|library| library.is_scanning()) {
+                    ui.spinner();
+                    ui.label("Scanning...");
+                }
+            });
+
+            library.with(|library| {
+                let wav_files = AudioOutput::WavFiles(PathBuf::from(WAV_OUTPUT_DIR));
+
+                ComboBox::from_label("Audio output")
+                    .selected_text(library.audio_output.label())
+                    .show_ui(ui, |ui| {
+                        for output in [AudioOutput::Mute, wav_files] {
+                            let label = output.label();
+                            ui.selectable_value(&mut library.audio_output, output, label);
+                        }
+                    });
+
+                if let AudioOutput::WavFiles(ref directory) = library.audio_output {
+                    ui.label(format!(
+                        "Videos opened now play into {}",
+                        directory.display()
+                    ));
+                }
+            });
+
+            let recent = library.with_ref(|library| library.recent().to_vec());
+
+            if !recent.is_empty() {
+                ui.collapsing("Recent", |ui| {
+                    for path in recent {
+                        if ui.link(file_name(&path)).clicked() {
+                            library.with(|library| library.request_open(path.clone()));
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+
+            let mut opened = None;
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                library.with_ref(|library| {
+                    for entry in library.entries() {
+                        ui.horizontal(|ui| {
+                            let info = match entry.info {
+                                Ok(ref info) => info,
+                                Err(ref error) => {
+                                    ui.label(file_name(&entry.path));
+                                    ui.colored_label(egui::Color32::LIGHT_RED, error.to_string());
+                                    return;
+                                }
+                            };
+
+                            match this.thumbnail(ui, info) {
+                                Some(texture) => {
+                                    ui.image(&texture);
+                                }
+                                None => {
+                                    ui.label("No preview");
+                                }
+                            }
+
+                            ui.vertical(|ui| {
+                                ui.strong(file_name(&info.path));
+                                ui.label(format!(
+                                    "{}x{} {} - {}",
+                                    info.resolution.width,
+                                    info.resolution.height,
+                                    info.codec,
+                                    FancyDuration(info.duration)
+                                ));
+
+                                if ui.button("Open").clicked() {
+                                    opened = Some(info.path.clone());
+                                }
+                            });
+                        });
+                    }
+                });
+            });
+
+            if let Some(path) = opened {
+                library.with(|library| library.request_open(path));
+            }
+        });
+    }
+
+    fn as_tab_handle(
+        &self,
+        surface: egui_dock::SurfaceIndex,
+        node: egui_dock::NodeIndex,
+    ) -> TabHandle {
+        TabHandle::new(self.clone().into(), surface, node)
+    }
+}
@@ -1,20 +1,30 @@
 mod custom_view;
+mod export_controls;
 mod fancy_view;
+mod media_library_view;
 mod quick_view;
 mod regular_view;
+mod scene_view;
+mod shader_view;
 mod world_view;
 mod code_view;
+mod video_controls;
 
 pub use custom_view::CustomView;
+pub use export_controls::ExportControls;
 use egui_dock::{NodeIndex, SurfaceIndex};
 use enum_dispatch::enum_dispatch;
 pub use fancy_view::FancyView;
+pub use media_library_view::MediaLibraryView;
 pub use quick_view::QuickView;
 pub use regular_view::RegularView;
+pub use scene_view::SceneView;
+pub use shader_view::ShaderView;
 pub use world_view::WorldView;
 pub use code_view::CodeView;
+pub use video_controls::{video_controls, Filmstrip};
 
-use crate::{egui_tools::EguiRenderer, gpu::GPUCtx, shared::{Shared, WeakShared}};
+use crate::{egui_tools::EguiRenderer, gpu::GPUCtx, scene::Scene, shared::{Shared, WeakShared}};
 
 #[enum_dispatch(TabInstance)]
 pub trait TabView {
@@ -33,6 +43,9 @@ pub enum TabInstance {
     CustomView(Shared<CustomView>),
     QuickView(Shared<QuickView>),
     CodeView(Shared<CodeView>),
+    MediaLibraryView(Shared<MediaLibraryView>),
+    ShaderView(Shared<ShaderView>),
+    SceneView(Shared<SceneView>),
 }
 
 /// Doesn't cover all tabs because not all tab types
@@ -83,6 +96,7 @@ impl<'a> HandleList<'a> {
         &mut self,
         ctx: &GPUCtx,
         egui_renderer: &mut EguiRenderer,
+        scene: &Shared<Scene>,
         render_passes: &mut Vec<WeakShared<WorldView>>,
         egui_passes: &mut Vec<WeakShared<WorldView>>,
     ) {
@@ -97,7 +111,7 @@ impl<'a> HandleList<'a> {
                     self.handles.push(tab.as_tab_handle(surface, node));
                 }
                 PendingTabRequest::WorldView(surface, node) => {
-                    let tab = WorldView::new(ctx, egui_renderer, render_passes, egui_passes);
+                    let tab = WorldView::new(ctx, egui_renderer, scene.clone(), render_passes, egui_passes);
                     self.handles.push(tab.as_tab_handle(surface, node));
                 }
             }
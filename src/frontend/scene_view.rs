@@ -0,0 +1,128 @@
+use crate::frontend::{TabHandle, TabView};
+use crate::scene::{EntityId, Scene};
+use crate::shared::Shared;
+use egui::collapsing_header::CollapsingState;
+use egui::{DragValue, Ui};
+use glam::{EulerRot, Quat, Vec3};
+
+/// Entity tree of the scene, picks the entity the
+/// gizmo edits and shows its local transform
+pub struct SceneView {
+    scene: Shared<Scene>,
+}
+
+impl SceneView {
+    pub fn new(scene: Shared<Scene>) -> Shared<Self> {
+        Self { scene }.into()
+    }
+}
+
+fn entity_row(ui: &mut Ui, scene: &Scene, id: EntityId, selected: &mut Option<EntityId>) {
+    let entity = scene.get(id);
+
+    let label = match entity.renderable {
+        Some(ref renderable) => format!("{} ({})", entity.name, renderable.label()),
+        None => entity.name.clone(),
+    };
+
+    if ui.selectable_label(*selected == Some(id), label).clicked() {
+        *selected = Some(id);
+    }
+}
+
+fn entity_tree(ui: &mut Ui, scene: &Scene, id: EntityId, selected: &mut Option<EntityId>) {
+    let children = scene.get(id).children();
+
+    if children.is_empty() {
+        entity_row(ui, scene, id, selected);
+        return;
+    }
+
+    CollapsingState::load_with_default_open(ui.ctx(), ui.make_persistent_id(id), true)
+        .show_header(ui, |ui| entity_row(ui, scene, id, selected))
+        .body(|ui| {
+            for &child in children {
+                entity_tree(ui, scene, child, selected);
+            }
+        });
+}
+
+fn vec3_row(ui: &mut Ui, label: &str, value: &mut Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+
+        let x = ui.add(DragValue::new(&mut value.x).speed(speed)).changed();
+        let y = ui.add(DragValue::new(&mut value.y).speed(speed)).changed();
+        let z = ui.add(DragValue::new(&mut value.z).speed(speed)).changed();
+
+        x || y || z
+    })
+    .inner
+}
+
+impl TabView for Shared<SceneView> {
+    fn title(&self, tab: &TabHandle) -> String {
+        format!("Scene {}", tab.node.0)
+    }
+
+    fn content(&mut self, ui: &mut Ui) {
+        self.with_ref(|this| {
+            this.scene.with(|scene| {
+                let mut selected = scene.selected;
+
+                egui::ScrollArea::vertical()
+                    .max_height(ui.available_height() * 0.6)
+                    .show(ui, |ui| {
+                        for &root in scene.roots() {
+                            entity_tree(ui, scene, root, &mut selected);
+                        }
+                    });
+
+                scene.selected = selected;
+
+                ui.separator();
+
+                let Some(selected) = selected else {
+                    ui.label("Nothing selected");
+                    return;
+                };
+
+                let mut transform = scene.get(selected).transform;
+
+                // Edited as degrees, the quaternion is rebuilt on change
+                let (x, y, z) = transform.rotation.to_euler(EulerRot::XYZ);
+                let mut rotation = Vec3::new(x, y, z) * (180.0 / std::f32::consts::PI);
+
+                ui.strong(&scene.get(selected).name);
+
+                let mut changed = vec3_row(ui, "Translation", &mut transform.translation, 0.5);
+
+                if vec3_row(ui, "Rotation", &mut rotation, 1.0) {
+                    let rotation = rotation * (std::f32::consts::PI / 180.0);
+                    transform.rotation =
+                        Quat::from_euler(EulerRot::XYZ, rotation.x, rotation.y, rotation.z);
+                    changed = true;
+                }
+
+                changed |= vec3_row(ui, "Scale", &mut transform.scale, 0.01);
+
+                if ui.button("Reset").clicked() {
+                    transform = Default::default();
+                    changed = true;
+                }
+
+                if changed {
+                    scene.get_mut(selected).transform = transform;
+                }
+            });
+        });
+    }
+
+    fn as_tab_handle(
+        &self,
+        surface: egui_dock::SurfaceIndex,
+        node: egui_dock::NodeIndex,
+    ) -> TabHandle {
+        TabHandle::new(self.clone().into(), surface, node)
+    }
+}
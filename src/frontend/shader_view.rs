@@ -0,0 +1,59 @@
+use crate::frontend::{TabHandle, TabView};
+use crate::gpu::ShaderLibrary;
+use crate::shared::Shared;
+use egui::{Color32, RichText, Ui};
+
+/// Lists the runtime loaded shaders and the naga
+/// diagnostics of the ones that failed to reload
+pub struct ShaderView {
+    shaders: Shared<ShaderLibrary>,
+}
+
+impl ShaderView {
+    pub fn new(shaders: Shared<ShaderLibrary>) -> Shared<Self> {
+        Self { shaders }.into()
+    }
+}
+
+impl TabView for Shared<ShaderView> {
+    fn title(&self, tab: &TabHandle) -> String {
+        format!("Shaders {}", tab.node.0)
+    }
+
+    fn content(&mut self, ui: &mut Ui) {
+        self.with_ref(|this| {
+            this.shaders.with_ref(|shaders| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for file in shaders.files() {
+                        ui.horizontal(|ui| {
+                            ui.strong(file.name);
+                            ui.label(format!("v{}", file.generation));
+
+                            match file.error {
+                                Some(_) => ui.colored_label(Color32::LIGHT_RED, "Error"),
+                                None => ui.colored_label(Color32::LIGHT_GREEN, "OK"),
+                            };
+                        });
+
+                        ui.label(file.path.display().to_string());
+
+                        if let Some(ref error) = file.error {
+                            ui.label("Still running the last good version");
+                            ui.label(RichText::new(error.to_string()).monospace());
+                        }
+
+                        ui.separator();
+                    }
+                });
+            });
+        });
+    }
+
+    fn as_tab_handle(
+        &self,
+        surface: egui_dock::SurfaceIndex,
+        node: egui_dock::NodeIndex,
+    ) -> TabHandle {
+        TabHandle::new(self.clone().into(), surface, node)
+    }
+}
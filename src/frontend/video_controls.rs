@@ -0,0 +1,257 @@
+use std::{path::Path, time::Duration};
+
+use egui::{ColorImage, ProgressBar, Sense, Slider, Stroke, TextureHandle, TextureOptions, Ui};
+use fancy_duration::FancyDuration;
+
+use crate::{
+    shared::Shared,
+    video::{PlaySpeed, VideoError, VideoHandle, VideoIndex},
+};
+
+/// Thumbnails of the `VideoIndex`, uploaded once and drawn under the scrubber
+#[derive(Default)]
+pub struct Filmstrip {
+    textures: Vec<(Duration, TextureHandle)>,
+}
+
+impl Filmstrip {
+    /// Draws the strip with a playhead at `progress`,
+    /// returns the time of the thumbnail clicked
+    fn ui(&mut self, ui: &mut Ui, index: &VideoIndex, progress: f64) -> Option<Duration> {
+        if self.textures.len() != index.thumbnails.len() {
+            self.textures = index
+                .thumbnails
+                .iter()
+                .enumerate()
+                .map(|(slot, (time, thumbnail))| {
+                    let texture = ui.ctx().load_texture(
+                        format!("filmstrip-{slot}"),
+                        ColorImage::from_rgba_unmultiplied(
+                            [thumbnail.width, thumbnail.height],
+                            &thumbnail.rgba,
+                        ),
+                        TextureOptions::LINEAR,
+                    );
+
+                    (*time, texture)
+                })
+                .collect();
+        }
+
+        let first = index.thumbnails.first()?;
+
+        let width = ui.available_width() / self.textures.len() as f32;
+        let height = width * first.1.height as f32 / first.1.width as f32;
+
+        let mut clicked = None;
+
+        let strip = ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+
+            for (time, texture) in &self.textures {
+                let response = ui.add(
+                    egui::Image::new(texture)
+                        .fit_to_exact_size(egui::vec2(width, height))
+                        .sense(Sense::click()),
+                );
+
+                if response.clicked() {
+                    clicked = Some(*time);
+                }
+            }
+        });
+
+        let rect = strip.response.rect;
+        let x = rect.left() + rect.width() * progress.clamp(0.0, 1.0) as f32;
+
+        ui.painter()
+            .vline(x, rect.y_range(), Stroke::new(2.0, egui::Color32::WHITE));
+
+        clicked
+    }
+}
+
+/// Transport controls and decoder stats of a single video source
+pub fn video_controls(
+    ui: &mut Ui,
+    path: &Path,
+    value: &Result<Shared<VideoHandle>, VideoError>,
+    filmstrip: &mut Filmstrip,
+) {
+    if let Some(name) = path.file_name() {
+        ui.heading(name.to_string_lossy());
+    }
+
+    let value = match value {
+        Ok(value) => value,
+        Err(error) => {
+            ui.heading("Cannot open file");
+            ui.colored_label(egui::Color32::LIGHT_RED, error.to_string());
+            return;
+        }
+    };
+
+    value.with(|video| {
+        ui.label(format!("Movie avg framerate: {:.2}FPS", video.fps));
+
+        ui.label(format!(
+            "Movie length: {}",
+            FancyDuration(video.total_duration)
+        ));
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("Pause").clicked() {
+            value.pause();
+        }
+        if ui.button("Play").clicked() {
+            value.play();
+        }
+        if ui.button("Stop").clicked() {
+            value.stop();
+        }
+        if ui.button("SkipBackward").clicked() {
+            value.skip_backward();
+        }
+        if ui.button("SkipForward").clicked() {
+            value.skip_forward();
+        }
+        if ui.button("Previous Frame").clicked() {
+            value.step_backward();
+        }
+        if ui.button("Next Frame").clicked() {
+            value.step_forward();
+        }
+    });
+
+    ui.label(format!("State: {:?}", value.get_play_state()));
+
+    let prog = value.get_realtime_progress();
+    let mut new_prog = prog;
+
+    ui.horizontal(|ui| {
+        let available_width = ui.available_width();
+
+        ui.spacing_mut().slider_width = available_width;
+
+        ui.add(Slider::new(&mut new_prog, 0.0..=1.0).show_value(false))
+    });
+
+    match value.get_index() {
+        Some(index) => {
+            if let Some(time) = filmstrip.ui(ui, &index, prog) {
+                value.seek(time);
+            }
+
+            ui.label(format!(
+                "Frame {} of {}, {} keyframes",
+                value.get_frame_index() + 1,
+                index.frame_count(),
+                index.keyframes.len()
+            ));
+        }
+        None if value.is_indexing() => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Indexing...");
+            });
+        }
+        None => {}
+    }
+
+    value.with(|v| {
+        ui.horizontal(|ui: &mut egui::Ui| {
+            let available_width = ui.available_width();
+
+            ui.spacing_mut().slider_width = available_width / 2.0;
+
+            ui.add(Slider::new(&mut v.play_speed, 0.0..=10.0).show_value(true));
+        });
+    });
+
+    ui.horizontal(|ui| {
+        for speed in PlaySpeed::ALL {
+            if ui.button(speed.label()).clicked() {
+                value.set_play_speed(speed);
+            }
+        }
+    });
+
+    let mut seek_target = None;
+
+    let current = value
+        .get_next_pts()
+        .map(|t| FancyDuration(t))
+        .unwrap_or(FancyDuration(Duration::from_millis(0)));
+
+    ui.label(format!(
+        "Frame PTS: {}",
+        value
+            .get_frame_pts()
+            .map(|t| FancyDuration(t))
+            .unwrap_or(FancyDuration(Duration::from_millis(0)))
+    ));
+
+    value.with(|video| {
+        if new_prog == prog {
+            ui.label(format!(
+                "Current Time: {}",
+                FancyDuration(video.total_duration.mul_f64(prog))
+            ));
+
+            ui.label(format!("Next Frame: {}", current));
+        } else {
+            let new_duration = video.total_duration.mul_f64(new_prog);
+
+            ui.label(format!("Seek Target: {}", FancyDuration(new_duration)));
+
+            ui.label(format!("Next Frame: None"));
+
+            seek_target = Some(new_duration);
+        }
+    });
+
+    if let Some(seek_target) = seek_target.take() {
+        value.seek(seek_target);
+    }
+
+    ui.add_space(12.0);
+
+    ui.label(format!(
+        "Last frame decode latency {}",
+        value
+            .get_last_decode_cost()
+            .map(|t| FancyDuration(t))
+            .unwrap_or(FancyDuration(Duration::from_millis(0)))
+    ));
+
+    ui.label(format!("Realtime PTS {}", FancyDuration(value.get_pts())));
+
+    ui.label(format!("Dropped Frames (render): {}", value.get_dropped_frames()));
+
+    ui.label(format!(
+        "Dropped Frames (decoder): {}",
+        value.get_decoder_dropped_frames()
+    ));
+
+    match value.get_audio_buffered() {
+        Some(buffered) => ui.label(format!("Audio buffered: {}", FancyDuration(buffered))),
+        None => ui.label("No audio stream"),
+    };
+
+    let (error_count, last_error) = value.get_errors();
+
+    if let Some(last_error) = last_error {
+        ui.colored_label(
+            egui::Color32::LIGHT_RED,
+            format!("Decoder errors: {error_count} ({last_error})"),
+        );
+    }
+
+    let (len, cap) = value.get_buffer_size();
+    let health = value.get_buffer_health();
+
+    ui.label(format!("Buffer Health: {len}/{cap}"));
+
+    ui.add(ProgressBar::new(health as f32));
+}
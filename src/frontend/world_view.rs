@@ -1,16 +1,22 @@
 use crate::camera::Camera;
 use crate::camera_controller::CameraController;
+use crate::chunk_manager::{BlockEdit, BlockEditRequest};
 use crate::egui_tools::EguiRenderer;
-use crate::frontend::{TabView, TabHandle};
+use crate::frontend::{ExportControls, TabView, TabHandle};
 use crate::gizmo_example::GizmoExample;
-use crate::gpu::{GPUCtx, GPUTexture, ViewTarget};
-use bytemuck::{Pod, Zeroable};
+use crate::gpu::{Filler0, GPUCtx, GPUTexture, TextureRead, ViewTarget};
+use crate::render_graph::RenderGraph;
+use crate::scene::Scene;
 use egui::load::SizedTexture;
-use egui::{ImageSource, Rect, TextureId, Ui};
+use egui::{Image, ImageSource, Rect, Sense, TextureId, Ui};
 use egui_wgpu::wgpu;
-use egui_wgpu::wgpu::{FilterMode, TextureView};
-use glam::{Mat4, Vec2, Vec3};
-use std::time::Duration;
+use egui_wgpu::wgpu::FilterMode;
+use glam::{Vec2, Vec3};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 use crate::shared::{Shared, WeakShared};
 
 pub struct WorldView {
@@ -22,22 +28,22 @@ pub struct WorldView {
     pub secondary_camera_controller: CameraController,
     pub ctx: GPUCtx,
     pub needs_texture_update: bool,
+    pub export: ExportControls,
+    /// Read-back of a recorded frame still in flight, with its capture time
+    pending_capture: Option<(TextureRead, Instant)>,
+    pub scene: Shared<Scene>,
+    /// Right click on the view, taken by the terrain next frame
+    pub block_edit: Option<BlockEditRequest>,
 }
 
-#[repr(C)]
-#[derive(Pod, Copy, Clone, Zeroable)]
-struct Filler0(u8, u8, u8, u8);
-
 impl WorldView {
-    pub fn get_transform(&mut self, delta: Duration) -> Mat4 {
+    pub fn update_camera(&mut self, delta: Duration) {
         self.secondary_camera_controller
             .update_camera(&mut self.secondary_camera, delta);
         self.secondary_camera.compute();
-
-        self.gizmo_example.transform
     }
 
-    pub fn new(ctx: &GPUCtx, egui_renderer: &mut EguiRenderer, render_passes: &mut Vec<WeakShared<WorldView>>, egui_passes: &mut Vec<WeakShared<WorldView>>) -> Shared<Self> {
+    pub fn new(ctx: &GPUCtx, egui_renderer: &mut EguiRenderer, scene: Shared<Scene>, render_passes: &mut Vec<WeakShared<WorldView>>, egui_passes: &mut Vec<WeakShared<WorldView>>) -> Shared<Self> {
         let view_width = 700.0;
         let view_height = 400.0;
         let secondary_camera = Camera::new(
@@ -59,6 +65,7 @@ impl WorldView {
             wgpu::TextureFormat::Bgra8UnormSrgb,
             Filler0(0, 0, 0, 255),
             wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
                 | wgpu::TextureUsages::RENDER_ATTACHMENT
                 | wgpu::TextureUsages::TEXTURE_BINDING,
         );
@@ -80,6 +87,14 @@ impl WorldView {
             secondary_camera_controller,
             ctx: ctx.clone(),
             needs_texture_update: false,
+            export: ExportControls::new(
+                view_width as u32,
+                view_height as u32,
+                PathBuf::from("out"),
+            ),
+            pending_capture: None,
+            scene,
+            block_edit: None,
         }.into();
 
         render_passes.push(data.weak());
@@ -88,14 +103,62 @@ impl WorldView {
         data
     }
 
-    pub fn render_to<F: FnMut(&Camera, &TextureView, &TextureView)>(&self, render_pass: &mut F) {
+    pub fn render_to(&self, graph: &mut RenderGraph) {
         let color_view = &self.secondary_rt_gpu_texture.view;
         let depth_view = &self
             .secondary_render_target_depth
             .depth_stencil
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        render_pass(&self.secondary_camera, color_view, depth_view);
+        let size = self.secondary_rt_gpu_texture.size();
+
+        graph.execute(
+            &self.ctx,
+            &self.secondary_camera,
+            color_view,
+            depth_view,
+            (size.width, size.height),
+        );
+    }
+
+    /// Starts reading the last render back for the recording, if any.
+    ///
+    /// The read is picked up on a later frame instead of waiting for the
+    /// GPU, while it's still in flight the new renders aren't captured.
+    pub fn capture_frame(&mut self) {
+        if !self.export.is_recording() {
+            self.pending_capture = None;
+            return;
+        }
+
+        puffin::profile_function!();
+
+        if let Some((mut read, captured_at)) = self.pending_capture.take() {
+            self.ctx.device.poll(wgpu::Maintain::Poll);
+
+            let mut cx = Context::from_waker(Waker::noop());
+
+            match Pin::new(&mut read).poll(&mut cx) {
+                Poll::Pending => {
+                    self.pending_capture = Some((read, captured_at));
+                    return;
+                }
+                Poll::Ready(Ok(mut pixels)) => {
+                    // The render target is BGRA, the encoder wants RGBA
+                    for pixel in pixels.chunks_exact_mut(4) {
+                        pixel.swap(0, 2);
+                    }
+
+                    self.export.push_frame(pixels, captured_at);
+                }
+                Poll::Ready(Err(e)) => eprintln!("Failed to read the view back: {e}"),
+            }
+        }
+
+        match self.secondary_rt_gpu_texture.read_to_vec(&self.ctx) {
+            Ok(read) => self.pending_capture = Some((read, Instant::now())),
+            Err(e) => eprintln!("Failed to read the view back: {e}"),
+        }
     }
 
     pub fn on_egui(&self, egui_renderer: &mut EguiRenderer) {
@@ -123,17 +186,63 @@ impl TabView for Shared<WorldView> {
                 let w = rect.width();
                 let h = rect.height();
 
-                ui.image(ImageSource::Texture(SizedTexture::new(
-                    view.secondary_rt_texture_id,
-                    [w, h],
-                )));
+                let response = ui.add(
+                    Image::new(ImageSource::Texture(SizedTexture::new(
+                        view.secondary_rt_texture_id,
+                        [w, h],
+                    )))
+                    .sense(Sense::click()),
+                );
+
+                // Right click removes the block under the cursor, with shift it places one
+                if response.secondary_clicked() {
+                    if let Some(pointer) = response.interact_pointer_pos() {
+                        let uv = (pointer - response.rect.min) / response.rect.size();
+                        let ndc = Vec2::new(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+
+                        let (origin, direction) = view.secondary_camera.ray_through(ndc);
+
+                        let edit = if ui.input(|input| input.modifiers.shift) {
+                            BlockEdit::Place
+                        } else {
+                            BlockEdit::Remove
+                        };
 
-                // view.gizmo_example.draw_gizmo(
-                //     ui,
-                //     &view.secondary_camera,
-                //     w,
-                //     h,
-                // );
+                        view.block_edit = Some(BlockEditRequest {
+                            edit,
+                            origin,
+                            direction,
+                        });
+                    }
+                }
+
+                view.scene.with(|scene| {
+                    let Some(selected) = scene.selected else {
+                        return;
+                    };
+
+                    let mut world = scene.world_matrix(selected);
+
+                    if view.gizmo_example.draw_gizmo(
+                        ui,
+                        &view.secondary_camera,
+                        w,
+                        h,
+                        &mut world,
+                    ) {
+                        scene.set_world_matrix(selected, world);
+                    }
+                });
+
+                // A running recording locks the render size, the encoder
+                // takes frames of the size it started with. The image is
+                // stretched over the tab until the recording stops.
+                let (w, h) = if view.export.is_recording() {
+                    let settings = view.export.settings;
+                    (settings.width as f32, settings.height as f32)
+                } else {
+                    (w, h)
+                };
 
                 view.secondary_camera.check_resize(w, h, || {
                     if w as u32 == 0 || h as u32 == 0 {
@@ -150,6 +259,9 @@ impl TabView for Shared<WorldView> {
                     view.secondary_render_target_depth
                         .resize(&view.ctx, w as u32, h as u32);
 
+                    view.export.settings.width = w as u32;
+                    view.export.settings.height = h as u32;
+
                     view.needs_texture_update = true;
                 });
             }
@@ -1,5 +1,6 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
@@ -24,4 +25,107 @@ pub fn get_random_file_from_directory(dir_path: &str) -> Option<PathBuf> {
 
     // Randomly choose one file if the list is not empty
     files.choose(&mut thread_rng()).cloned()
-}
\ No newline at end of file
+}
+
+/// Files directly inside `dir_path` whose extension is one of
+/// `extensions`, compared case insensitively. Sorted by name.
+pub fn list_files_with_extensions<P: AsRef<Path>>(dir_path: P, extensions: &[&str]) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir_path) else {
+        return vec![];
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+        })
+        .collect();
+
+    files.sort();
+    files
+}
+
+/// Reads a list of paths stored one per line, a missing file is an empty list
+pub fn read_path_list<P: AsRef<Path>>(file_path: P) -> Vec<PathBuf> {
+    fs::read_to_string(file_path)
+        .map(|content| {
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Creates the parent directory when it doesn't exist yet
+pub fn write_path_list<P: AsRef<Path>>(file_path: P, paths: &[PathBuf]) -> io::Result<()> {
+    if let Some(parent) = file_path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let content: String = paths
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect();
+
+    fs::write(file_path, content)
+}
+
+const APP_DIR_NAME: &str = "pony-renderer";
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `windows`, `macos` (under the home directory) or the XDG variable with
+/// its `fallback` (also under home), whichever applies. The working
+/// directory when none of those is set, like before these existed.
+fn platform_dir(windows: &str, macos: &str, xdg: &str, fallback: &str) -> PathBuf {
+    let base = if cfg!(windows) {
+        env_dir(windows)
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join(macos))
+    } else {
+        env_dir(xdg).or_else(|| env_dir("HOME").map(|home| home.join(fallback)))
+    };
+
+    match base {
+        Some(base) => base.join(APP_DIR_NAME),
+        None => PathBuf::from("."),
+    }
+}
+
+/// Where files that can be rebuilt at any time go, thumbnails and the like
+pub fn cache_dir() -> PathBuf {
+    platform_dir("LOCALAPPDATA", "Library/Caches", "XDG_CACHE_HOME", ".cache")
+}
+
+/// Where what the user made goes, edited chunks and the like
+pub fn data_dir() -> PathBuf {
+    platform_dir("APPDATA", "Library/Application Support", "XDG_DATA_HOME", ".local/share")
+}
+
+/// Where the settings the user picked go, media directories and the like
+pub fn config_dir() -> PathBuf {
+    platform_dir("APPDATA", "Library/Application Support", "XDG_CONFIG_HOME", ".config")
+}
+
+/// The user's own videos folder, when there is one
+pub fn videos_dir() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        env_dir("USERPROFILE").map(|home| home.join("Videos"))
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Movies"))
+    } else {
+        env_dir("XDG_VIDEOS_DIR").or_else(|| env_dir("HOME").map(|home| home.join("Videos")))
+    };
+
+    dir.filter(|dir| dir.is_dir())
+}
@@ -11,26 +11,26 @@ pub struct GizmoExample {
     gizmo: Gizmo,
     gizmo_modes: EnumSet<GizmoMode>,
     gizmo_orientation: GizmoOrientation,
-    scale: DVec3,
-    rotation: DQuat,
-    translation: DVec3,
-    pub transform: Mat4,
 }
 
 impl GizmoExample {
     pub fn new() -> Self {
         Self {
-            transform: Mat4::IDENTITY,
             gizmo: Gizmo::default(),
             gizmo_modes: GizmoMode::all(),
             gizmo_orientation: GizmoOrientation::Local,
-            scale: DVec3::ONE,
-            rotation: DQuat::IDENTITY,
-            translation: DVec3::ZERO,
         }
     }
 
-    pub fn draw_gizmo(&mut self, ui: &mut egui::Ui, camera: &Camera, w: f32, h: f32) {
+    /// Edits `transform` in place, returns whether the gizmo moved it
+    pub fn draw_gizmo(
+        &mut self,
+        ui: &mut egui::Ui,
+        camera: &Camera,
+        w: f32,
+        h: f32,
+        transform: &mut Mat4,
+    ) -> bool {
         // The whole clipping area of the UI is used as viewport
 
         // Ctrl toggles snapping
@@ -54,77 +54,83 @@ impl GizmoExample {
             ..Default::default()
         });
 
-        let mut transform =
-            Transform::from_scale_rotation_translation(self.scale, self.rotation, self.translation);
-
-        if let Some((result, new_transforms)) = self.gizmo.interact(ui, &[transform]) {
-            for (new_transform, transform) in
-                new_transforms.iter().zip(std::iter::once(&mut transform))
-            {
-                *transform = *new_transform;
-            }
-
-            self.transform = Mat4::from_scale_rotation_translation(
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+
+        let gizmo_transform = Transform::from_scale_rotation_translation(
+            DVec3::new(scale.x as f64, scale.y as f64, scale.z as f64),
+            DQuat::from_xyzw(
+                rotation.x as f64,
+                rotation.y as f64,
+                rotation.z as f64,
+                rotation.w as f64,
+            ),
+            DVec3::new(translation.x as f64, translation.y as f64, translation.z as f64),
+        );
+
+        let Some((result, new_transforms)) = self.gizmo.interact(ui, &[gizmo_transform]) else {
+            return false;
+        };
+
+        if let Some(new_transform) = new_transforms.first() {
+            *transform = Mat4::from_scale_rotation_translation(
                 Vec3::new(
-                    self.scale.x as f32,
-                    self.scale.y as f32,
-                    self.scale.z as f32,
+                    new_transform.scale.x as f32,
+                    new_transform.scale.y as f32,
+                    new_transform.scale.z as f32,
                 ),
                 Quat::from_xyzw(
-                    transform.rotation.v.x as f32,
-                    transform.rotation.v.y as f32,
-                    transform.rotation.v.z as f32,
-                    transform.rotation.s as f32,
+                    new_transform.rotation.v.x as f32,
+                    new_transform.rotation.v.y as f32,
+                    new_transform.rotation.v.z as f32,
+                    new_transform.rotation.s as f32,
                 ),
                 Vec3::new(
-                    self.translation.x as f32,
-                    self.translation.y as f32,
-                    self.translation.z as f32,
+                    new_transform.translation.x as f32,
+                    new_transform.translation.y as f32,
+                    new_transform.translation.z as f32,
                 ),
             );
-
-            self.scale = transform.scale.into();
-            self.rotation = transform.rotation.into();
-            self.translation = transform.translation.into();
-
-            let text = match result {
-                GizmoResult::Rotation {
-                    axis,
-                    delta: _,
-                    total,
-                    is_view_axis: _,
-                } => {
-                    format!(
-                        "Rotation axis: ({:.2}, {:.2}, {:.2}), Angle: {:.2} deg",
-                        axis.x,
-                        axis.y,
-                        axis.z,
-                        total.to_degrees()
-                    )
-                }
-                GizmoResult::Translation { delta: _, total } => {
-                    format!(
-                        "Translation: ({:.2}, {:.2}, {:.2})",
-                        total.x, total.y, total.z,
-                    )
-                }
-                GizmoResult::Scale { total } => {
-                    format!("Scale: ({:.2}, {:.2}, {:.2})", total.x, total.y, total.z,)
-                }
-                GizmoResult::Arcball { delta: _, total } => {
-                    let (axis, angle) = DQuat::from(total).to_axis_angle();
-                    format!(
-                        "Rotation axis: ({:.2}, {:.2}, {:.2}), Angle: {:.2} deg",
-                        axis.x,
-                        axis.y,
-                        axis.z,
-                        angle.to_degrees()
-                    )
-                }
-            };
-
-            ui.label(text);
         }
+
+        let text = match result {
+            GizmoResult::Rotation {
+                axis,
+                delta: _,
+                total,
+                is_view_axis: _,
+            } => {
+                format!(
+                    "Rotation axis: ({:.2}, {:.2}, {:.2}), Angle: {:.2} deg",
+                    axis.x,
+                    axis.y,
+                    axis.z,
+                    total.to_degrees()
+                )
+            }
+            GizmoResult::Translation { delta: _, total } => {
+                format!(
+                    "Translation: ({:.2}, {:.2}, {:.2})",
+                    total.x, total.y, total.z,
+                )
+            }
+            GizmoResult::Scale { total } => {
+                format!("Scale: ({:.2}, {:.2}, {:.2})", total.x, total.y, total.z,)
+            }
+            GizmoResult::Arcball { delta: _, total } => {
+                let (axis, angle) = DQuat::from(total).to_axis_angle();
+                format!(
+                    "Rotation axis: ({:.2}, {:.2}, {:.2}), Angle: {:.2} deg",
+                    axis.x,
+                    axis.y,
+                    axis.z,
+                    angle.to_degrees()
+                )
+            }
+        };
+
+        ui.label(text);
+
+        true
     }
 
     #[allow(unused)]
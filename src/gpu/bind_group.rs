@@ -0,0 +1,90 @@
+use crate::gpu::{GPUCtx, GPUSampler, GPUTexture, Uniform};
+use bytemuck::Pod;
+use egui_wgpu::wgpu;
+use std::rc::Rc;
+
+/// Declares a bind group once and derives both its layout and its entries
+/// from that, bindings are numbered in the order they are added.
+///
+/// Resources are optional so the same declaration can build the layout a
+/// pipeline needs before any resource exists, `build` wants all of them.
+///
+/// ```ignore
+/// fn builder<'a>(camera: Option<&'a Uniform<Mat4Bytes>>, texture: Option<&'a GPUTexture>) -> BindGroupBuilder<'a> {
+///     BindGroupBuilder::new()
+///         .uniform(wgpu::ShaderStages::VERTEX, camera)
+///         .texture(texture)
+/// }
+/// ```
+#[derive(Default)]
+pub struct BindGroupBuilder<'a> {
+    layout_entries: Vec<wgpu::BindGroupLayoutEntry>,
+    resources: Vec<Option<wgpu::BindingResource<'a>>>,
+}
+
+impl<'a> BindGroupBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_binding(&self) -> u32 {
+        self.layout_entries.len() as u32
+    }
+
+    fn push(
+        mut self,
+        layout_entry: wgpu::BindGroupLayoutEntry,
+        resource: Option<wgpu::BindingResource<'a>>,
+    ) -> Self {
+        self.layout_entries.push(layout_entry);
+        self.resources.push(resource);
+        self
+    }
+
+    pub fn uniform<T: Pod>(
+        self,
+        visibility: wgpu::ShaderStages,
+        uniform: Option<&'a Uniform<T>>,
+    ) -> Self {
+        let layout_entry = Uniform::<T>::get_layout(self.next_binding(), visibility);
+        self.push(layout_entry, uniform.map(Uniform::get_resource))
+    }
+
+    pub fn texture(self, texture: Option<&'a GPUTexture>) -> Self {
+        let layout_entry = GPUTexture::get_layout(self.next_binding());
+        self.push(layout_entry, texture.map(GPUTexture::get_resource))
+    }
+
+    pub fn sampler(self, sampler: Option<&'a GPUSampler>) -> Self {
+        let layout_entry = GPUSampler::get_layout(self.next_binding());
+        self.push(layout_entry, sampler.map(GPUSampler::get_resource))
+    }
+
+    /// Shared with every bind group declared the same way, see `PipelineRegistry`
+    pub fn build_layout(&self, ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        ctx.pipelines.bind_group_layout(ctx, &self.layout_entries)
+    }
+
+    /// Panics when a binding was declared without its resource
+    pub fn build(self, ctx: &GPUCtx) -> wgpu::BindGroup {
+        let layout = self.build_layout(ctx);
+
+        let entries: Vec<wgpu::BindGroupEntry> = self
+            .resources
+            .into_iter()
+            .enumerate()
+            .map(|(binding, resource)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: resource.unwrap_or_else(|| {
+                    panic!("[BindGroupBuilder] Binding {binding} has no resource")
+                }),
+            })
+            .collect();
+
+        ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &entries,
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use crate::gpu::PipelineRegistry;
 use crate::shared::Shared;
 use crate::window::OSWindow;
 use egui_wgpu::wgpu;
@@ -6,10 +7,19 @@ use std::rc::Rc;
 use winit::dpi::LogicalSize;
 use winit::event_loop::EventLoop;
 
+#[derive(Debug, thiserror::Error)]
+pub enum GPUCtxError {
+    #[error("No adapter available (fallback adapter forced: {0})")]
+    NoAdapter(bool),
+    #[error("Failed to create the device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+}
+
 #[derive(Debug)]
 pub struct GPUCtx {
     pub device: Rc<wgpu::Device>,
     pub queue: Rc<wgpu::Queue>,
+    pub pipelines: Rc<PipelineRegistry>,
 }
 
 impl Clone for GPUCtx {
@@ -17,6 +27,7 @@ impl Clone for GPUCtx {
         Self {
             device: self.device.clone(),
             queue: self.queue.clone(),
+            pipelines: self.pipelines.clone(),
         }
     }
 }
@@ -38,10 +49,17 @@ impl GPUCtx {
         }))
         .unwrap();
 
-        let (device, queue) =
-            block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: PipelineRegistry::required_features(&adapter),
+                ..Default::default()
+            },
+            None,
+        ))
+        .unwrap();
 
         let ctx = Self {
+            pipelines: PipelineRegistry::new(&device, &adapter.get_info()).into(),
             queue: queue.into(),
             device: device.into(),
         };
@@ -51,6 +69,53 @@ impl GPUCtx {
         (ctx, Shared::new(window))
     }
 
+    /// A context without a window, for rendering into an `OffscreenTarget`.
+    ///
+    /// `force_fallback_adapter` picks the software adapter (lavapipe,
+    /// WARP, llvmpipe) so the output does not depend on the GPU.
+    #[allow(unused)]
+    pub fn headless(force_fallback_adapter: bool) -> Result<Self, GPUCtxError> {
+        // Software adapters often only exist on the secondary backends
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter,
+        }))
+        .ok_or(GPUCtxError::NoAdapter(force_fallback_adapter))?;
+
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: PipelineRegistry::required_features(&adapter),
+                ..Default::default()
+            },
+            None,
+        ))?;
+
+        Ok(Self {
+            pipelines: PipelineRegistry::new(&device, &adapter.get_info()).into(),
+            queue: queue.into(),
+            device: device.into(),
+        })
+    }
+
+    /// Runs `f` inside a validation error scope, so a bad shader or
+    /// layout comes back as an error instead of aborting the app
+    pub fn validate<T>(&self, f: impl FnOnce() -> T) -> Result<T, wgpu::Error> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let value = f();
+
+        match block_on(self.device.pop_error_scope()) {
+            Some(e) => Err(e),
+            None => Ok(value),
+        }
+    }
+
     #[allow(unused)]
     pub(crate) fn force_sync(&self) {
         self.queue.submit([]);
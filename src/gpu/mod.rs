@@ -3,11 +3,23 @@ mod view;
 mod texture;
 mod sampler;
 mod buffer;
+mod uniform;
+mod bind_group;
+mod shader;
+mod registry;
+mod upload;
+#[cfg(test)]
+pub mod test_utils;
 
 pub use ctx::*;
 pub use view::*;
 pub use texture::*;
 pub use sampler::*;
+pub use uniform::*;
+pub use bind_group::*;
+pub use shader::*;
+pub use registry::*;
+pub use upload::*;
 
 #[allow(unused)]
 pub use buffer::*;
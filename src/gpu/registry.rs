@@ -0,0 +1,222 @@
+use crate::gpu::{GPUCtx, ShaderError};
+use egui_wgpu::wgpu;
+use std::{
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+const PIPELINE_CACHE_DIR: &str = "pipeline_cache";
+
+/// A bind group layout from `PipelineRegistry::bind_group_layout`,
+/// compared by identity. The registry keeps every layout alive, so
+/// the same entries always give the same one.
+#[derive(Clone, Debug)]
+pub struct LayoutRef(pub Rc<wgpu::BindGroupLayout>);
+
+impl PartialEq for LayoutRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for LayoutRef {}
+
+impl Hash for LayoutRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// Everything besides the shader source that makes two render pipelines
+/// differ, `build` should take these from the key rather than repeat them
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: &'static str,
+    pub format: wgpu::TextureFormat,
+    pub blend: Option<wgpu::BlendState>,
+    pub vertex_layout: &'static [wgpu::VertexBufferLayout<'static>],
+    pub cull_mode: Option<wgpu::Face>,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub bind_group_layouts: Vec<LayoutRef>,
+}
+
+impl PipelineKey {
+    /// The pipeline layout of the key's bind group layouts, in order
+    pub fn create_layout(&self, ctx: &GPUCtx) -> wgpu::PipelineLayout {
+        let bind_group_layouts: Vec<&wgpu::BindGroupLayout> =
+            self.bind_group_layouts.iter().map(|layout| &*layout.0).collect();
+
+        ctx.device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(self.shader),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            })
+    }
+}
+
+/// Shares bind group layouts, shader modules and render pipelines
+/// between everything that asks for the same one, and keeps the
+/// driver's pipeline cache on disk when the backend has one
+#[derive(Debug)]
+pub struct PipelineRegistry {
+    layouts: RefCell<HashMap<Vec<wgpu::BindGroupLayoutEntry>, Rc<wgpu::BindGroupLayout>>>,
+    shaders: RefCell<HashMap<(&'static str, u64), Rc<wgpu::ShaderModule>>>,
+    pipelines: RefCell<HashMap<(PipelineKey, u64), Rc<wgpu::RenderPipeline>>>,
+    cache: Option<(wgpu::PipelineCache, PathBuf)>,
+}
+
+fn hash_source(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Only Vulkan has pipeline caches for now, the feature is off everywhere else
+fn create_pipeline_cache(
+    device: &wgpu::Device,
+    adapter_info: &wgpu::AdapterInfo,
+) -> Option<(wgpu::PipelineCache, PathBuf)> {
+    if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+        return None;
+    }
+
+    let path = Path::new(PIPELINE_CACHE_DIR).join(wgpu::util::pipeline_cache_key(adapter_info)?);
+    let data = fs::read(&path).ok();
+
+    // SAFETY: the data comes from `get_data` of a cache created for the
+    // same adapter and driver, the key encodes both. With `fallback`
+    // wgpu starts empty instead of failing when the driver rejects it.
+    let cache = unsafe {
+        device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+            label: Some("Pipeline cache"),
+            data: data.as_deref(),
+            fallback: true,
+        })
+    };
+
+    Some((cache, path))
+}
+
+impl PipelineRegistry {
+    pub fn new(device: &wgpu::Device, adapter_info: &wgpu::AdapterInfo) -> Self {
+        Self {
+            layouts: RefCell::new(HashMap::new()),
+            shaders: RefCell::new(HashMap::new()),
+            pipelines: RefCell::new(HashMap::new()),
+            cache: create_pipeline_cache(device, adapter_info),
+        }
+    }
+
+    /// Features to request so `new` can create a pipeline cache
+    pub fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+        adapter.features() & wgpu::Features::PIPELINE_CACHE
+    }
+
+    pub fn bind_group_layout(
+        &self,
+        ctx: &GPUCtx,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> Rc<wgpu::BindGroupLayout> {
+        self.layouts
+            .borrow_mut()
+            .entry(entries.to_vec())
+            .or_insert_with(|| {
+                Rc::new(
+                    ctx.device
+                        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                            label: None,
+                            entries,
+                        }),
+                )
+            })
+            .clone()
+    }
+
+    /// The pipeline for `key` compiled from `source`, `build` only runs on a miss.
+    ///
+    /// A new source for a shader evicts the pipelines built from its old one.
+    pub fn render_pipeline(
+        &self,
+        ctx: &GPUCtx,
+        key: PipelineKey,
+        source: &str,
+        build: impl FnOnce(
+            &PipelineKey,
+            &wgpu::ShaderModule,
+            Option<&wgpu::PipelineCache>,
+        ) -> wgpu::RenderPipeline,
+    ) -> Result<Rc<wgpu::RenderPipeline>, ShaderError> {
+        let source_hash = hash_source(source);
+        let map_key = (key, source_hash);
+
+        if let Some(pipeline) = self.pipelines.borrow().get(&map_key) {
+            return Ok(pipeline.clone());
+        }
+
+        let key = &map_key.0;
+
+        let cached_module = self
+            .shaders
+            .borrow()
+            .get(&(key.shader, source_hash))
+            .cloned();
+
+        // Nothing is stored before the error scope says it is valid
+        let (module, pipeline) = ctx
+            .validate(|| {
+                let module = cached_module.unwrap_or_else(|| {
+                    Rc::new(
+                        ctx.device
+                            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                                label: Some(key.shader),
+                                source: wgpu::ShaderSource::Wgsl(source.into()),
+                            }),
+                    )
+                });
+
+                let cache = self.cache.as_ref().map(|(cache, _)| cache);
+                let pipeline = Rc::new(build(key, &module, cache));
+
+                (module, pipeline)
+            })
+            .map_err(|e| ShaderError::Pipeline(e.to_string()))?;
+
+        let mut shaders = self.shaders.borrow_mut();
+        shaders.retain(|(shader, hash), _| *shader != key.shader || *hash == source_hash);
+        shaders.insert((key.shader, source_hash), module);
+
+        let mut pipelines = self.pipelines.borrow_mut();
+        pipelines.retain(|(other, hash), _| other.shader != key.shader || *hash == source_hash);
+        pipelines.insert(map_key, pipeline.clone());
+
+        Ok(pipeline)
+    }
+
+    /// Writes the driver's pipeline cache to disk, once the pipelines are
+    /// warmed up and on exit, the data covers every pipeline built so far
+    pub fn save_cache(&self) {
+        let Some((ref cache, ref path)) = self.cache else {
+            return;
+        };
+
+        let Some(data) = cache.get_data() else {
+            return;
+        };
+
+        // Written aside and renamed, a crash never leaves half a cache behind
+        let temp = path.with_extension("tmp");
+
+        let result = fs::create_dir_all(PIPELINE_CACHE_DIR)
+            .and_then(|_| fs::write(&temp, data))
+            .and_then(|_| fs::rename(&temp, path));
+
+        if let Err(e) = result {
+            eprintln!("Failed to write the pipeline cache: {e}");
+        }
+    }
+}
@@ -30,10 +30,15 @@ impl GPUSampler {
         Self { sampler }
     }
 
+    pub fn get_resource(&self) -> wgpu::BindingResource {
+        wgpu::BindingResource::Sampler(&self.sampler)
+    }
+
+    #[allow(unused)]
     pub fn get_binding(&self, binding: u32) -> wgpu::BindGroupEntry {
         wgpu::BindGroupEntry {
             binding,
-            resource: wgpu::BindingResource::Sampler(&self.sampler),
+            resource: self.get_resource(),
         }
     }
 }
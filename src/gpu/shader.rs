@@ -0,0 +1,196 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the shader files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShaderError {
+    #[error("Failed to read {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("{0}")]
+    Parse(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("Failed to create the pipeline: {0}")]
+    Pipeline(String),
+}
+
+/// Parses and validates WGSL with naga, the errors
+/// carry the annotated source naga prints
+pub fn validate_wgsl(name: &str, source: &str) -> Result<naga::Module, ShaderError> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| ShaderError::Parse(e.emit_to_string_with_path(source, name)))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| ShaderError::Validation(e.emit_to_string_with_path(source, name)))?;
+
+    Ok(module)
+}
+
+/// A WGSL file read from disk, with the copy embedded in the
+/// binary as a fallback when the file is missing or broken
+pub struct ShaderFile {
+    pub name: &'static str,
+    pub path: PathBuf,
+    embedded: &'static str,
+    /// The last source that passed validation
+    source: String,
+    modified: Option<SystemTime>,
+    /// Why the file on disk is not the one in use, if it is not
+    pub error: Option<ShaderError>,
+    /// Bumped on every successful reload
+    pub generation: u64,
+}
+
+impl ShaderFile {
+    pub fn new(name: &'static str, path: PathBuf, embedded: &'static str) -> Self {
+        let mut file = Self {
+            name,
+            path,
+            embedded,
+            source: embedded.to_string(),
+            modified: None,
+            error: None,
+            generation: 0,
+        };
+
+        file.reload();
+
+        file
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn embedded(&self) -> &'static str {
+        self.embedded
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Reads and validates the file, returns whether the source changed
+    fn reload(&mut self) -> bool {
+        self.modified = self.modified();
+
+        let result = fs::read_to_string(&self.path)
+            .map_err(|e| ShaderError::Io(self.path.clone(), e))
+            .and_then(|source| validate_wgsl(self.name, &source).map(|_| source));
+
+        match result {
+            Ok(source) => {
+                self.error = None;
+
+                if source == self.source {
+                    return false;
+                }
+
+                self.source = source;
+                self.generation += 1;
+
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+/// Every shader the pipelines load at runtime, polled for changes
+pub struct ShaderLibrary {
+    files: Vec<ShaderFile>,
+    last_poll: Instant,
+}
+
+impl ShaderLibrary {
+    pub fn new(files: Vec<ShaderFile>) -> Self {
+        Self {
+            files,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn files(&self) -> &[ShaderFile] {
+        &self.files
+    }
+
+    fn file(&self, name: &str) -> &ShaderFile {
+        self.files
+            .iter()
+            .find(|file| file.name == name)
+            .unwrap_or_else(|| panic!("[ShaderLibrary] Unknown shader {name}"))
+    }
+
+    fn file_mut(&mut self, name: &str) -> &mut ShaderFile {
+        self.files
+            .iter_mut()
+            .find(|file| file.name == name)
+            .unwrap_or_else(|| panic!("[ShaderLibrary] Unknown shader {name}"))
+    }
+
+    pub fn source(&self, name: &str) -> &str {
+        self.file(name).source()
+    }
+
+    /// Names of the shaders whose file changed and validated since the last poll
+    pub fn poll(&mut self) -> Vec<&'static str> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+
+        self.last_poll = Instant::now();
+
+        self.files
+            .iter_mut()
+            .filter(|file| file.modified() != file.modified)
+            .filter_map(|file| file.reload().then_some(file.name))
+            .collect()
+    }
+
+    /// Builds with the current source of `name`. When that fails the
+    /// embedded copy is used instead, so startup always gets a pipeline.
+    pub fn build<T>(
+        &mut self,
+        name: &str,
+        mut build: impl FnMut(&str) -> Result<T, ShaderError>,
+    ) -> T {
+        let file = self.file_mut(name);
+
+        match build(&file.source) {
+            Ok(built) => built,
+            Err(e) => {
+                file.error = Some(e);
+
+                build(file.embedded)
+                    .unwrap_or_else(|e| panic!("[ShaderLibrary] Embedded {name} is broken: {e}"))
+            }
+        }
+    }
+
+    /// Rebuilds after a change, `current` stays when the new source fails
+    pub fn rebuild<T>(
+        &mut self,
+        name: &str,
+        current: &mut T,
+        build: impl FnOnce(&str) -> Result<T, ShaderError>,
+    ) {
+        let file = self.file_mut(name);
+
+        match build(&file.source) {
+            Ok(built) => *current = built,
+            Err(e) => file.error = Some(e),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Rendering into an `OffscreenTarget` and checking the pixels, for the
+//! pipeline tests. They need an adapter, so they are `#[ignore]`d and
+//! run with `cargo test -- --ignored` on a machine that has one.
+
+use crate::gpu::{GPUCtx, OffscreenTarget};
+use egui_wgpu::wgpu;
+
+/// The software adapter when there is one, any adapter otherwise
+pub fn test_ctx() -> GPUCtx {
+    GPUCtx::headless(true)
+        .or_else(|_| GPUCtx::headless(false))
+        .expect("the GPU tests need an adapter")
+}
+
+/// Clears a `width` x `height` target to `clear`, lets `draw` record into
+/// one pass over it and reads the result back as RGBA pixels
+pub fn render_offscreen(
+    ctx: &GPUCtx,
+    width: u32,
+    height: u32,
+    clear: wgpu::Color,
+    draw: impl FnOnce(&mut wgpu::RenderPass),
+) -> Vec<[u8; 4]> {
+    let target = OffscreenTarget::create(ctx, width, height);
+
+    let mut encoder = ctx.device.create_command_encoder(&Default::default());
+
+    {
+        let view = target.view();
+        let mut pass = view.render_pass_with_load(
+            &mut encoder,
+            wgpu::LoadOp::Clear(clear),
+            wgpu::LoadOp::Clear(1.0),
+        );
+
+        draw(&mut pass);
+    }
+
+    ctx.queue.submit([encoder.finish()]);
+
+    // The target is BGRA
+    target
+        .read_blocking(ctx)
+        .unwrap()
+        .chunks_exact(4)
+        .map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+        .collect()
+}
+
+/// Filtering and sRGB encoding cost a step at most
+#[track_caller]
+pub fn assert_pixels_close(actual: &[[u8; 4]], expected: &[[u8; 4]]) {
+    assert_eq!(actual.len(), expected.len(), "pixel count");
+
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        let close = a.iter().zip(e).all(|(&a, &e)| a.abs_diff(e) <= 1);
+
+        assert!(close, "pixel {i}: expected {e:?}, got {a:?}");
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
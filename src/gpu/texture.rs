@@ -1,6 +1,25 @@
-use bytemuck::NoUninit;
+use bytemuck::{NoUninit, Pod, Zeroable};
 use egui_wgpu::wgpu;
 use crate::gpu::GPUCtx;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TextureReadError {
+    #[error("The texture was created without COPY_SRC usage")]
+    MissingCopySrc,
+    #[error("Reading back {0:?} textures is not supported")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("Failed to map the staging buffer: {0}")]
+    Map(#[from] wgpu::BufferAsyncError),
+}
+
+/// One RGBA8 texel, to fill new textures with
+#[repr(C)]
+#[derive(Pod, Copy, Clone, Zeroable)]
+pub struct Filler0(pub u8, pub u8, pub u8, pub u8);
 
 #[derive(Debug)]
 pub struct GPUTexture {
@@ -15,6 +34,7 @@ fn size_of<T>() -> u32 {
     std::mem::size_of::<T>() as u32
 }
 
+/// `None` for formats we don't know the texel size of
 fn get_bytes_per_row(format: wgpu::TextureFormat, width: u32) -> Option<u32> {
     let bytes = match format {
         wgpu::TextureFormat::Depth32Float => size_of::<f32>() * width,
@@ -22,14 +42,84 @@ fn get_bytes_per_row(format: wgpu::TextureFormat, width: u32) -> Option<u32> {
         wgpu::TextureFormat::Rgba8Unorm => 4 * size_of::<u8>() * width,
         wgpu::TextureFormat::Rgba8UnormSrgb => 4 * size_of::<u8>() * width,
         wgpu::TextureFormat::Bgra8UnormSrgb => 4 * size_of::<u8>() * width,
-        _ => {
-            panic!("[GPUTexture] Unknown format {:?}", format);
-        }
+        _ => return None,
     };
 
     Some(bytes)
 }
 
+/// For `write_texture`, which can't lay out rows of an unknown format
+fn expect_bytes_per_row(format: wgpu::TextureFormat, width: u32) -> u32 {
+    get_bytes_per_row(format, width)
+        .unwrap_or_else(|| panic!("[GPUTexture] Can't write {:?} texels", format))
+}
+
+/// Buffer copies need every row to start at a multiple of 256 bytes
+pub fn get_padded_bytes_per_row(bytes_per_row: u32) -> u32 {
+    bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+#[derive(Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+/// A texture copy on its way back to the CPU, see `GPUTexture::read_to_vec`.
+///
+/// The map callback only fires while the device is polled, so
+/// something has to call `device.poll` for this to resolve.
+pub struct TextureRead {
+    staging: wgpu::Buffer,
+    state: Arc<Mutex<MapState>>,
+    bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+    height: u32,
+}
+
+impl TextureRead {
+    /// Strips the row padding of the mapped staging buffer
+    fn copy_rows(&self) -> Vec<u8> {
+        let bytes_per_row = self.bytes_per_row as usize;
+        let mut data = Vec::with_capacity(bytes_per_row * self.height as usize);
+
+        {
+            let mapped = self.staging.slice(..).get_mapped_range();
+
+            for row in mapped
+                .chunks_exact(self.padded_bytes_per_row as usize)
+                .take(self.height as usize)
+            {
+                data.extend_from_slice(&row[..bytes_per_row]);
+            }
+        }
+
+        self.staging.unmap();
+
+        data
+    }
+}
+
+impl Future for TextureRead {
+    type Output = Result<Vec<u8>, TextureReadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = {
+            let mut state = self.state.lock().unwrap();
+
+            match state.result.take() {
+                Some(result) => result,
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        Poll::Ready(result.map(|_| self.copy_rows()).map_err(TextureReadError::from))
+    }
+}
+
 impl GPUTexture {
     pub const fn get_layout(binding: u32) -> wgpu::BindGroupLayoutEntry {
         wgpu::BindGroupLayoutEntry {
@@ -61,7 +151,8 @@ impl GPUTexture {
             size,
             format,
             usage,
-            view_formats: &[format],
+            // Lets `srgb_view` reinterpret linear RGBA textures
+            view_formats: &[format.add_srgb_suffix()],
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -71,7 +162,7 @@ impl GPUTexture {
             bytemuck::cast_slice(&vec![filler; (width * height) as usize]),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: get_bytes_per_row(format, size.width),
+                bytes_per_row: Some(expect_bytes_per_row(format, size.width)),
                 rows_per_image: None,
             },
             size,
@@ -99,17 +190,107 @@ impl GPUTexture {
             bytemuck::cast_slice(data),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: get_bytes_per_row(self.format, self.size.width),
+                bytes_per_row: Some(expect_bytes_per_row(self.format, self.size.width)),
                 rows_per_image: None,
             },
             self.size,
         );
     }
 
+    /// Copies the texture into a staging buffer and maps it, the future
+    /// resolves to the texels with rows tightly packed, in `format` order.
+    ///
+    /// The copy is submitted right away, awaiting only waits for the map.
+    pub fn read_to_vec(&self, ctx: &GPUCtx) -> Result<TextureRead, TextureReadError> {
+        if !self.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(TextureReadError::MissingCopySrc);
+        }
+
+        let bytes_per_row = get_bytes_per_row(self.format, self.size.width)
+            .ok_or(TextureReadError::UnsupportedFormat(self.format))?;
+        let padded_bytes_per_row = get_padded_bytes_per_row(bytes_per_row);
+
+        let staging = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture read-back"),
+            size: (padded_bytes_per_row * self.size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = ctx.device.create_command_encoder(&Default::default());
+
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(self.size.height),
+                },
+            },
+            self.size,
+        );
+
+        ctx.queue.submit([encoder.finish()]);
+
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = state.clone();
+
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let mut state = callback_state.lock().unwrap();
+            state.result = Some(result);
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Ok(TextureRead {
+            staging,
+            state,
+            bytes_per_row,
+            padded_bytes_per_row,
+            height: self.size.height,
+        })
+    }
+
+    /// `read_to_vec` for callers outside of an executor, stalls until the GPU is idle
+    pub fn read_to_vec_blocking(&self, ctx: &GPUCtx) -> Result<Vec<u8>, TextureReadError> {
+        let read = self.read_to_vec(ctx)?;
+
+        ctx.device.poll(wgpu::Maintain::Wait);
+
+        pollster::block_on(read)
+    }
+
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.size
+    }
+
+    /// Tightly packed, what `update` expects
+    pub fn bytes_per_row(&self) -> Option<u32> {
+        get_bytes_per_row(self.format, self.size.width)
+    }
+
+    /// A view that decodes sRGB when sampled, for holders of sRGB bytes in
+    /// a linear format. The same as `view` for formats without sRGB variant.
+    pub fn srgb_view(&self) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.format.add_srgb_suffix()),
+            ..Default::default()
+        })
+    }
+
+    pub fn get_resource(&self) -> wgpu::BindingResource {
+        wgpu::BindingResource::TextureView(&self.view)
+    }
+
+    #[allow(unused)]
     pub fn get_binding(&self, binding: u32) -> wgpu::BindGroupEntry {
         wgpu::BindGroupEntry {
             binding,
-            resource: wgpu::BindingResource::TextureView(&self.view),
+            resource: self.get_resource(),
         }
     }
 }
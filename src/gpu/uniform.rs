@@ -0,0 +1,50 @@
+use crate::gpu::GPUCtx;
+use bytemuck::Pod;
+use egui_wgpu::wgpu;
+use egui_wgpu::wgpu::util::DeviceExt;
+use std::marker::PhantomData;
+
+/// A uniform buffer holding exactly one `T`
+pub struct Uniform<T: Pod> {
+    buffer: wgpu::Buffer,
+    _value: PhantomData<T>,
+}
+
+impl<T: Pod> Uniform<T> {
+    pub const fn get_layout(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(size_of::<T>() as u64),
+            },
+            count: None,
+        }
+    }
+
+    pub fn create(ctx: &GPUCtx, value: T) -> Self {
+        let buffer = ctx
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&value),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Self {
+            buffer,
+            _value: PhantomData,
+        }
+    }
+
+    pub fn update(&self, ctx: &GPUCtx, value: T) {
+        ctx.queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&value));
+    }
+
+    pub fn get_resource(&self) -> wgpu::BindingResource {
+        self.buffer.as_entire_binding()
+    }
+}
@@ -0,0 +1,100 @@
+use crate::gpu::{get_padded_bytes_per_row, GPUCtx, GPUTexture};
+use egui_wgpu::wgpu;
+
+/// Writes several textures from one staging buffer with a single submit,
+/// instead of a `write_texture` per texture. The buffer is kept between
+/// uploads and only grows.
+#[derive(Default)]
+pub struct StagingUpload {
+    staging: Option<wgpu::Buffer>,
+}
+
+impl StagingUpload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn staging(&mut self, ctx: &GPUCtx, size: wgpu::BufferAddress) -> &wgpu::Buffer {
+        let too_small = self
+            .staging
+            .as_ref()
+            .map_or(true, |staging| staging.size() < size);
+
+        if too_small {
+            self.staging = Some(ctx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("texture upload"),
+                size,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+
+        self.staging.as_ref().unwrap()
+    }
+
+    /// `planes` pairs each texture with texels covering all of it, rows
+    /// tightly packed. They are copied into the staging buffer once,
+    /// padded to the row alignment buffer to texture copies need.
+    pub fn upload(&mut self, ctx: &GPUCtx, planes: &[(&GPUTexture, &[u8])]) {
+        puffin::profile_function!();
+
+        // Offset, tight and padded row size of every plane. Each
+        // plane starts at a multiple of 256, right after a padded row.
+        let mut layouts = Vec::with_capacity(planes.len());
+        let mut total_size: wgpu::BufferAddress = 0;
+
+        for (texture, _) in planes {
+            let Some(bytes_per_row) = texture.bytes_per_row() else {
+                eprintln!("[StagingUpload] Can't upload into a texture of unknown format");
+                return;
+            };
+            let padded_bytes_per_row = get_padded_bytes_per_row(bytes_per_row);
+
+            layouts.push((total_size, bytes_per_row, padded_bytes_per_row));
+            total_size += (padded_bytes_per_row * texture.size().height) as wgpu::BufferAddress;
+        }
+
+        let Some(size) = wgpu::BufferSize::new(total_size) else {
+            return;
+        };
+
+        let staging = self.staging(ctx, total_size);
+
+        {
+            // Written straight into wgpu's staging memory, no intermediate copy
+            let Some(mut view) = ctx.queue.write_buffer_with(staging, 0, size) else {
+                return;
+            };
+
+            for ((texture, data), &(offset, bytes_per_row, padded)) in planes.iter().zip(&layouts) {
+                let rows = data
+                    .chunks_exact(bytes_per_row as usize)
+                    .take(texture.size().height as usize);
+
+                for (row, source) in rows.enumerate() {
+                    let start = offset as usize + row * padded as usize;
+                    view[start..start + source.len()].copy_from_slice(source);
+                }
+            }
+        }
+
+        let mut encoder = ctx.device.create_command_encoder(&Default::default());
+
+        for ((texture, _), &(offset, _, padded)) in planes.iter().zip(&layouts) {
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset,
+                        bytes_per_row: Some(padded),
+                        rows_per_image: Some(texture.size().height),
+                    },
+                },
+                texture.texture.as_image_copy(),
+                texture.size(),
+            );
+        }
+
+        ctx.queue.submit([encoder.finish()]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::gpu::GPUCtx;
+use crate::gpu::{Filler0, GPUCtx, GPUTexture, TextureReadError};
 use crate::gpu_utils::build_depth_texture;
 use egui_wgpu::wgpu;
 
@@ -18,6 +18,55 @@ impl ViewTarget {
     }
 }
 
+/// Color and depth to render into without a surface, in the same
+/// formats as the window so every pipeline works unchanged
+#[allow(unused)]
+pub struct OffscreenTarget {
+    pub color: GPUTexture,
+    pub depth: ViewTarget,
+    depth_view: wgpu::TextureView,
+}
+
+#[allow(unused)]
+impl OffscreenTarget {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+
+    pub fn create(ctx: &GPUCtx, width: u32, height: u32) -> Self {
+        let color = GPUTexture::create(
+            ctx,
+            width,
+            height,
+            Self::FORMAT,
+            Filler0(0, 0, 0, 255),
+            wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+
+        let depth = ViewTarget::create(ctx, width, height);
+
+        let depth_view = depth
+            .depth_stencil
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            color,
+            depth,
+            depth_view,
+        }
+    }
+
+    pub fn view(&self) -> SView {
+        SView::new(&self.color.view, &self.depth_view)
+    }
+
+    /// The rendered image as tightly packed BGRA rows
+    pub fn read_blocking(&self, ctx: &GPUCtx) -> Result<Vec<u8>, TextureReadError> {
+        self.color.read_to_vec_blocking(ctx)
+    }
+}
+
 pub struct SView<'a> {
     color_view: &'a wgpu::TextureView,
     depth_view: &'a wgpu::TextureView,
@@ -31,9 +80,24 @@ impl<'a> SView<'a> {
         }
     }
 
+    /// Clears both attachments
+    #[allow(unused)]
     pub fn render_pass<'pass>(
         &'pass self,
         encoder: &'pass mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'pass> {
+        self.render_pass_with_load(
+            encoder,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            wgpu::LoadOp::Clear(1.0),
+        )
+    }
+
+    pub fn render_pass_with_load<'pass>(
+        &'pass self,
+        encoder: &'pass mut wgpu::CommandEncoder,
+        color_load: wgpu::LoadOp<wgpu::Color>,
+        depth_load: wgpu::LoadOp<f32>,
     ) -> wgpu::RenderPass<'pass> {
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
@@ -41,19 +105,14 @@ impl<'a> SView<'a> {
                 view: &self.color_view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 1.0,
-                    }),
+                    load: color_load,
                     store: wgpu::StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.depth_view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: depth_load,
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
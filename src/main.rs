@@ -6,9 +6,12 @@ mod camera_controller;
 mod camera_utils;
 mod canvas;
 mod chunk;
+mod chunk_manager;
+mod chunk_store;
 mod cube;
 mod demos;
 mod egui_tools;
+mod export;
 mod frontend;
 mod fs_utils;
 mod gizmo_example;
@@ -18,6 +21,8 @@ mod js;
 mod multimath;
 mod paint_utils;
 mod pipelines;
+mod render_graph;
+mod scene;
 mod shared;
 mod thread_utils;
 mod video;
@@ -27,21 +32,20 @@ use crate::camera::Camera;
 use crate::camera_controller::CameraController;
 use crate::camera_utils::process_camera_input;
 use crate::canvas::{render_svg, Canvas};
-use crate::demos::{ChunksDemo, VideoDemo};
+use crate::cube::Mesher;
+use crate::demos::{CanvasQuadDemo, ChunksDemo, VideoSource};
 use crate::egui_tools::EguiRenderer;
 use crate::frontend::{HandleList, TabHandle};
-use crate::gpu::{GPUCtx, GPUTexture, SView, ViewTarget};
+use crate::gpu::{Filler0, GPUCtx, GPUTexture, ShaderLibrary, ViewTarget};
 use crate::js::VM;
-use crate::video::FrameData;
-use bytemuck::{Pod, Zeroable};
+use crate::render_graph::{Attachment, PassDesc, RenderGraph};
+use crate::scene::{EntityId, Renderable, Scene, Transform};
 use egui::load::SizedTexture;
-use egui::{Event, ImageSource, ProgressBar, Slider};
+use egui::{Event, ImageSource};
 use egui_dock::{DockArea, DockState, NodeIndex, Style, SurfaceIndex};
 use egui_wgpu::wgpu::FilterMode;
 use egui_wgpu::{wgpu, ScreenDescriptor};
-use fancy_duration::FancyDuration;
 use frontend::{TabView, WorldView};
-use fs_utils::get_random_file_from_directory;
 use winit::application::ApplicationHandler;
 use winit::event::DeviceEvent;
 use winit::event_loop::ActiveEventLoop;
@@ -51,7 +55,7 @@ use shared::{Shared, WeakShared};
 use std::error::Error;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
-use video::VideoHandle;
+use video::{DecoderPool, MediaLibrary};
 use window::OSWindow;
 use winit::window::WindowId;
 use winit::{
@@ -61,11 +65,10 @@ use winit::{
     window::CursorGrabMode,
 };
 
-pub const BENCHMARK_MODE: bool = true;
+pub const BENCHMARK_MODE: bool = false;
 
-#[repr(C)]
-#[derive(Pod, Copy, Clone, Zeroable)]
-struct Filler0(u8, u8, u8, u8);
+/// How many of the recent files are opened again on startup
+const RESTORED_SOURCES: usize = 2;
 
 fn fps(frame_duration: Duration) -> f64 {
     let seconds = frame_duration.as_secs_f64();
@@ -76,6 +79,22 @@ fn fps(frame_duration: Duration) -> f64 {
     }
 }
 
+/// Puts the source's quad in the scene, named after its file
+fn spawn_video_entity(scene: &mut Scene, group: EntityId, source: &VideoSource) {
+    let name = source
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.path.display().to_string());
+
+    scene.spawn(
+        name,
+        Some(group),
+        Transform::IDENTITY,
+        Some(Renderable::VideoQuad(source.demo.clone())),
+    );
+}
+
 struct ApplicationHack {
     os_window: Shared<OSWindow>,
     boxed_fn: Box<dyn FnMut(&ActiveEventLoop, WindowId, WindowEvent, Option<(f64, f64)>)>,
@@ -86,12 +105,19 @@ impl ApplicationHack {
     fn new(ctx: GPUCtx, os_window: Shared<OSWindow>) -> Self {
         let mut vm = Shared::new(VM::new());
 
-        // let video_path = get_random_file_from_directory("/Volumes/dev/Shared/mp4")
-        //     .or_else(|| Some(PathBuf::from("/Users/cold/Desktop/YP-1R-05x13.mp4")))
-        //     .unwrap();
+        let decoder_pool = DecoderPool::with_default_workers();
 
-        let video_path = PathBuf::from("/Users/cold/Desktop/YP-1R-05x13.mp4");
-        let video_handle = VideoHandle::create(video_path);
+        let media_library = Shared::new(MediaLibrary::load());
+
+        // Pick up the clips compared in the last session
+        let video_paths: Vec<PathBuf> = media_library.with_ref(|library| {
+            library
+                .recent()
+                .iter()
+                .take(RESTORED_SOURCES)
+                .cloned()
+                .collect()
+        });
 
         let window_size = { os_window.borrow().window.inner_size() };
         let high_dpi_factor = 2.0 * { os_window.borrow().window.scale_factor() as f32 };
@@ -107,12 +133,55 @@ impl ApplicationHack {
             { &os_window.borrow().window },
         );
 
-        let chunks_demo = Shared::new(ChunksDemo::create(
-            &os_window.borrow().surface_configuration,
-            &ctx,
-        ));
+        let shaders = Shared::new(ShaderLibrary::new(vec![
+            pipelines::quad_mesh::shader_file(),
+            pipelines::video::shader_file(),
+            pipelines::canvas_quad::shader_file(),
+        ]));
+
+        let chunks_demo = Shared::new(shaders.with(|shaders| {
+            ChunksDemo::create(&os_window.borrow().surface_configuration, &ctx, shaders)
+        }));
+
+        let video_sources: Shared<Vec<VideoSource>> = video_paths
+            .into_iter()
+            .enumerate()
+            .map(|(slot, path)| {
+                shaders.with(|shaders| {
+                    VideoSource::open(
+                        &ctx,
+                        &os_window.borrow().surface_configuration,
+                        &decoder_pool,
+                        shaders,
+                        path,
+                        slot,
+                        &media_library.with_ref(|library| library.audio_output.clone()),
+                    )
+                })
+            })
+            .collect::<Vec<_>>()
+            .into();
+
+        let scene = Shared::new(Scene::new());
+
+        let videos_group = scene.with(|scene| {
+            scene.spawn(
+                "Terrain",
+                None,
+                Transform::IDENTITY,
+                Some(Renderable::Mesh(chunks_demo.clone())),
+            );
 
-        let mut video_demo = VideoDemo::create(&ctx, &os_window.borrow().surface_configuration);
+            let group = scene.spawn("Videos", None, Transform::IDENTITY, None);
+
+            video_sources.with_ref(|sources| {
+                for source in sources {
+                    spawn_video_entity(scene, group, source);
+                }
+            });
+
+            group
+        });
 
         let canvas_size = [1000.0 * high_dpi_factor, 1000.0 * high_dpi_factor];
         let skia_canvas = Shared::new(Canvas::new(
@@ -136,17 +205,53 @@ impl ApplicationHack {
             &ctx,
             canvas_size[0] as u32,
             canvas_size[1] as u32,
-            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Rgba8Unorm,
             Filler0(0, 0, 0, 255),
-            wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+        ));
+
+        let canvas_demo = Shared::new(skia_gpu_texture.with_ref(|texture| {
+            shaders.with(|shaders| {
+                CanvasQuadDemo::create(
+                    &ctx,
+                    &os_window.borrow().surface_configuration,
+                    shaders,
+                    texture,
+                )
+            })
+        }));
+
+        // Left of the first video quad, drag it around with the gizmo
+        scene.with(|scene| {
+            scene.spawn(
+                "Canvas",
+                None,
+                Transform {
+                    translation: Vec3::new(0.0, 0.0, -200.0),
+                    ..Transform::IDENTITY
+                },
+                Some(Renderable::CanvasQuad(canvas_demo.clone())),
+            );
+        });
+
+        // Every pipeline the scene starts with is built by now
+        ctx.pipelines.save_cache();
+
+        let canvas_export = Shared::new(frontend::ExportControls::new(
+            canvas_size[0] as u32,
+            canvas_size[1] as u32,
+            PathBuf::from("out"),
         ));
 
         let canvas_texture_id = skia_gpu_texture.with(|t| {
             t.update(&ctx, &canvas_data);
 
+            // egui decodes sRGB textures, then blends premultiplied as is
             egui_renderer
                 .renderer
-                .register_native_texture(&ctx.device, &t.view, FilterMode::Linear)
+                .register_native_texture(&ctx.device, &t.srgb_view(), FilterMode::Linear)
         });
 
         let mut main_camera = Camera::new(
@@ -170,30 +275,49 @@ impl ApplicationHack {
         let world_view1 = frontend::WorldView::new(
             &ctx,
             &mut egui_renderer,
+            scene.clone(),
             &mut egui_passes,
             &mut render_passes,
         );
         let mut stats_view = frontend::QuickView::new();
         let mut canvas_example_view = frontend::QuickView::new();
         let mut chunk_manager_view = frontend::QuickView::new();
-        let mut video_view = frontend::QuickView::new();
         let code_editor_view = frontend::CodeView::new();
         let mut profiler_view = frontend::QuickView::new();
+        let media_library_view = frontend::MediaLibraryView::new(media_library.clone());
+        let shader_view = frontend::ShaderView::new(shaders.clone());
+        let scene_view = frontend::SceneView::new(scene.clone());
+
+        let mut counter: usize = 9;
+
+        let video_tabs = video_sources.with_ref(|sources| {
+            sources
+                .iter()
+                .map(|source| {
+                    let tab = source
+                        .view
+                        .as_tab_handle(SurfaceIndex::main(), NodeIndex(counter));
+                    counter += 1;
+                    tab
+                })
+                .collect()
+        });
 
         let mut dock_state = DockState::new(vec![
             profiler_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(2)),
-            stats_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(1)), // canvas_example_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(1)),
+            media_library_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(5)),
+            shader_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(6)),
+            scene_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(7)),
+            chunk_manager_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(8)),
+            stats_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(1)),
+            canvas_example_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(3)),
         ]);
 
         {
             let [a, b] = dock_state.main_surface_mut().split_above(
                 NodeIndex::root(),
                 0.5,
-                vec![
-                    video_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(3)),
-                    // code_editor_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(6)),
-                    // stats_view.as_tab_handle(SurfaceIndex::main(), NodeIndex(8)),
-                ],
+                video_tabs,
             );
 
             dock_state.main_surface_mut().split_left(
@@ -217,7 +341,30 @@ impl ApplicationHack {
             // );
         }
 
-        let mut counter: usize = 9;
+        let mut render_graph = RenderGraph::new();
+
+        {
+            let scene = scene.clone();
+
+            render_graph.add_pass(
+                PassDesc::new("opaque")
+                    .writes(Attachment::Color)
+                    .writes(Attachment::Depth),
+                move |pass| scene.with_ref(|scene| scene.render_opaque(pass)),
+            );
+        }
+
+        {
+            let scene = scene.clone();
+
+            // Blends over the opaque pass, its depth keeps hidden quads hidden
+            render_graph.add_pass(
+                PassDesc::new("transparent")
+                    .reads(Attachment::Depth)
+                    .writes(Attachment::Color),
+                move |pass| scene.with_ref(|scene| scene.render_transparent(pass)),
+            );
+        }
 
         Self {
             os_window: os_window.clone(),
@@ -225,7 +372,30 @@ impl ApplicationHack {
             boxed_fn: Box::new(move |event_loop, window_id, event, mouse_delta| {
                 puffin::profile_function!();
 
-                video_handle.tick();
+                video_sources.with_ref(|sources| {
+                    for source in sources {
+                        source.tick();
+                    }
+                });
+
+                shaders.with(|shaders| {
+                    let changed = shaders.poll();
+
+                    if changed.is_empty() {
+                        return;
+                    }
+
+                    chunks_demo.with(|demo| demo.on_shaders_changed(&ctx, shaders, &changed));
+                    canvas_demo.with(|demo| demo.on_shaders_changed(&ctx, shaders, &changed));
+
+                    video_sources.with(|sources| {
+                        for source in sources {
+                            source
+                                .demo
+                                .with(|demo| demo.on_shaders_changed(&ctx, shaders, &changed));
+                        }
+                    });
+                });
 
                 if use_secondary_camera {
                     world_view1.with(|view| {
@@ -254,6 +424,8 @@ impl ApplicationHack {
                         main_camera.resize(size.width as f32, size.height as f32);
                     }
                     WindowEvent::CloseRequested => {
+                        // Keeps the pipelines of reloaded shaders and later videos
+                        ctx.pipelines.save_cache();
                         event_loop.exit();
                     }
                     WindowEvent::KeyboardInput { event, .. } => {
@@ -303,15 +475,28 @@ impl ApplicationHack {
                         camera_controller.update_camera(&mut main_camera, delta);
                         main_camera.compute();
 
-                        // TODO: This is also TRASH we should not
-                        // need to match against the enum
+                        world_view1.with(|view| view.update_camera(delta));
+
+                        let streaming_position =
+                            world_view1.with_ref(|view| view.secondary_camera.view.position);
 
-                        world_view1.with(|view| {
-                            let transform = view.get_transform(delta);
+                        // Any world view can edit, including the ones opened from the dock
+                        let block_edits: Vec<_> = render_passes
+                            .iter()
+                            .filter_map(|weak| weak.upgrade())
+                            .filter_map(|view| view.borrow_mut().block_edit.take())
+                            .collect();
 
-                            video_demo.update_location(&ctx, transform);
+                        chunks_demo.with(|demo| {
+                            for request in block_edits {
+                                demo.edit_block(request);
+                            }
+
+                            demo.update(&ctx, streaming_position);
                         });
 
+                        scene.with(|scene| scene.update_transforms(&ctx));
+
                         let frame = {
                             puffin::profile_scope!("Get current texture (Blocks due to VSync)");
                             // Get Surface Texture
@@ -324,47 +509,15 @@ impl ApplicationHack {
                             }
                         };
 
-                        // Try to update video texture
-                        if let Some(new_frame) = video_handle.get_current_frame() {
-                            video_demo.check_resize(&ctx, new_frame.resolution);
-
-                            match new_frame.data {
-                                FrameData::PlanarYuv420(planes) => {
-                                    video_demo.update_texture(
-                                        &ctx,
-                                        &planes.y_plane,
-                                        &planes.u_plane,
-                                        &planes.v_plane,
-                                    );
-                                }
+                        // Try to update video textures
+                        video_sources.with(|sources| {
+                            for source in sources {
+                                source.upload_current_frame(&ctx);
                             }
-                        }
+                        });
 
                         let frame_view = &frame.texture.create_view(&Default::default());
 
-                        let mut render_pass =
-                            |camera: &Camera,
-                             color_view: &wgpu::TextureView,
-                             depth_view: &wgpu::TextureView| {
-                                puffin::profile_scope!("Render pass");
-                                let mut encoder =
-                                    ctx.device.create_command_encoder(&Default::default());
-                                {
-                                    let view = SView::new(color_view, depth_view);
-                                    let mut pass = view.render_pass(&mut encoder).forget_lifetime();
-
-                                    chunks_demo.with(|u| {
-                                        u.setup_dynamic_camera(&ctx, &camera);
-                                        u.render_static(&mut pass);
-                                    });
-
-                                    video_demo.setup_dynamic_camera(&ctx, &camera);
-                                    video_demo.render_static(&mut pass);
-                                }
-
-                                ctx.queue.submit([encoder.finish()]);
-                            };
-
                         {
                             for item in &egui_passes {
                                 if let Some(rc) = item.upgrade() {
@@ -377,7 +530,9 @@ impl ApplicationHack {
                         {
                             for item in &render_passes {
                                 if let Some(rc) = item.upgrade() {
-                                    rc.borrow_mut().render_to(&mut render_pass);
+                                    let mut view = rc.borrow_mut();
+                                    view.render_to(&mut render_graph);
+                                    view.capture_frame();
                                 }
                             }
                             render_passes.retain(|weak| weak.upgrade().is_some());
@@ -385,14 +540,23 @@ impl ApplicationHack {
 
                         let v = dock_state.focused_leaf();
 
+                        let decoder_workers = decoder_pool.worker_count();
+
+                        let inner_world_view = world_view1.clone();
+
                         stats_view.ui(move |ui| {
                             ui.label(format!("FPS: {}", fps(delta)));
+                            ui.label(format!("Decoder workers: {decoder_workers}"));
 
                             if let Some((a, b)) = v {
                                 ui.label(format!("Focused surface {} on node {}", a.0, b.0));
                             } else {
                                 ui.label(format!("No focused tab"));
                             }
+
+                            ui.collapsing("Record view", |ui| {
+                                inner_world_view.with(|view| view.export.ui(ui));
+                            });
                         });
 
                         {
@@ -414,6 +578,7 @@ impl ApplicationHack {
                             handle_list.build_tabs(
                                 &ctx,
                                 &mut egui_renderer,
+                                &scene,
                                 &mut egui_passes,
                                 &mut render_passes,
                             );
@@ -428,12 +593,57 @@ impl ApplicationHack {
                                 counter += 1;
                             });
 
+                            let open_requests =
+                                media_library.with(|library| library.take_open_requests());
+
+                            for path in open_requests {
+                                let source = shaders.with(|shaders| {
+                                    VideoSource::open(
+                                        &ctx,
+                                        &os_window.borrow().surface_configuration,
+                                        &decoder_pool,
+                                        shaders,
+                                        path.clone(),
+                                        video_sources.with_ref(|sources| sources.len()),
+                                        &media_library
+                                            .with_ref(|library| library.audio_output.clone()),
+                                    )
+                                });
+
+                                if source.handle.is_ok() {
+                                    media_library.with(|library| library.add_recent(path));
+                                }
+
+                                dock_state.push_to_focused_leaf(
+                                    source
+                                        .view
+                                        .as_tab_handle(SurfaceIndex::main(), NodeIndex(counter)),
+                                );
+                                counter += 1;
+
+                                scene.with(|scene| {
+                                    spawn_video_entity(scene, videos_group, &source);
+                                });
+
+                                video_sources.with(|sources| sources.push(source));
+                            }
+
                             let inner_size = os_window.borrow().window.inner_size();
                             let outer_size = os_window.borrow().window.outer_size();
 
+                            // Record the canvas as it is this frame
+                            canvas_export.with(|export| {
+                                if export.is_recording() {
+                                    if let Ok(bytes) = skia_canvas.borrow_mut().as_unpremul_bytes() {
+                                        export.push_frame(bytes, Instant::now());
+                                    }
+                                }
+                            });
+
                             let inner = skia_canvas.clone();
                             let inner_text = skia_gpu_texture.clone();
                             let inner_ctx = ctx.clone();
+                            let inner_export = canvas_export.clone();
 
                             canvas_example_view.ui(move |ui| {
                                 ui.label(format!("Available Size {}", ui.available_size()));
@@ -464,148 +674,88 @@ impl ApplicationHack {
                                         u.update(&inner_ctx, &example_data);
                                     });
                                 }
-                            });
 
-                            let inner_ctx = ctx.clone();
+                                ui.separator();
 
-                            let inner_c_demo = chunks_demo.clone();
-
-                            chunk_manager_view.ui(move |ui| {
-                                if ui.button("Spawn Chunk").clicked() {
-                                    inner_c_demo.with(|u| u.spawn_chunk(&inner_ctx));
-                                }
+                                inner_export.with(|export| export.ui(ui));
                             });
 
-                            let value = video_handle.clone();
+                            let inner_c_demo = chunks_demo.clone();
 
-                            video_view.ui(move |ui: &mut egui::Ui| {
-                                value.with(|video| {
-                                    ui.label(format!("Movie avg framerate: {:.2}FPS", video.fps));
+                            chunk_manager_view.ui(move |ui| {
+                                inner_c_demo.with(|demo| {
+                                    let manager = &mut demo.manager;
 
+                                    ui.label(format!("Loaded chunks: {}", manager.loaded_count()));
+                                    ui.label(format!("Generating: {}", manager.pending_count()));
                                     ui.label(format!(
-                                        "Movie length: {}",
-                                        FancyDuration(video.total_duration)
+                                        "GPU memory: {:.1} MiB",
+                                        manager.gpu_bytes() as f64 / (1024.0 * 1024.0)
                                     ));
-                                });
-
-                                ui.horizontal(|ui| {
-                                    if ui.button("Pause").clicked() {
-                                        value.pause();
-                                        // let _ = value.try_send(MP4Command::Pause);
-                                    }
-                                    if ui.button("Play").clicked() {
-                                        value.play();
-                                        // let _ = value.try_send(MP4Command::Play);
-                                    }
-                                    if ui.button("Stop").clicked() {
-                                        value.stop();
-                                        // let _ = value.try_send(MP4Command::Stop);
-                                    }
-                                    if ui.button("SkipForward").clicked() {
-                                        // let _ = value.try_send(MP4Command::SkipForward);
-                                    }
-                                    if ui.button("SkipBackward").clicked() {
-                                        // let _ = value.try_send(MP4Command::SkipBackward);
-                                    }
-                                    if ui.button("Seek(Duration)").clicked() {
-                                        // let _ =
-                                        //     value.try_send(MP4Command::Seek(Duration::from_millis(0)));
-                                    }
-                                });
-
-                                let prog = value.get_realtime_progress();
-                                let mut new_prog = prog;
 
-                                ui.horizontal(|ui| {
-                                    let available_width = ui.available_width();
+                                    let (vertices, indices) = manager.mesh_counts();
 
-                                    ui.spacing_mut().slider_width = available_width;
-
-                                    ui.add(Slider::new(&mut new_prog, 0.0..=1.0).show_value(false))
-                                });
+                                    ui.label(format!("Vertices: {vertices}"));
+                                    ui.label(format!("Indices: {indices}"));
+                                    ui.label(
+                                        "Right click in the view removes a block, \
+                                         shift + right click places one",
+                                    );
 
-                                value.with(|v| {
-                                    ui.horizontal(|ui: &mut egui::Ui| {
-                                        let available_width = ui.available_width();
+                                    ui.separator();
 
-                                        ui.spacing_mut().slider_width = available_width / 2.0;
+                                    let settings = &mut manager.settings;
 
-                                        ui.add(
-                                            Slider::new(&mut v.play_speed, 0.0..=10.0)
-                                                .show_value(true),
+                                    // Same terrain both ways, compare the counts above
+                                    ui.horizontal(|ui| {
+                                        ui.label("Mesher");
+                                        ui.selectable_value(
+                                            &mut settings.mesher,
+                                            Mesher::PerFace,
+                                            "Per face",
+                                        );
+                                        ui.selectable_value(
+                                            &mut settings.mesher,
+                                            Mesher::Greedy,
+                                            "Greedy",
                                         );
                                     });
-                                });
-
-                                let mut seek_target = None;
-
-                                let current = value
-                                    .get_next_pts()
-                                    .map(|t| FancyDuration(t))
-                                    .unwrap_or(FancyDuration(Duration::from_millis(0)));
-
-                                ui.label(format!(
-                                    "Frame PTS: {}",
-                                    value
-                                        .get_frame_pts()
-                                        .map(|t| FancyDuration(t))
-                                        .unwrap_or(FancyDuration(Duration::from_millis(0)))
-                                ));
-
-                                value.with(|video| {
-                                    if new_prog == prog {
-                                        ui.label(format!(
-                                            "Current Time: {}",
-                                            FancyDuration(video.total_duration.mul_f64(prog))
-                                        ));
 
-                                        ui.label(format!("Next Frame: {}", current));
-                                    } else {
-                                        let new_duration = video.total_duration.mul_f64(new_prog);
-
-                                        ui.label(format!(
-                                            "Seek Target: {}",
-                                            FancyDuration(new_duration)
-                                        ));
+                                    ui.add(
+                                        egui::Slider::new(&mut settings.radius, 1..=16)
+                                            .text("Radius (chunks)"),
+                                    );
+                                    ui.add(
+                                        egui::Slider::new(&mut settings.uploads_per_frame, 1..=32)
+                                            .text("Uploads per frame"),
+                                    );
 
-                                        ui.label(format!("Next Frame: None"));
+                                    let mut budget_mib = settings.max_gpu_bytes / (1024 * 1024);
 
-                                        seek_target = Some(new_duration);
+                                    if ui
+                                        .add(
+                                            egui::Slider::new(&mut budget_mib, 16..=4096)
+                                                .text("Memory cap (MiB)"),
+                                        )
+                                        .changed()
+                                    {
+                                        settings.max_gpu_bytes = budget_mib * 1024 * 1024;
                                     }
                                 });
+                            });
 
-                                if let Some(seek_target) = seek_target.take() {
-                                    value.seek(seek_target);
-                                }
-
-                                ui.add_space(12.0);
-
-                                ui.label(format!(
-                                    "Last frame decode latency {}",
-                                    value
-                                        .get_last_decode_cost()
-                                        .map(|t| FancyDuration(t))
-                                        .unwrap_or(FancyDuration(Duration::from_millis(0)))
-                                ));
-
-                                ui.label(format!(
-                                    "Realtime PTS {}",
-                                    FancyDuration(value.get_pts())
-                                ));
-
-                                ui.label(format!(
-                                    "Realtime PTS {}",
-                                    FancyDuration(value.get_pts())
-                                ));
-
-                                ui.label(format!("Dropped Frames: {}", value.get_dropped_frames()));
-
-                                let (len, cap) = value.get_buffer_size();
-                                let health = value.get_buffer_health();
-
-                                ui.label(format!("Buffer Health: {len}/{cap}"));
+                            video_sources.with_ref(|sources| {
+                                for source in sources {
+                                    let path = source.path.clone();
+                                    let handle = source.handle.clone();
+                                    let filmstrip = source.filmstrip.clone();
 
-                                ui.add(ProgressBar::new(health as f32));
+                                    source.view.clone().ui(move |ui: &mut egui::Ui| {
+                                        filmstrip.with(|filmstrip| {
+                                            frontend::video_controls(ui, &path, &handle, filmstrip)
+                                        });
+                                    });
+                                }
                             });
 
                             profiler_view.ui(|ui| {
@@ -0,0 +1,172 @@
+use crate::camera::Camera;
+use crate::gpu::{
+    BindGroupBuilder, GPUCtx, GPUSampler, GPUTexture, LayoutRef, PipelineKey, ShaderError,
+    ShaderFile, Uniform,
+};
+use crate::multimath::Mat4Bytes;
+use egui_wgpu::wgpu;
+use glam::*;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// A textured quad, same vertices as the video one
+pub use super::video::{ModelBundle, Vertex, VertexFormat};
+
+pub struct BindGroup0 {
+    pub bind_group: wgpu::BindGroup,
+    camera: Uniform<Mat4Bytes>,
+    sampler: GPUSampler,
+}
+
+impl BindGroup0 {
+    fn builder<'a>(
+        camera: Option<&'a Uniform<Mat4Bytes>>,
+        sampler: Option<&'a GPUSampler>,
+        texture: Option<&'a GPUTexture>,
+    ) -> BindGroupBuilder<'a> {
+        BindGroupBuilder::new()
+            .uniform(wgpu::ShaderStages::VERTEX, camera)
+            .sampler(sampler)
+            .texture(texture)
+    }
+
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None, None, None).build_layout(ctx)
+    }
+
+    pub fn update_globals(&self, ctx: &GPUCtx, camera: &Camera) {
+        self.camera.update(ctx, Mat4Bytes(camera.matrix));
+    }
+
+    /// `texture` is owned by the canvas, the bind group only views it
+    pub fn create(ctx: &GPUCtx, texture: &GPUTexture) -> Self {
+        let camera = Uniform::create(ctx, Mat4Bytes(Mat4::IDENTITY));
+
+        let sampler = GPUSampler::create(
+            ctx,
+            wgpu::FilterMode::Linear,
+            wgpu::AddressMode::ClampToEdge,
+        );
+
+        let bind_group = Self::builder(Some(&camera), Some(&sampler), Some(texture)).build(ctx);
+
+        Self {
+            bind_group,
+            camera,
+            sampler,
+        }
+    }
+}
+
+pub struct BindGroup1 {
+    pub bind_group: wgpu::BindGroup,
+    transform: Uniform<Mat4Bytes>,
+}
+
+impl BindGroup1 {
+    fn builder(transform: Option<&Uniform<Mat4Bytes>>) -> BindGroupBuilder<'_> {
+        BindGroupBuilder::new().uniform(wgpu::ShaderStages::VERTEX, transform)
+    }
+
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None).build_layout(ctx)
+    }
+
+    pub fn update_transform(&self, ctx: &GPUCtx, transform: Mat4) {
+        self.transform.update(ctx, Mat4Bytes(transform));
+    }
+
+    pub fn create(ctx: &GPUCtx, transform: Mat4) -> Self {
+        let transform = Uniform::create(ctx, Mat4Bytes(transform));
+
+        let bind_group = Self::builder(Some(&transform)).build(ctx);
+
+        Self {
+            bind_group,
+            transform,
+        }
+    }
+}
+
+pub const SHADER: &str = "canvas_quad";
+
+pub fn shader_file() -> ShaderFile {
+    ShaderFile::new(
+        SHADER,
+        PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/pipelines/canvas_quad.wgsl"
+        )),
+        include_str!("./canvas_quad.wgsl"),
+    )
+}
+
+pub struct Pipeline {
+    pub pipeline: Rc<wgpu::RenderPipeline>,
+}
+
+impl Pipeline {
+    /// Blends premultiplied alpha and leaves the depth buffer alone,
+    /// so the quad has to be drawn after the opaque geometry
+    pub fn create(
+        ctx: &GPUCtx,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Result<Self, ShaderError> {
+        let key = PipelineKey {
+            shader: SHADER,
+            format,
+            blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            vertex_layout: &VertexFormat::LAYOUT,
+            cull_mode: None,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            bind_group_layouts: vec![
+                LayoutRef(BindGroup0::get_layout(ctx)),
+                LayoutRef(BindGroup1::get_layout(ctx)),
+            ],
+        };
+
+        let pipeline = ctx.pipelines.render_pipeline(ctx, key, source, |key, shader, cache| {
+            let pipeline_layout = key.create_layout(ctx);
+
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(SHADER),
+                    layout: Some(&pipeline_layout),
+                    cache,
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: key.vertex_layout,
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: key.format,
+                            blend: key.blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        cull_mode: key.cull_mode,
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: key.depth_stencil.clone(),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
@@ -0,0 +1,60 @@
+struct Vertex {
+    @location(0) position: vec4<f32>,
+    @location(1) tex_coord: vec2<f32>,
+}
+
+struct PerInstance {
+    transform: mat4x4<f32>,
+};
+
+struct Locals {
+    transform: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> locals: Locals;
+
+@group(0) @binding(1)
+var main_sampler: sampler;
+
+@group(0) @binding(2)
+var canvas: texture_2d<f32>;
+
+@group(1) @binding(0)
+var<uniform> perInstance: PerInstance;
+
+struct VertexOutput {
+    @location(0) tex_coord: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coord = vertex.tex_coord;
+    out.position = locals.transform * perInstance.transform * vec4<f32>(vertex.position.xyz, 1.0);
+    return out;
+}
+
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let below_threshold = srgb / 12.92;
+    let above_threshold = pow((srgb + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+
+    return select(above_threshold, below_threshold, srgb <= vec3<f32>(0.04045));
+}
+
+// Skia premultiplies the sRGB encoded color and the texture is
+// Rgba8Unorm, so the sample is alpha * color, still encoded and filtered
+// that way. Blending happens in linear space and wants alpha * decode(color).
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sampled = textureSample(canvas, main_sampler, in.tex_coord);
+
+    if sampled.a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    let color = srgb_to_linear(clamp(sampled.rgb / sampled.a, vec3<f32>(0.0), vec3<f32>(1.0)));
+
+    return vec4<f32>(color * sampled.a, sampled.a);
+}
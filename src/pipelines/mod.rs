@@ -1,6 +1,5 @@
-
+pub mod canvas_quad;
 
 pub mod quad_mesh;
 
 pub mod video;
-
@@ -1,10 +1,15 @@
 use crate::camera::Camera;
-use crate::gpu::GPUCtx;
-use crate::multimath::{Mat4Bytes, Vec4Bytes};
+use crate::gpu::{
+    BindGroupBuilder, GPUCtx, GPUSampler, GPUTexture, LayoutRef, PipelineKey, ShaderError,
+    ShaderFile, Uniform,
+};
+use crate::multimath::Mat4Bytes;
 use bytemuck::{Pod, Zeroable};
 use egui_wgpu::wgpu;
 use glam::*;
 use std::mem::offset_of;
+use std::path::PathBuf;
+use std::rc::Rc;
 use wgpu::util::DeviceExt;
 
 #[repr(C)]
@@ -84,224 +89,237 @@ impl VertexFormat {
 
 pub struct BindGroup0 {
     pub bind_group: wgpu::BindGroup,
-    uniform_buffer: wgpu::Buffer,
+    camera: Uniform<Mat4Bytes>,
 }
 
 impl BindGroup0 {
-    const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(64),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-        ],
-    };
+    fn builder<'a>(
+        camera: Option<&'a Uniform<Mat4Bytes>>,
+        texture: Option<&'a GPUTexture>,
+        sampler: Option<&'a GPUSampler>,
+    ) -> BindGroupBuilder<'a> {
+        BindGroupBuilder::new()
+            .uniform(wgpu::ShaderStages::VERTEX, camera)
+            .texture(texture)
+            .sampler(sampler)
+    }
 
-    pub fn get_layout(ctx: &GPUCtx) -> wgpu::BindGroupLayout {
-        ctx.device.create_bind_group_layout(&Self::LAYOUT)
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None, None, None).build_layout(ctx)
     }
 
     pub fn update_globals(&self, ctx: &GPUCtx, camera: &Camera) {
-        ctx.queue
-            .write_buffer(&self.uniform_buffer, 0, Mat4Bytes(camera.matrix).as_bytes());
+        self.camera.update(ctx, Mat4Bytes(camera.matrix));
     }
 
+    /// `texels` come from a Skia canvas, sRGB encoded and opaque
     pub fn create(ctx: &GPUCtx, size: u32, texels: Vec<u8>) -> Self {
-        let texture_extent = wgpu::Extent3d {
-            width: size,
-            height: size,
-            depth_or_array_layers: 1,
-        };
-
-        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
-            label: None,
-            size: texture_extent,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-        });
-
-        ctx.queue.write_texture(
-            texture.as_image_copy(),
-            &texels,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(size * 4),
-                rows_per_image: None,
-            },
-            texture_extent,
+        let texture = GPUTexture::create(
+            ctx,
+            size,
+            size,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            [0u8; 4],
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         );
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        texture.update(ctx, &texels);
 
-        let uniform_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: Mat4Bytes(Mat4::IDENTITY).as_bytes(),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+        let camera = Uniform::create(ctx, Mat4Bytes(Mat4::IDENTITY));
 
-        let sampler = ctx.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Texture sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = GPUSampler::create(
+            ctx,
+            wgpu::FilterMode::Nearest,
+            wgpu::AddressMode::Repeat,
+        );
 
-        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &Self::get_layout(ctx),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-            label: None,
-        });
+        let bind_group = Self::builder(Some(&camera), Some(&texture), Some(&sampler)).build(ctx);
 
-        Self {
-            bind_group,
-            uniform_buffer,
-        }
+        Self { bind_group, camera }
     }
 }
 
 pub struct BindGroup1 {
     pub bind_group: wgpu::BindGroup,
+    transform: Uniform<Mat4Bytes>,
 }
 
 impl BindGroup1 {
-    const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(16),
-            },
-            count: None,
-        }],
-    };
+    fn builder(transform: Option<&Uniform<Mat4Bytes>>) -> BindGroupBuilder<'_> {
+        BindGroupBuilder::new().uniform(wgpu::ShaderStages::VERTEX, transform)
+    }
 
-    pub fn get_layout(ctx: &GPUCtx) -> wgpu::BindGroupLayout {
-        ctx.device.create_bind_group_layout(&Self::LAYOUT)
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None).build_layout(ctx)
     }
 
-    pub fn create(ctx: &GPUCtx, position: Vec4) -> Self {
-        let position_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: Vec4Bytes(position).as_bytes(),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+    pub fn update_transform(&self, ctx: &GPUCtx, transform: Mat4) {
+        self.transform.update(ctx, Mat4Bytes(transform));
+    }
 
-        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &BindGroup1::get_layout(ctx),
-            label: None,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: position_buffer.as_entire_binding(),
-            }],
-        });
+    pub fn create(ctx: &GPUCtx, transform: Mat4) -> Self {
+        let transform = Uniform::create(ctx, Mat4Bytes(transform));
+
+        let bind_group = Self::builder(Some(&transform)).build(ctx);
 
-        Self { bind_group }
+        Self {
+            bind_group,
+            transform,
+        }
     }
 }
 
+pub const SHADER: &str = "quad_mesh";
+
+/// `quad_mesh.wgsl` is read from the source tree so edits show up without
+/// a rebuild, the embedded copy covers running from anywhere else
+pub fn shader_file() -> ShaderFile {
+    ShaderFile::new(
+        SHADER,
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/pipelines/quad_mesh.wgsl")),
+        include_str!("./quad_mesh.wgsl"),
+    )
+}
+
 pub struct Pipeline {
-    pub pipeline: wgpu::RenderPipeline,
+    pub pipeline: Rc<wgpu::RenderPipeline>,
 }
 
 impl Pipeline {
-    pub fn create(ctx: &GPUCtx, format: wgpu::TextureFormat) -> Self {
-        let shader = ctx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("./quad_mesh.wgsl"));
+    pub fn create(
+        ctx: &GPUCtx,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Result<Self, ShaderError> {
+        let key = PipelineKey {
+            shader: SHADER,
+            format,
+            blend: None,
+            vertex_layout: &VertexFormat::LAYOUT,
+            cull_mode: Some(wgpu::Face::Back),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            bind_group_layouts: vec![
+                LayoutRef(BindGroup0::get_layout(ctx)),
+                LayoutRef(BindGroup1::get_layout(ctx)),
+            ],
+        };
 
-        let pipeline_layout = ctx
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: None,
-                bind_group_layouts: &[&BindGroup0::get_layout(ctx), &BindGroup1::get_layout(ctx)],
-                push_constant_ranges: &[],
-            });
+        let pipeline = ctx.pipelines.render_pipeline(ctx, key, source, |key, shader, cache| {
+            let pipeline_layout = key.create_layout(ctx);
 
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                cache: None,
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &VertexFormat::LAYOUT,
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(format.into())],
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    cull_mode: Some(wgpu::Face::Back),
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    ..Default::default()
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32Float,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                }),
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(SHADER),
+                    layout: Some(&pipeline_layout),
+                    cache,
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: key.vertex_layout,
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: key.format,
+                            blend: key.blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        cull_mode: key.cull_mode,
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: key.depth_stencil.clone(),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::test_utils::{
+        assert_pixels_close, linear_to_srgb, render_offscreen, srgb_to_linear, test_ctx,
+    };
+    use crate::gpu::OffscreenTarget;
+
+    const SIZE: u32 = 4;
+
+    /// sRGB encoded, the texture decodes it when sampled
+    const TEXEL: [u8; 4] = [200, 100, 50, 255];
+
+    /// A quad facing the viewer over the left half of clip space, and one
+    /// wound the other way over the right half that back-face culling drops
+    fn halves() -> ModelBundle {
+        let normal = [0, 0, 1];
+
+        ModelBundle {
+            vertex_data: vec![
+                Vertex::new([-1.0, -1.0, 0.5], normal, [0, 1]),
+                Vertex::new([0.0, -1.0, 0.5], normal, [1, 1]),
+                Vertex::new([0.0, 1.0, 0.5], normal, [1, 0]),
+                Vertex::new([-1.0, 1.0, 0.5], normal, [0, 0]),
+                Vertex::new([0.0, -1.0, 0.5], normal, [0, 1]),
+                Vertex::new([0.0, 1.0, 0.5], normal, [0, 0]),
+                Vertex::new([1.0, 1.0, 0.5], normal, [1, 0]),
+                Vertex::new([1.0, -1.0, 0.5], normal, [1, 1]),
+            ],
+            index_data: vec![0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4],
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn renders_offscreen() {
+        let ctx = test_ctx();
+
+        let pipeline =
+            Pipeline::create(&ctx, OffscreenTarget::FORMAT, shader_file().embedded()).unwrap();
+
+        let texels = TEXEL.repeat((SIZE * SIZE) as usize);
+        let bind_group_0 = BindGroup0::create(&ctx, SIZE, texels);
+        let bind_group_1 = BindGroup1::create(&ctx, Mat4::IDENTITY);
+
+        let model = halves();
+        let vertex_format = VertexFormat::create(&ctx, &model);
+
+        let pixels = render_offscreen(&ctx, SIZE, SIZE, wgpu::Color::BLACK, |pass| {
+            pass.set_pipeline(&pipeline.pipeline);
+            pass.set_bind_group(0, &bind_group_0.bind_group, &[]);
+            pass.set_bind_group(1, &bind_group_1.bind_group, &[]);
+            pass.set_index_buffer(vertex_format.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            pass.set_vertex_buffer(0, vertex_format.vertex_buffer.slice(..));
+            pass.draw_indexed(0..model.index_data.len() as u32, 0, 0..1);
+        });
+
+        // A +Z normal is lit below the 0.5 floor of the shader
+        let lit = TEXEL.map(|c| {
+            let linear = srgb_to_linear(c as f32 / 255.0) * 0.5;
+            (linear_to_srgb(linear) * 255.0).round() as u8
+        });
+
+        let expected: Vec<[u8; 4]> = (0..SIZE * SIZE)
+            .map(|i| match i % SIZE < SIZE / 2 {
+                true => [lit[0], lit[1], lit[2], 255],
+                // The clear color, the right quad faces away
+                false => [0, 0, 0, 255],
+            })
+            .collect();
 
-        Self { pipeline }
+        assert_pixels_close(&pixels, &expected);
     }
 }
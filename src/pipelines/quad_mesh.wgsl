@@ -5,7 +5,7 @@ struct VertexOutput {
 };
 
 struct PerInstance {
-    position: vec4<f32>,
+    transform: mat4x4<f32>,
 };
 
 
@@ -27,8 +27,8 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coord = tex_coord;
-    out.position = locals.transform * vec4<f32>(position.xyz + perInstance.position.xyz, 1.0);
-    out.normal = normal.xyz;
+    out.position = locals.transform * perInstance.transform * vec4<f32>(position.xyz, 1.0);
+    out.normal = (perInstance.transform * vec4<f32>(normal.xyz, 0.0)).xyz;
     return out;
 }
 
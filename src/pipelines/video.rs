@@ -1,10 +1,16 @@
 use crate::camera::Camera;
-use crate::gpu::{GPUCtx, GPUSampler, GPUTexture};
+use crate::gpu::{
+    BindGroupBuilder, GPUCtx, GPUSampler, GPUTexture, LayoutRef, PipelineKey, ShaderError,
+    ShaderFile, StagingUpload, Uniform,
+};
 use crate::multimath::Mat4Bytes;
-use bytemuck::{NoUninit, Pod, Zeroable};
+use crate::video::ColorInfo;
+use bytemuck::{Pod, Zeroable};
 use egui_wgpu::wgpu;
 use glam::*;
 use std::mem::offset_of;
+use std::path::PathBuf;
+use std::rc::Rc;
 use wgpu::util::DeviceExt;
 
 #[repr(C)]
@@ -77,9 +83,29 @@ impl VertexFormat {
     }
 }
 
+/// `ColorInfo` as the fragment shader reads it
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct ColorConversion {
+    yuv_to_rgb: [f32; 16],
+    transfer: u32,
+    _padding: [u32; 3],
+}
+
+impl ColorConversion {
+    pub fn new(color: &ColorInfo) -> Self {
+        Self {
+            yuv_to_rgb: color.yuv_to_rgb_matrix().to_cols_array(),
+            transfer: color.transfer.shader_id(),
+            _padding: [0; 3],
+        }
+    }
+}
+
 pub struct BindGroup0 {
     pub bind_group: wgpu::BindGroup,
-    uniform_buffer: wgpu::Buffer,
+    camera: Uniform<Mat4Bytes>,
+    conversion: Uniform<ColorConversion>,
 
     y_texture: GPUTexture,
     u_texture: GPUTexture,
@@ -89,66 +115,79 @@ pub struct BindGroup0 {
 }
 
 impl BindGroup0 {
-    pub fn update_texture<T: NoUninit>(
+    /// All three planes go through `upload` in one submit
+    pub fn update_texture(
         &self,
         ctx: &GPUCtx,
-        y_data: &[T],
-        u_data: &[T],
-        v_data: &[T],
+        upload: &mut StagingUpload,
+        y_data: &[u8],
+        u_data: &[u8],
+        v_data: &[u8],
     ) {
-        self.y_texture.update(&ctx, y_data);
-        self.u_texture.update(&ctx, u_data);
-        self.v_texture.update(&ctx, v_data);
+        upload.upload(
+            ctx,
+            &[
+                (&self.y_texture, y_data),
+                (&self.u_texture, u_data),
+                (&self.v_texture, v_data),
+            ],
+        );
     }
-    pub(crate) fn resize_textures(&mut self, ctx: &GPUCtx, width: u32, height: u32) {
+
+    pub fn update_color(&self, ctx: &GPUCtx, color: &ColorInfo) {
+        self.conversion.update(ctx, ColorConversion::new(color));
+    }
+    pub(crate) fn resize_textures(
+        &mut self,
+        ctx: &GPUCtx,
+        width: u32,
+        height: u32,
+        uv_width: u32,
+        uv_height: u32,
+    ) {
         let y_width = width;
         let y_height = height;
-        let uv_width = width / 2;
-        let uv_height = height / 2;
 
         self.y_texture.resize(&ctx, y_width, y_height, 0u8);
         self.u_texture.resize(&ctx, uv_width, uv_height, 0u8);
         self.v_texture.resize(&ctx, uv_width, uv_height, 0u8);
 
-        self.bind_group = Self::generate_bind_group(
-            ctx,
-            &self.uniform_buffer,
-            &self.sampler,
-            &self.y_texture,
-            &self.u_texture,
-            &self.v_texture,
-        );
+        self.bind_group = Self::builder(
+            Some(&self.camera),
+            Some(&self.sampler),
+            Some(&self.y_texture),
+            Some(&self.u_texture),
+            Some(&self.v_texture),
+            Some(&self.conversion),
+        )
+        .build(ctx);
     }
 }
 
 impl BindGroup0 {
-    const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(64),
-                },
-                count: None,
-            },
-            GPUSampler::get_layout(1),
-            GPUTexture::get_layout(2),
-            GPUTexture::get_layout(3),
-            GPUTexture::get_layout(4),
-        ],
-    };
+    fn builder<'a>(
+        camera: Option<&'a Uniform<Mat4Bytes>>,
+        sampler: Option<&'a GPUSampler>,
+        y_texture: Option<&'a GPUTexture>,
+        u_texture: Option<&'a GPUTexture>,
+        v_texture: Option<&'a GPUTexture>,
+        conversion: Option<&'a Uniform<ColorConversion>>,
+    ) -> BindGroupBuilder<'a> {
+        BindGroupBuilder::new()
+            .uniform(wgpu::ShaderStages::VERTEX, camera)
+            .sampler(sampler)
+            .texture(y_texture)
+            .texture(u_texture)
+            .texture(v_texture)
+            .uniform(wgpu::ShaderStages::FRAGMENT, conversion)
+    }
 
-    pub fn get_layout(ctx: &GPUCtx) -> wgpu::BindGroupLayout {
-        ctx.device.create_bind_group_layout(&Self::LAYOUT)
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None, None, None, None, None, None).build_layout(ctx)
     }
 
     pub fn update_globals(&self, ctx: &GPUCtx, camera: &Camera) {
-        ctx.queue
-            .write_buffer(&self.uniform_buffer, 0, Mat4Bytes(camera.matrix).as_bytes());
+        self.camera.update(ctx, Mat4Bytes(camera.matrix));
     }
 
     pub fn create(ctx: &GPUCtx, width: u32, height: u32) -> Self {
@@ -179,13 +218,8 @@ impl BindGroup0 {
             usage,
         );
 
-        let uniform_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: Mat4Bytes(Mat4::IDENTITY).as_bytes(),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+        let camera = Uniform::create(ctx, Mat4Bytes(Mat4::IDENTITY));
+        let conversion = Uniform::create(ctx, ColorConversion::new(&ColorInfo::default()));
 
         let sampler = GPUSampler::create(
             &ctx,
@@ -193,156 +227,133 @@ impl BindGroup0 {
             wgpu::AddressMode::ClampToEdge,
         );
 
-        let bind_group = Self::generate_bind_group(
-            ctx,
-            &uniform_buffer,
-            &sampler,
-            &y_texture,
-            &u_texture,
-            &v_texture,
-        );
+        let bind_group = Self::builder(
+            Some(&camera),
+            Some(&sampler),
+            Some(&y_texture),
+            Some(&u_texture),
+            Some(&v_texture),
+            Some(&conversion),
+        )
+        .build(ctx);
 
         Self {
             bind_group,
-            uniform_buffer,
+            camera,
+            conversion,
             y_texture,
             u_texture,
             v_texture,
             sampler,
         }
     }
-
-    fn generate_bind_group(
-        ctx: &GPUCtx,
-        uniform_buffer: &wgpu::Buffer,
-        sampler: &GPUSampler,
-        y_texture_view: &GPUTexture,
-        u_texture_view: &GPUTexture,
-        v_texture_view: &GPUTexture,
-    ) -> wgpu::BindGroup {
-        ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &Self::get_layout(ctx),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                },
-                sampler.get_binding(1),
-                y_texture_view.get_binding(2),
-                u_texture_view.get_binding(3),
-                v_texture_view.get_binding(4),
-            ],
-            label: None,
-        })
-    }
 }
 
 pub struct BindGroup1 {
     pub bind_group: wgpu::BindGroup,
-    buffer: wgpu::Buffer,
+    transform: Uniform<Mat4Bytes>,
 }
 
 impl BindGroup1 {
-    const LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> = wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[wgpu::BindGroupLayoutEntry {
-            binding: 0,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(64),
-            },
-            count: None,
-        }],
-    };
+    fn builder(transform: Option<&Uniform<Mat4Bytes>>) -> BindGroupBuilder<'_> {
+        BindGroupBuilder::new().uniform(wgpu::ShaderStages::VERTEX, transform)
+    }
 
-    pub fn get_layout(ctx: &GPUCtx) -> wgpu::BindGroupLayout {
-        ctx.device.create_bind_group_layout(&Self::LAYOUT)
+    pub fn get_layout(ctx: &GPUCtx) -> Rc<wgpu::BindGroupLayout> {
+        Self::builder(None).build_layout(ctx)
     }
 
     pub fn update_transform(&self, ctx: &GPUCtx, transform: Mat4) {
-        ctx.queue
-            .write_buffer(&self.buffer, 0, Mat4Bytes(transform).as_bytes());
+        self.transform.update(ctx, Mat4Bytes(transform));
     }
 
     pub fn create(ctx: &GPUCtx, transform: Mat4) -> Self {
-        let position_buffer = ctx
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: Mat4Bytes(transform).as_bytes(),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+        let transform = Uniform::create(ctx, Mat4Bytes(transform));
 
-        let bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &BindGroup1::get_layout(ctx),
-            label: None,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: position_buffer.as_entire_binding(),
-            }],
-        });
+        let bind_group = Self::builder(Some(&transform)).build(ctx);
 
         Self {
             bind_group,
-            buffer: position_buffer,
+            transform,
         }
     }
 }
 
+pub const SHADER: &str = "video";
+
+/// Hot-reloaded like the quad mesh shader, see `quad_mesh::shader_file`
+pub fn shader_file() -> ShaderFile {
+    ShaderFile::new(
+        SHADER,
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/pipelines/video.wgsl")),
+        include_str!("./video.wgsl"),
+    )
+}
+
 pub struct Pipeline {
-    pub pipeline: wgpu::RenderPipeline,
+    pub pipeline: Rc<wgpu::RenderPipeline>,
 }
 
 impl Pipeline {
-    pub fn create(ctx: &GPUCtx, format: wgpu::TextureFormat) -> Self {
-        let shader = ctx
-            .device
-            .create_shader_module(wgpu::include_wgsl!("./video.wgsl"));
-
-        let pipeline_layout = ctx
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: None,
-                bind_group_layouts: &[&BindGroup0::get_layout(ctx), &BindGroup1::get_layout(ctx)],
-                push_constant_ranges: &[],
-            });
-
-        let pipeline = ctx
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                cache: None,
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &VertexFormat::LAYOUT,
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(format.into())],
-                    compilation_options: Default::default(),
-                }),
-                primitive: wgpu::PrimitiveState {
-                    cull_mode: None,
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    ..Default::default()
-                },
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32Float,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                }),
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
-
-        Self { pipeline }
+    pub fn create(
+        ctx: &GPUCtx,
+        format: wgpu::TextureFormat,
+        source: &str,
+    ) -> Result<Self, ShaderError> {
+        let key = PipelineKey {
+            shader: SHADER,
+            format,
+            blend: None,
+            vertex_layout: &VertexFormat::LAYOUT,
+            cull_mode: None,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            bind_group_layouts: vec![
+                LayoutRef(BindGroup0::get_layout(ctx)),
+                LayoutRef(BindGroup1::get_layout(ctx)),
+            ],
+        };
+
+        let pipeline = ctx.pipelines.render_pipeline(ctx, key, source, |key, shader, cache| {
+            let pipeline_layout = key.create_layout(ctx);
+
+            ctx.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(SHADER),
+                    layout: Some(&pipeline_layout),
+                    cache,
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: key.vertex_layout,
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: key.format,
+                            blend: key.blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        cull_mode: key.cull_mode,
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil: key.depth_stencil.clone(),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+        })?;
+
+        Ok(Self { pipeline })
     }
 }
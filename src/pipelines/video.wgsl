@@ -26,6 +26,16 @@ var u_color: texture_2d<f32>;
 @group(0) @binding(4)
 var v_color: texture_2d<f32>;
 
+struct ColorConversion {
+    // (Y, U, V, 1) to R'G'B', range offsets included
+    yuv_to_rgb: mat4x4<f32>,
+    // 0 sRGB, 1 BT.1886, 2 linear
+    transfer: u32,
+};
+
+@group(0) @binding(5)
+var<uniform> conversion: ColorConversion;
+
 @group(1) @binding(0)
 var<uniform> perInstance: PerInstance;
 
@@ -44,17 +54,29 @@ struct VertexOutput {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var y = textureSample(y_color, main_sampler, in.tex_coord).x;
-    var u = textureSample(u_color, main_sampler, in.tex_coord).x;
-    var v = textureSample(v_color, main_sampler, in.tex_coord).x;
+    let y = textureSample(y_color, main_sampler, in.tex_coord).x;
+    let u = textureSample(u_color, main_sampler, in.tex_coord).x;
+    let v = textureSample(v_color, main_sampler, in.tex_coord).x;
 
-    let r = y + 1.40200 * (v - 128.0 / 255.0);
-    let g = y - 0.34414 * (u - 128.0 / 255.0) - 0.71414 * (v - 128.0 / 255.0);
-    let b = y + 1.77200 * (u - 128.0 / 255.0);
+    let rgb = (conversion.yuv_to_rgb * vec4<f32>(y, u, v, 1.0)).rgb;
+    let nonlinear = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
 
-    let xlinear = vec4(clamp(r, 0.0, 1.0), clamp(g, 0.0, 1.0), clamp(b, 0.0, 1.0), 1.0);
+    // The render targets are sRGB, they encode the linear result again
+    return vec4(to_linear(nonlinear, conversion.transfer), 1.0);
+}
 
-    return vec4(srgb_to_linear(xlinear.xyz), 1.0);
+fn to_linear(nonlinear: vec3<f32>, transfer: u32) -> vec3<f32> {
+    switch transfer {
+        case 1u: {
+            return pow(nonlinear, vec3<f32>(2.4));
+        }
+        case 2u: {
+            return nonlinear;
+        }
+        default: {
+            return srgb_to_linear(nonlinear);
+        }
+    }
 }
 
 fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
@@ -68,4 +90,4 @@ fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
 
     // Use smoothstep to blend between the two cases based on the threshold
     return mix(below_threshold, above_threshold, step(threshold, srgb));
-}
\ No newline at end of file
+}
@@ -0,0 +1,479 @@
+use crate::camera::Camera;
+use crate::gpu::{GPUCtx, SView};
+use egui_wgpu::wgpu;
+use std::collections::{HashMap, HashSet};
+
+/// How many executes a transient texture outlives its last use. Views of
+/// other sizes run in between every frame, only resized views let go.
+const MAX_IDLE_RUNS: u32 = 16;
+
+/// What a pass renders into or samples from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Attachment {
+    /// The view's color target
+    Color,
+    /// The view's depth buffer
+    Depth,
+    /// Owned by the graph at the size of the view, see `RenderGraph::add_transient`
+    Transient(&'static str),
+}
+
+/// Declares what a pass touches and what it has to run after
+pub struct PassDesc {
+    pub name: &'static str,
+    reads: Vec<Attachment>,
+    writes: Vec<Attachment>,
+    after: Vec<&'static str>,
+}
+
+impl PassDesc {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            reads: vec![],
+            writes: vec![],
+            after: vec![],
+        }
+    }
+
+    /// Runs after every pass writing `attachment` that does not read it too
+    pub fn reads(mut self, attachment: Attachment) -> Self {
+        self.reads.push(attachment);
+        self
+    }
+
+    pub fn writes(mut self, attachment: Attachment) -> Self {
+        self.writes.push(attachment);
+        self
+    }
+
+    pub fn after(mut self, pass: &'static str) -> Self {
+        self.after.push(pass);
+        self
+    }
+
+    fn attachments(&self) -> impl Iterator<Item = Attachment> + '_ {
+        self.reads.iter().chain(&self.writes).copied()
+    }
+}
+
+/// The view a pass renders for, handed to it while the graph executes
+pub struct PassContext<'a> {
+    pub ctx: &'a GPUCtx,
+    pub camera: &'a Camera,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    color_view: &'a wgpu::TextureView,
+    depth_view: &'a wgpu::TextureView,
+    transients: &'a HashMap<&'static str, &'a wgpu::TextureView>,
+    /// Attachments no earlier pass in this view used, they get cleared
+    first_use: Vec<Attachment>,
+}
+
+impl<'a> PassContext<'a> {
+    fn clears(&self, attachment: Attachment) -> bool {
+        self.first_use.contains(&attachment)
+    }
+
+    /// A render pass on the view's attachments. Only the first
+    /// pass using an attachment in this view clears it.
+    pub fn begin_render_pass(&mut self) -> wgpu::RenderPass<'static> {
+        let color_load = if self.clears(Attachment::Color) {
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+        } else {
+            wgpu::LoadOp::Load
+        };
+
+        let depth_load = if self.clears(Attachment::Depth) {
+            wgpu::LoadOp::Clear(1.0)
+        } else {
+            wgpu::LoadOp::Load
+        };
+
+        SView::new(self.color_view, self.depth_view)
+            .render_pass_with_load(self.encoder, color_load, depth_load)
+            .forget_lifetime()
+    }
+
+    /// A render pass on a single color `attachment` without depth, to
+    /// draw into a transient. Transients are cleared to transparent black.
+    #[allow(unused)]
+    pub fn begin_color_pass(&mut self, attachment: Attachment) -> wgpu::RenderPass<'static> {
+        let load = match attachment {
+            _ if !self.clears(attachment) => wgpu::LoadOp::Load,
+            Attachment::Color => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            _ => wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+        };
+
+        let view = self.attachment_view(attachment);
+
+        self.encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            })
+            .forget_lifetime()
+    }
+
+    /// The texture behind `attachment`, to bind a transient another pass wrote
+    #[allow(unused)]
+    pub fn attachment_view(&self, attachment: Attachment) -> &'a wgpu::TextureView {
+        match attachment {
+            Attachment::Color => self.color_view,
+            Attachment::Depth => self.depth_view,
+            Attachment::Transient(name) => self.transients[name],
+        }
+    }
+}
+
+type PassFn = Box<dyn FnMut(&mut PassContext)>;
+
+struct Pass {
+    desc: PassDesc,
+    run: PassFn,
+}
+
+struct TransientTexture {
+    // Kept alive for the view
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    idle_runs: u32,
+}
+
+impl TransientTexture {
+    fn create(
+        ctx: &GPUCtx,
+        name: &'static str,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> Self {
+        let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(name),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            _texture: texture,
+            view,
+            idle_runs: 0,
+        }
+    }
+}
+
+/// Named passes ordered by their dependencies, executed once per view
+/// with a single command encoder. Demos and overlays add a pass
+/// instead of being called by hand from the event loop.
+#[derive(Default)]
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+    /// Indices into `passes`, recomputed when a pass is added
+    order: Option<Vec<usize>>,
+    /// Formats of the declared transient attachments
+    transients: HashMap<&'static str, wgpu::TextureFormat>,
+    /// One texture per transient and view size
+    transient_textures: HashMap<(&'static str, (u32, u32)), TransientTexture>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_pass<F: FnMut(&mut PassContext) + 'static>(&mut self, desc: PassDesc, run: F) {
+        assert!(
+            self.passes.iter().all(|pass| pass.desc.name != desc.name),
+            "[RenderGraph] Pass {} added twice",
+            desc.name
+        );
+
+        self.passes.push(Pass {
+            desc,
+            run: Box::new(run),
+        });
+        self.order = None;
+    }
+
+    /// Declares `Attachment::Transient(name)`, a texture the graph creates
+    /// for every view at its size. It holds what one pass writes for later
+    /// passes of the same view, nothing survives between views.
+    #[allow(unused)]
+    pub fn add_transient(&mut self, name: &'static str, format: wgpu::TextureFormat) {
+        assert!(
+            self.transients.insert(name, format).is_none(),
+            "[RenderGraph] Transient {name} added twice"
+        );
+
+        self.order = None;
+    }
+
+    fn depends_on(&self, pass: usize, other: usize) -> bool {
+        let desc = &self.passes[pass].desc;
+        let other_desc = &self.passes[other].desc;
+
+        if pass == other {
+            return false;
+        }
+
+        desc.after.contains(&other_desc.name)
+            || desc.reads.iter().any(|attachment| {
+                other_desc.writes.contains(attachment) && !other_desc.reads.contains(attachment)
+            })
+    }
+
+    /// Every `after` and transient names something that exists, and
+    /// every transient read is written by some pass
+    fn validate(&self) {
+        let names: HashSet<&str> = self.passes.iter().map(|pass| pass.desc.name).collect();
+
+        for pass in &self.passes {
+            let desc = &pass.desc;
+
+            for dependency in &desc.after {
+                assert!(
+                    names.contains(dependency),
+                    "[RenderGraph] {} runs after unknown pass {dependency}",
+                    desc.name
+                );
+            }
+
+            for attachment in desc.attachments() {
+                let Attachment::Transient(name) = attachment else {
+                    continue;
+                };
+
+                assert!(
+                    self.transients.contains_key(name),
+                    "[RenderGraph] {} uses undeclared transient {name}",
+                    desc.name
+                );
+            }
+
+            for &attachment in &desc.reads {
+                if !matches!(attachment, Attachment::Transient(_)) {
+                    continue;
+                }
+
+                assert!(
+                    self.passes.iter().any(|other| other.desc.writes.contains(&attachment)),
+                    "[RenderGraph] {} reads {attachment:?} that no pass writes",
+                    desc.name
+                );
+            }
+        }
+    }
+
+    /// Topological order, passes without a dependency between
+    /// them keep the order they were added in
+    fn sort(&self) -> Vec<usize> {
+        self.validate();
+
+        let count = self.passes.len();
+        let mut done = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+
+        while order.len() < count {
+            let next = (0..count).find(|&pass| {
+                !done[pass] && (0..count).all(|other| done[other] || !self.depends_on(pass, other))
+            });
+
+            let Some(next) = next else {
+                let stuck: Vec<&str> = (0..count)
+                    .filter(|&pass| !done[pass])
+                    .map(|pass| self.passes[pass].desc.name)
+                    .collect();
+
+                panic!("[RenderGraph] Dependency cycle between {stuck:?}");
+            };
+
+            done[next] = true;
+            order.push(next);
+        }
+
+        order
+    }
+
+    /// Creates the transients for a view of `size`, and drops
+    /// the ones of sizes no view rendered at for a while
+    fn prepare_transients(&mut self, ctx: &GPUCtx, size: (u32, u32)) {
+        for texture in self.transient_textures.values_mut() {
+            texture.idle_runs += 1;
+        }
+
+        for (&name, &format) in &self.transients {
+            self.transient_textures
+                .entry((name, size))
+                .or_insert_with(|| TransientTexture::create(ctx, name, format, size))
+                .idle_runs = 0;
+        }
+
+        self.transient_textures
+            .retain(|_, texture| texture.idle_runs <= MAX_IDLE_RUNS);
+    }
+
+    /// Runs every pass for one view of `size` and submits the result
+    pub fn execute(
+        &mut self,
+        ctx: &GPUCtx,
+        camera: &Camera,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        puffin::profile_function!();
+
+        if self.order.is_none() {
+            self.order = Some(self.sort());
+        }
+
+        let order = self.order.clone().unwrap_or_default();
+
+        self.prepare_transients(ctx, size);
+
+        let transients: HashMap<&'static str, &wgpu::TextureView> = self
+            .transient_textures
+            .iter()
+            .filter(|((_, texture_size), _)| *texture_size == size)
+            .map(|((name, _), texture)| (*name, &texture.view))
+            .collect();
+
+        let mut encoder = ctx.device.create_command_encoder(&Default::default());
+
+        let mut used = HashSet::new();
+
+        for index in order {
+            let pass = &mut self.passes[index];
+
+            puffin::profile_scope!("Render pass", pass.desc.name);
+
+            let first_use = pass
+                .desc
+                .attachments()
+                .filter(|attachment| !used.contains(attachment))
+                .collect();
+
+            let mut pass_ctx = PassContext {
+                ctx,
+                camera,
+                encoder: &mut encoder,
+                color_view,
+                depth_view,
+                transients: &transients,
+                first_use,
+            };
+
+            (pass.run)(&mut pass_ctx);
+
+            used.extend(pass.desc.attachments());
+        }
+
+        ctx.queue.submit([encoder.finish()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(passes: Vec<PassDesc>) -> RenderGraph {
+        let mut graph = RenderGraph::new();
+        graph.add_transient("bloom", wgpu::TextureFormat::Rgba16Float);
+
+        for desc in passes {
+            graph.add_pass(desc, |_| {});
+        }
+
+        graph
+    }
+
+    fn sorted_names(graph: &RenderGraph) -> Vec<&'static str> {
+        graph
+            .sort()
+            .into_iter()
+            .map(|index| graph.passes[index].desc.name)
+            .collect()
+    }
+
+    const BLOOM: Attachment = Attachment::Transient("bloom");
+
+    #[test]
+    fn readers_run_after_the_writers() {
+        let graph = graph(vec![
+            PassDesc::new("composite")
+                .reads(BLOOM)
+                .reads(Attachment::Color)
+                .writes(Attachment::Color),
+            PassDesc::new("bloom").reads(Attachment::Color).writes(BLOOM),
+            PassDesc::new("transparent")
+                .reads(Attachment::Depth)
+                .writes(Attachment::Color),
+            PassDesc::new("opaque")
+                .writes(Attachment::Color)
+                .writes(Attachment::Depth),
+        ]);
+
+        assert_eq!(
+            sorted_names(&graph),
+            ["opaque", "transparent", "bloom", "composite"]
+        );
+    }
+
+    #[test]
+    fn independent_passes_keep_their_order() {
+        let graph = graph(vec![
+            PassDesc::new("b").writes(Attachment::Color),
+            PassDesc::new("a").writes(Attachment::Color),
+            PassDesc::new("c").writes(BLOOM).after("a"),
+        ]);
+
+        assert_eq!(sorted_names(&graph), ["b", "a", "c"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "undeclared transient")]
+    fn undeclared_transients_are_rejected() {
+        let graph = graph(vec![PassDesc::new("a").writes(Attachment::Transient("nope"))]);
+
+        graph.sort();
+    }
+
+    #[test]
+    #[should_panic(expected = "that no pass writes")]
+    fn transients_nobody_writes_are_rejected() {
+        let graph = graph(vec![PassDesc::new("a").reads(BLOOM).writes(Attachment::Color)]);
+
+        graph.sort();
+    }
+
+    #[test]
+    #[should_panic(expected = "Dependency cycle")]
+    fn cycles_are_rejected() {
+        let graph = graph(vec![
+            PassDesc::new("a").reads(BLOOM).writes(Attachment::Color),
+            PassDesc::new("b").reads(Attachment::Color).writes(BLOOM),
+        ]);
+
+        graph.sort();
+    }
+}
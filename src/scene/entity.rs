@@ -0,0 +1,56 @@
+use super::Renderable;
+use glam::{Mat4, Quat, Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EntityId(pub(super) usize);
+
+/// Local transform of an entity, relative to its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    /// Shear is lost, the scene only ever builds TRS matrices
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+pub struct Entity {
+    pub name: String,
+    pub transform: Transform,
+    pub renderable: Option<Renderable>,
+    pub(super) parent: Option<EntityId>,
+    pub(super) children: Vec<EntityId>,
+}
+
+impl Entity {
+    pub fn children(&self) -> &[EntityId] {
+        &self.children
+    }
+}
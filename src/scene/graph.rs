@@ -0,0 +1,137 @@
+use super::{Entity, EntityId, Render, Renderable, Transform};
+use crate::gpu::GPUCtx;
+use crate::render_graph::PassContext;
+use glam::Mat4;
+
+/// Entities with parent/child transforms. WorldViews render every
+/// entity with a renderable and the gizmo edits the selected one.
+#[derive(Default)]
+pub struct Scene {
+    entities: Vec<Entity>,
+    roots: Vec<EntityId>,
+    pub selected: Option<EntityId>,
+    /// Some transform changed since the renderables last got theirs
+    dirty: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(
+        &mut self,
+        name: impl Into<String>,
+        parent: Option<EntityId>,
+        transform: Transform,
+        renderable: Option<Renderable>,
+    ) -> EntityId {
+        let id = EntityId(self.entities.len());
+
+        self.entities.push(Entity {
+            name: name.into(),
+            transform,
+            renderable,
+            parent,
+            children: vec![],
+        });
+
+        match parent {
+            Some(parent) => self.entities[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.dirty = true;
+
+        id
+    }
+
+    pub fn roots(&self) -> &[EntityId] {
+        &self.roots
+    }
+
+    pub fn get(&self, id: EntityId) -> &Entity {
+        &self.entities[id.0]
+    }
+
+    /// Marks the scene dirty, the caller is assumed to edit the transform
+    pub fn get_mut(&mut self, id: EntityId) -> &mut Entity {
+        self.dirty = true;
+        &mut self.entities[id.0]
+    }
+
+    pub fn world_matrix(&self, id: EntityId) -> Mat4 {
+        let entity = self.get(id);
+        let local = entity.transform.matrix();
+
+        match entity.parent {
+            Some(parent) => self.world_matrix(parent) * local,
+            None => local,
+        }
+    }
+
+    /// Sets the local transform that puts the entity at `world`
+    pub fn set_world_matrix(&mut self, id: EntityId, world: Mat4) {
+        let parent_world = match self.get(id).parent {
+            Some(parent) => self.world_matrix(parent),
+            None => Mat4::IDENTITY,
+        };
+
+        self.get_mut(id).transform = Transform::from_matrix(parent_world.inverse() * world);
+    }
+
+    /// Hands the world transforms to the renderables, if anything moved
+    pub fn update_transforms(&mut self, ctx: &GPUCtx) {
+        if !self.dirty {
+            return;
+        }
+
+        puffin::profile_function!();
+
+        let mut stack: Vec<(EntityId, Mat4)> =
+            self.roots.iter().map(|&id| (id, Mat4::IDENTITY)).collect();
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let entity = self.get(id);
+            let world = parent_world * entity.transform.matrix();
+
+            if let Some(ref renderable) = entity.renderable {
+                renderable.set_transform(ctx, world);
+            }
+
+            stack.extend(entity.children.iter().map(|&child| (child, world)));
+        }
+
+        self.dirty = false;
+    }
+
+    /// Draws the opaque renderables, they fill the depth buffer
+    pub fn render_opaque(&self, pass: &mut PassContext) {
+        puffin::profile_function!();
+
+        self.render_where(pass, false);
+    }
+
+    /// Draws the transparent renderables over the opaque ones, tested
+    /// against their depth. Not sorted by depth, overlapping transparent
+    /// quads may blend in the wrong order.
+    pub fn render_transparent(&self, pass: &mut PassContext) {
+        puffin::profile_function!();
+
+        self.render_where(pass, true);
+    }
+
+    fn render_where(&self, pass: &mut PassContext, transparent: bool) {
+        let mut render_pass = pass.begin_render_pass();
+
+        let renderables = self
+            .entities
+            .iter()
+            .filter_map(|entity| entity.renderable.as_ref())
+            .filter(|renderable| renderable.is_transparent() == transparent);
+
+        for renderable in renderables {
+            renderable.render(pass.ctx, pass.camera, &mut render_pass);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod entity;
+pub use entity::*;
+mod graph;
+pub use graph::*;
+mod renderable;
+pub use renderable::*;
@@ -0,0 +1,81 @@
+use crate::camera::Camera;
+use crate::demos::{CanvasQuadDemo, ChunksDemo, VideoDemo};
+use crate::gpu::GPUCtx;
+use crate::shared::Shared;
+use egui_wgpu::wgpu;
+use enum_dispatch::enum_dispatch;
+use glam::Mat4;
+
+#[enum_dispatch(Renderable)]
+pub trait Render {
+    /// Called with the entity's world transform whenever it changes
+    fn set_transform(&self, ctx: &GPUCtx, transform: Mat4);
+
+    fn render(&self, ctx: &GPUCtx, camera: &Camera, pass: &mut wgpu::RenderPass<'static>);
+
+    /// Blended renderables draw after the opaque ones and skip the depth write
+    fn is_transparent(&self) -> bool {
+        false
+    }
+}
+
+/// What an entity draws, entities without one only group their children
+#[enum_dispatch]
+pub enum Renderable {
+    Mesh(Shared<ChunksDemo>),
+    VideoQuad(Shared<VideoDemo>),
+    CanvasQuad(Shared<CanvasQuadDemo>),
+}
+
+impl Renderable {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Renderable::Mesh(_) => "Mesh",
+            Renderable::VideoQuad(_) => "Video quad",
+            Renderable::CanvasQuad(_) => "Canvas quad",
+        }
+    }
+}
+
+impl Render for Shared<ChunksDemo> {
+    fn set_transform(&self, ctx: &GPUCtx, transform: Mat4) {
+        self.with(|demo| demo.set_transform(ctx, transform));
+    }
+
+    fn render(&self, ctx: &GPUCtx, camera: &Camera, pass: &mut wgpu::RenderPass<'static>) {
+        self.with(|demo| {
+            demo.setup_dynamic_camera(ctx, camera);
+            demo.render_static(pass);
+        });
+    }
+}
+
+impl Render for Shared<VideoDemo> {
+    fn set_transform(&self, ctx: &GPUCtx, transform: Mat4) {
+        self.with_ref(|demo| demo.update_location(ctx, transform));
+    }
+
+    fn render(&self, ctx: &GPUCtx, camera: &Camera, pass: &mut wgpu::RenderPass<'static>) {
+        self.with(|demo| {
+            demo.setup_dynamic_camera(ctx, camera);
+            demo.render_static(pass);
+        });
+    }
+}
+
+impl Render for Shared<CanvasQuadDemo> {
+    fn set_transform(&self, ctx: &GPUCtx, transform: Mat4) {
+        self.with_ref(|demo| demo.update_location(ctx, transform));
+    }
+
+    fn render(&self, ctx: &GPUCtx, camera: &Camera, pass: &mut wgpu::RenderPass<'static>) {
+        self.with_ref(|demo| {
+            demo.setup_dynamic_camera(ctx, camera);
+            demo.render_static(pass);
+        });
+    }
+
+    fn is_transparent(&self) -> bool {
+        true
+    }
+}
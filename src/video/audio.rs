@@ -0,0 +1,722 @@
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ffmpeg_next::{format, frame, software::resampling, ChannelLayout, Rational};
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// Every audio stream is resampled to interleaved stereo f32 at 48kHz
+pub const AUDIO_SAMPLE_RATE: u32 = 48_000;
+pub const AUDIO_CHANNELS: usize = 2;
+
+const AUDIO_SAMPLE_FORMAT: format::Sample = format::Sample::F32(format::sample::Type::Packed);
+
+/// The decoder can run far ahead of the playhead with a large frame
+/// budget, so the ring buffer holds a generous amount of audio
+const AUDIO_BUFFER_DURATION: Duration = Duration::from_secs(32);
+
+/// How far pushed samples may start from the end of the buffered ones
+/// before the difference is treated as a gap, or as a jump back
+const AUDIO_PTS_TOLERANCE: Duration = Duration::from_millis(20);
+
+fn frames_to_duration(frames: usize) -> Duration {
+    Duration::from_secs_f64(frames as f64 / AUDIO_SAMPLE_RATE as f64)
+}
+
+fn duration_to_frames(duration: Duration) -> usize {
+    (duration.as_secs_f64() * AUDIO_SAMPLE_RATE as f64) as usize
+}
+
+/// Receives the PCM in the fixed output format as it gets played
+pub trait AudioSink: Send {
+    /// A failed sink is replaced by a `NullSink`, the clock keeps going
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+
+    fn flush(&mut self) {}
+}
+
+/// Discards everything, the clock still advances
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn write(&mut self, _: &[f32]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where opened videos play their audio to
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioOutput {
+    Mute,
+    /// One WAV file per video in this directory, named after the video
+    WavFiles(PathBuf),
+}
+
+impl AudioOutput {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioOutput::Mute => "Mute",
+            AudioOutput::WavFiles(_) => "WAV files",
+        }
+    }
+
+    /// The sink for the audio of `video`
+    pub fn create_sink(&self, video: &Path) -> io::Result<Box<dyn AudioSink>> {
+        match self {
+            AudioOutput::Mute => Ok(Box::new(NullSink)),
+            AudioOutput::WavFiles(directory) => {
+                fs::create_dir_all(directory)?;
+
+                let name = video.file_stem().unwrap_or_default().to_string_lossy();
+                let sink = WavFileSink::create(directory.join(format!("{name}.wav")))?;
+
+                Ok(Box::new(sink))
+            }
+        }
+    }
+}
+
+/// Writes the played audio to a 32 bit float WAV file
+pub struct WavFileSink {
+    writer: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavFileSink {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        Self::write_header(&mut writer, 0)?;
+
+        Ok(Self {
+            writer,
+            data_bytes: 0,
+        })
+    }
+
+    fn write_header<W: Write>(writer: &mut W, data_bytes: u32) -> io::Result<()> {
+        let bytes_per_sample = size_of::<f32>() as u16;
+        let block_align = AUDIO_CHANNELS as u16 * bytes_per_sample;
+        let byte_rate = AUDIO_SAMPLE_RATE * block_align as u32;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(36 + data_bytes).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // WAVE_FORMAT_IEEE_FLOAT
+        writer.write_all(&3u16.to_le_bytes())?;
+        writer.write_all(&(AUDIO_CHANNELS as u16).to_le_bytes())?;
+        writer.write_all(&AUDIO_SAMPLE_RATE.to_le_bytes())?;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&data_bytes.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        Self::write_header(&mut self.writer, self.data_bytes)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+impl AudioSink for WavFileSink {
+    /// Fails instead of writing past the 4 GiB the header can describe
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let data_bytes = u32::try_from(samples.len() * size_of::<f32>())
+            .ok()
+            .and_then(|bytes| self.data_bytes.checked_add(bytes))
+            // The RIFF chunk size adds the rest of the header
+            .filter(|bytes| bytes.checked_add(36).is_some())
+            .ok_or_else(|| io::Error::other("the WAV file is full"))?;
+
+        for sample in samples {
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+
+        self.data_bytes = data_bytes;
+
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = self.finalize() {
+            eprintln!("Failed to finalize WAV file: {e:?}");
+        }
+    }
+}
+
+impl Drop for WavFileSink {
+    fn drop(&mut self) {
+        let _ = self.finalize();
+    }
+}
+
+struct RingState {
+    samples: VecDeque<f32>,
+    // PTS of the next sample to be played
+    front_pts: Option<Duration>,
+    // Pushed samples before this point are dropped, see `skip_to`
+    skip_until: Option<Duration>,
+}
+
+/// Decoded PCM waiting to be played, also the source of the playback clock
+pub struct AudioRingBuffer {
+    state: Mutex<RingState>,
+    capacity: usize,
+}
+
+impl AudioRingBuffer {
+    pub fn new() -> Self {
+        let capacity = duration_to_frames(AUDIO_BUFFER_DURATION) * AUDIO_CHANNELS;
+
+        Self {
+            state: Mutex::new(RingState {
+                samples: VecDeque::with_capacity(capacity),
+                front_pts: None,
+                skip_until: None,
+            }),
+            capacity,
+        }
+    }
+
+    /// Appends samples that start at `pts`. When full the oldest
+    /// samples are dropped instead of blocking the decoder,
+    /// the same way `LooseSender` treats commands.
+    pub fn push(&self, pts: Duration, samples: &[f32]) {
+        let mut state = self.state.lock().unwrap();
+
+        let mut pts = pts;
+        let mut samples = samples;
+
+        if let Some(until) = state.skip_until {
+            let frames = samples.len() / AUDIO_CHANNELS;
+
+            if pts + frames_to_duration(frames) <= until {
+                return;
+            }
+
+            let skip = duration_to_frames(until.saturating_sub(pts)).min(frames);
+            samples = &samples[skip * AUDIO_CHANNELS..];
+            pts = pts.max(until);
+
+            state.skip_until = None;
+        }
+
+        let buffered_end = state
+            .front_pts
+            .filter(|_| !state.samples.is_empty())
+            .map(|front| front + frames_to_duration(state.samples.len() / AUDIO_CHANNELS));
+
+        match buffered_end {
+            // A gap in the stream, filled with silence so the
+            // samples after it are played at their PTS
+            Some(end)
+                if pts > end + AUDIO_PTS_TOLERANCE && pts - end < AUDIO_BUFFER_DURATION =>
+            {
+                let gap = duration_to_frames(pts - end) * AUDIO_CHANNELS;
+                state.samples.extend(std::iter::repeat_n(0.0, gap));
+            }
+            Some(end) if pts.abs_diff(end) <= AUDIO_PTS_TOLERANCE => {}
+            // Nothing buffered, or a jump the buffered samples
+            // can't be stretched to, start over at `pts`
+            _ => {
+                state.samples.clear();
+                state.front_pts = Some(pts);
+            }
+        }
+
+        state.samples.extend(samples);
+
+        let overflow = state.samples.len().saturating_sub(self.capacity);
+
+        if overflow > 0 {
+            let overflow = overflow.next_multiple_of(AUDIO_CHANNELS);
+            state.samples.drain(..overflow);
+            state.front_pts = state
+                .front_pts
+                .map(|pts| pts + frames_to_duration(overflow / AUDIO_CHANNELS));
+        }
+    }
+
+    /// Moves up to `frames` frames into `out`, returns how many were moved
+    pub fn pop(&self, frames: usize, out: &mut Vec<f32>) -> usize {
+        let mut state = self.state.lock().unwrap();
+
+        let count = (frames * AUDIO_CHANNELS).min(state.samples.len());
+        out.extend(state.samples.drain(..count));
+
+        let popped = count / AUDIO_CHANNELS;
+
+        state.front_pts = state.front_pts.map(|pts| pts + frames_to_duration(popped));
+
+        popped
+    }
+
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.samples.clear();
+        state.front_pts = None;
+        state.skip_until = None;
+    }
+
+    /// Drops the samples before `pts`, for a playhead that moved forward
+    /// without a seek. When less is buffered the next pushes are trimmed
+    /// up to it, the way `AudioDecoder` trims up to a seek target.
+    pub fn skip_to(&self, pts: Duration) {
+        let mut state = self.state.lock().unwrap();
+
+        let front = match state.front_pts {
+            Some(front) if !state.samples.is_empty() => front,
+            _ => {
+                state.skip_until = Some(pts);
+                return;
+            }
+        };
+
+        let buffered = state.samples.len() / AUDIO_CHANNELS;
+        let skip = duration_to_frames(pts.saturating_sub(front)).min(buffered);
+
+        state.samples.drain(..skip * AUDIO_CHANNELS);
+        state.front_pts = Some(front + frames_to_duration(skip));
+
+        if skip == buffered {
+            state.skip_until = Some(pts);
+        }
+    }
+
+    pub fn buffered(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+
+        frames_to_duration(state.samples.len() / AUDIO_CHANNELS)
+    }
+
+    /// Position of the next sample to be played, `None` when the audio
+    /// can't drive the clock. That is whenever nothing is buffered: just
+    /// reset, played to the end, or the decoder fell behind. The front PTS
+    /// stops advancing without samples, the playhead would freeze on it.
+    pub fn clock(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+
+        if state.samples.is_empty() {
+            return None;
+        }
+
+        state.front_pts
+    }
+}
+
+/// Playback state shared by the `VideoHandle` and the audio output thread
+pub struct AudioControl {
+    playing: AtomicBool,
+    speed: AtomicU32,
+    close: AtomicBool,
+}
+
+impl AudioControl {
+    pub fn new() -> Self {
+        Self {
+            playing: AtomicBool::new(false),
+            speed: AtomicU32::new(1.0f32.to_bits()),
+            close: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_playing(&self, playing: bool) {
+        self.playing.store(playing, Ordering::Relaxed);
+    }
+
+    pub fn set_speed(&self, speed: f32) {
+        self.speed.store(speed.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn close(&self) {
+        self.close.store(true, Ordering::Relaxed);
+    }
+
+    fn speed(&self) -> f32 {
+        f32::from_bits(self.speed.load(Ordering::Relaxed))
+    }
+}
+
+/// One source played by the `AudioOutputThread`
+struct AudioStream {
+    ring: Arc<AudioRingBuffer>,
+    control: Arc<AudioControl>,
+    sink: Box<dyn AudioSink>,
+    pending_frames: f64,
+}
+
+impl AudioStream {
+    /// Consumes the ring buffer at the playback rate and feeds the sink.
+    /// Speeds other than x1.0 consume faster or slower, without pitch
+    /// correction. Returns false once the handle closed the stream.
+    fn advance(&mut self, delta: Duration, buffer: &mut Vec<f32>) -> bool {
+        if self.control.close.load(Ordering::Relaxed) {
+            self.sink.flush();
+            return false;
+        }
+
+        if !self.control.playing.load(Ordering::Relaxed) {
+            self.pending_frames = 0.0;
+            return true;
+        }
+
+        self.pending_frames +=
+            delta.as_secs_f64() * AUDIO_SAMPLE_RATE as f64 * self.control.speed() as f64;
+
+        let frames = self.pending_frames.floor() as usize;
+        self.pending_frames -= frames as f64;
+
+        buffer.clear();
+        self.ring.pop(frames, buffer);
+
+        if !buffer.is_empty() {
+            if let Err(e) = self.sink.write(buffer) {
+                eprintln!("Failed to write audio, muting the source: {e}");
+                self.sink.flush();
+                self.sink = Box::new(NullSink);
+            }
+        }
+
+        true
+    }
+}
+
+/// Plays the audio of every open video from a single thread
+pub struct AudioOutputThread {
+    streams: Option<Sender<AudioStream>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AudioOutputThread {
+    pub fn spawn() -> Self {
+        let (streams, receiver) = crossbeam_channel::unbounded();
+
+        let thread = std::thread::Builder::new()
+            .name("audio-output".into())
+            .spawn(move || run_audio_output_thread(receiver))
+            .expect("Failed to spawn the audio output thread");
+
+        Self {
+            streams: Some(streams),
+            thread: Some(thread),
+        }
+    }
+
+    pub fn add(
+        &self,
+        ring: Arc<AudioRingBuffer>,
+        control: Arc<AudioControl>,
+        sink: Box<dyn AudioSink>,
+    ) {
+        if let Some(ref streams) = self.streams {
+            let _ = streams.send(AudioStream {
+                ring,
+                control,
+                sink,
+                pending_frames: 0.0,
+            });
+        }
+    }
+}
+
+impl Drop for AudioOutputThread {
+    fn drop(&mut self) {
+        // Disconnecting ends the thread after flushing every sink
+        self.streams = None;
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_audio_output_thread(new_streams: Receiver<AudioStream>) {
+    let mut streams: Vec<AudioStream> = vec![];
+    let mut last_update = Instant::now();
+    let mut buffer = Vec::new();
+
+    loop {
+        std::thread::sleep(Duration::from_millis(5));
+
+        loop {
+            match new_streams.try_recv() {
+                Ok(stream) => streams.push(stream),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    for stream in &mut streams {
+                        stream.sink.flush();
+                    }
+                    return;
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let delta = now - last_update;
+        last_update = now;
+
+        streams.retain_mut(|stream| stream.advance(delta, &mut buffer));
+    }
+}
+
+/// Decodes the best audio stream of a file and resamples it into the ring buffer
+pub struct AudioDecoder {
+    decoder: ffmpeg_next::decoder::Audio,
+    resampler: Option<resampling::Context>,
+    pub stream_index: usize,
+    time_base: Rational,
+    /// Start of the video stream, the audio clock counts from it
+    origin: Duration,
+    ring: Arc<AudioRingBuffer>,
+    // Samples before this point are dropped, set after a seek
+    seek_target: Option<Duration>,
+}
+
+impl AudioDecoder {
+    pub fn open(
+        ictx: &format::context::Input,
+        ring: Arc<AudioRingBuffer>,
+        origin: Duration,
+    ) -> Option<Self> {
+        let input = ictx.streams().best(ffmpeg_next::media::Type::Audio)?;
+
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(input.parameters())
+            .ok()?
+            .decoder()
+            .audio()
+            .ok()?;
+
+        Some(Self {
+            decoder,
+            resampler: None,
+            stream_index: input.index(),
+            time_base: input.time_base(),
+            origin,
+            ring,
+            seek_target: None,
+        })
+    }
+
+    pub fn send_packet(&mut self, packet: &ffmpeg_next::Packet) {
+        if self.decoder.send_packet(packet).is_ok() {
+            self.drain();
+        }
+    }
+
+    pub fn send_eof(&mut self) {
+        if self.decoder.send_eof().is_ok() {
+            self.drain();
+        }
+    }
+
+    pub fn seek(&mut self, target: Duration) {
+        self.decoder.flush();
+        // Drops the samples the resampler is still holding
+        self.resampler = None;
+        self.ring.reset();
+        self.seek_target = Some(target);
+    }
+
+    fn drain(&mut self) {
+        let mut decoded = frame::Audio::empty();
+
+        while self.decoder.receive_frame(&mut decoded).is_ok() {
+            let pts = decoded.timestamp().or(decoded.pts()).unwrap_or(0);
+            let pts = Duration::from_secs_f64((pts as f64 * f64::from(self.time_base)).max(0.0))
+                .saturating_sub(self.origin);
+
+            let Some(samples) = self.resample(&decoded) else {
+                continue;
+            };
+
+            let mut pts = pts;
+            let mut samples = samples.as_slice();
+
+            // Trim everything between the keyframe we landed on and the seek target
+            if let Some(target) = self.seek_target {
+                let frames = samples.len() / AUDIO_CHANNELS;
+
+                if pts + frames_to_duration(frames) <= target {
+                    continue;
+                }
+
+                let skip = duration_to_frames(target.saturating_sub(pts)).min(frames);
+                samples = &samples[skip * AUDIO_CHANNELS..];
+                pts = pts.max(target);
+
+                self.seek_target = None;
+            }
+
+            self.ring.push(pts, samples);
+        }
+    }
+
+    fn resample(&mut self, decoded: &frame::Audio) -> Option<Vec<f32>> {
+        let layout = if decoded.channel_layout().is_empty() {
+            ChannelLayout::default(decoded.channels() as i32)
+        } else {
+            decoded.channel_layout()
+        };
+
+        if self.resampler.is_none() {
+            self.resampler = resampling::Context::get(
+                decoded.format(),
+                layout,
+                decoded.rate(),
+                AUDIO_SAMPLE_FORMAT,
+                ChannelLayout::STEREO,
+                AUDIO_SAMPLE_RATE,
+            )
+            .ok();
+        }
+
+        let resampler = self.resampler.as_mut()?;
+
+        // Leave room for the upsampled frames, otherwise swresample
+        // keeps the overflow around and the audio drifts behind
+        let capacity =
+            decoded.samples() * AUDIO_SAMPLE_RATE as usize / decoded.rate().max(1) as usize + 64;
+
+        let mut resampled = frame::Audio::new(AUDIO_SAMPLE_FORMAT, capacity, ChannelLayout::STEREO);
+
+        resampler.run(decoded, &mut resampled).ok()?;
+
+        let len = resampled.samples() * AUDIO_CHANNELS * size_of::<f32>();
+
+        let samples = resampled.data(0)[..len]
+            .chunks_exact(size_of::<f32>())
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+
+        Some(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_follows_the_played_samples() {
+        let ring = AudioRingBuffer::new();
+        let mut out = Vec::new();
+
+        assert_eq!(ring.clock(), None);
+
+        let start = Duration::from_secs(2);
+        ring.push(
+            start,
+            &vec![0.0; AUDIO_SAMPLE_RATE as usize * AUDIO_CHANNELS],
+        );
+
+        assert_eq!(ring.clock(), Some(start));
+
+        ring.pop(AUDIO_SAMPLE_RATE as usize / 2, &mut out);
+
+        assert_eq!(ring.clock(), Some(start + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn clock_gives_up_on_underrun() {
+        let ring = AudioRingBuffer::new();
+        let mut out = Vec::new();
+
+        ring.push(Duration::ZERO, &[0.0; 64 * AUDIO_CHANNELS]);
+        ring.pop(1024, &mut out);
+
+        // Not at the end, the decoder is just late
+        assert_eq!(ring.clock(), None);
+
+        let resumed = Duration::from_secs(1);
+        ring.push(resumed, &[0.0; 64 * AUDIO_CHANNELS]);
+
+        assert_eq!(ring.clock(), Some(resumed));
+    }
+
+    fn second_of(value: f32) -> Vec<f32> {
+        vec![value; AUDIO_SAMPLE_RATE as usize * AUDIO_CHANNELS]
+    }
+
+    #[test]
+    fn gaps_in_the_stream_are_filled_with_silence() {
+        let ring = AudioRingBuffer::new();
+        let mut out = Vec::new();
+
+        ring.push(Duration::ZERO, &second_of(1.0));
+        ring.push(Duration::from_secs(2), &second_of(1.0));
+
+        assert_eq!(ring.buffered(), Duration::from_secs(3));
+
+        ring.pop(AUDIO_SAMPLE_RATE as usize, &mut out);
+        assert!(out.iter().all(|&sample| sample == 1.0));
+
+        out.clear();
+        ring.pop(AUDIO_SAMPLE_RATE as usize, &mut out);
+        assert!(out.iter().all(|&sample| sample == 0.0));
+
+        assert_eq!(ring.clock(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn jumps_back_restart_the_buffer() {
+        let ring = AudioRingBuffer::new();
+
+        ring.push(Duration::from_secs(5), &second_of(1.0));
+        ring.push(Duration::from_secs(1), &second_of(1.0));
+
+        assert_eq!(ring.clock(), Some(Duration::from_secs(1)));
+        assert_eq!(ring.buffered(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn skipping_ahead_trims_buffered_and_pushed_samples() {
+        let ring = AudioRingBuffer::new();
+
+        ring.push(Duration::ZERO, &second_of(1.0));
+        ring.skip_to(Duration::from_millis(500));
+
+        assert_eq!(ring.clock(), Some(Duration::from_millis(500)));
+
+        ring.skip_to(Duration::from_secs(3));
+        assert_eq!(ring.clock(), None);
+
+        // Decoded before the skip was seen, only the rest is kept
+        ring.push(Duration::from_secs(2), &second_of(1.0).repeat(2));
+
+        assert_eq!(ring.clock(), Some(Duration::from_secs(3)));
+        assert_eq!(ring.buffered(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn wav_sink_refuses_to_outgrow_its_header() {
+        let path = std::env::temp_dir().join(format!("pony-wav-{}.wav", std::process::id()));
+        let mut sink = WavFileSink::create(&path).unwrap();
+
+        sink.write(&[0.0; 4]).unwrap();
+
+        sink.data_bytes = u32::MAX - 36;
+        assert!(sink.write(&[0.0; 4]).is_err());
+        assert_eq!(sink.data_bytes, u32::MAX - 36);
+
+        drop(sink);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Where playback is, published by the handle on every tick so the
+/// decoder can skip frames that would be late by the time they arrive
+#[derive(Default)]
+pub struct PlaybackClock {
+    position_micros: AtomicU64,
+    playing: AtomicBool,
+    decoder_drops: AtomicU64,
+}
+
+impl PlaybackClock {
+    pub fn publish(&self, position: Duration, playing: bool) {
+        self.position_micros
+            .store(position.as_micros() as u64, Ordering::Relaxed);
+        self.playing.store(playing, Ordering::Relaxed);
+    }
+
+    /// `None` while paused, frames are only ever late while the clock runs
+    pub fn running_position(&self) -> Option<Duration> {
+        if !self.playing.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        Some(Duration::from_micros(
+            self.position_micros.load(Ordering::Relaxed),
+        ))
+    }
+
+    pub fn record_decoder_drop(&self) {
+        self.decoder_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Frames the decoder skipped without converting or sending them
+    pub fn decoder_drops(&self) -> u64 {
+        self.decoder_drops.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,304 @@
+use ffmpeg_next::{color, format, frame};
+use glam::{Mat3, Mat4, Vec3};
+
+/// Luma coefficients the encoder used to mix Y'CbCr from R'G'B'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvMatrix {
+    Bt601,
+    Bt709,
+    Bt2020,
+}
+
+impl YuvMatrix {
+    /// Kr and Kb, Kg is what is left
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+            YuvMatrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Y in 16..=235 and chroma in 16..=240, what most video uses
+    Limited,
+    /// Every code value used, JPEG style
+    Full,
+}
+
+/// How the decoded R'G'B' maps to linear light
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    Srgb,
+    /// The 2.4 gamma of BT.1886 displays, for BT.601/709/2020 sources
+    Bt1886,
+    Linear,
+}
+
+impl Transfer {
+    /// The value the shader switches on
+    pub fn shader_id(self) -> u32 {
+        match self {
+            Transfer::Srgb => 0,
+            Transfer::Bt1886 => 1,
+            Transfer::Linear => 2,
+        }
+    }
+}
+
+fn is_rgb(pixel: format::Pixel) -> bool {
+    let Some(descriptor) = pixel.descriptor() else {
+        return false;
+    };
+
+    // SAFETY: descriptors point into ffmpeg's static table
+    let flags = unsafe { (*descriptor.as_ptr()).flags };
+
+    flags & ffmpeg_next::ffi::AV_PIX_FMT_FLAG_RGB as u64 != 0
+}
+
+/// Colorimetry of a decoded frame, what the shader needs to get RGB out of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorInfo {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange,
+    pub transfer: Transfer,
+}
+
+impl Default for ColorInfo {
+    fn default() -> Self {
+        Self {
+            matrix: YuvMatrix::Bt709,
+            range: YuvRange::Limited,
+            transfer: Transfer::Bt1886,
+        }
+    }
+}
+
+impl ColorInfo {
+    /// Reads the frame's tags, untagged streams get what players assume:
+    /// BT.709 from 720p up and BT.601 below, limited range unless the
+    /// pixel format is one of the full range (J) variants
+    pub fn from_av(decoded: &frame::Video) -> Self {
+        let matrix = match decoded.color_space() {
+            color::Space::BT709 => YuvMatrix::Bt709,
+            color::Space::BT470BG | color::Space::SMPTE170M | color::Space::FCC => {
+                YuvMatrix::Bt601
+            }
+            color::Space::BT2020NCL | color::Space::BT2020CL => YuvMatrix::Bt2020,
+            // swscale writes BT.601 when it converts RGB sources to YUV
+            color::Space::RGB => YuvMatrix::Bt601,
+            _ if decoded.height() >= 720 => YuvMatrix::Bt709,
+            _ => YuvMatrix::Bt601,
+        };
+
+        let range = match decoded.color_range() {
+            color::Range::JPEG => YuvRange::Full,
+            color::Range::MPEG => YuvRange::Limited,
+            _ => match decoded.format() {
+                format::Pixel::YUVJ420P
+                | format::Pixel::YUVJ422P
+                | format::Pixel::YUVJ444P => YuvRange::Full,
+                _ => YuvRange::Limited,
+            },
+        };
+
+        // PQ and HLG are not tone mapped, they come out as BT.1886
+        let transfer = match decoded.color_transfer_characteristic() {
+            color::TransferCharacteristic::IEC61966_2_1 => Transfer::Srgb,
+            color::TransferCharacteristic::Linear => Transfer::Linear,
+            _ => Transfer::Bt1886,
+        };
+
+        Self {
+            matrix,
+            range,
+            transfer,
+        }
+    }
+
+    /// How the frame reads once swscale turned it into planar YUV. YUV
+    /// sources are only repacked and keep their samples, so their tags
+    /// still hold. RGB sources are mixed with swscale's default BT.601
+    /// table into limited range, and the full range (J) formats are
+    /// squeezed into limited range. The transfer goes through as is.
+    pub fn converted_by_swscale(decoded: &frame::Video) -> Self {
+        let source = Self::from_av(decoded);
+
+        if is_rgb(decoded.format()) {
+            return Self {
+                matrix: YuvMatrix::Bt601,
+                range: YuvRange::Limited,
+                ..source
+            };
+        }
+
+        match decoded.format() {
+            // The other J formats are uploaded without swscale
+            format::Pixel::YUVJ411P | format::Pixel::YUVJ440P => Self {
+                range: YuvRange::Limited,
+                ..source
+            },
+            _ => source,
+        }
+    }
+
+    /// Takes (Y, U, V, 1) as sampled from R8Unorm planes, code value / 255,
+    /// to nonlinear R'G'B'. The range scale and offsets are folded in.
+    pub fn yuv_to_rgb_matrix(&self) -> Mat4 {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+
+        // Columns: what Y, Cb and Cr add to R', G' and B'
+        let mix = Mat3::from_cols(
+            Vec3::ONE,
+            Vec3::new(0.0, -2.0 * kb * (1.0 - kb) / kg, 2.0 * (1.0 - kb)),
+            Vec3::new(2.0 * (1.0 - kr), -2.0 * kr * (1.0 - kr) / kg, 0.0),
+        );
+
+        // Normalized Y in 0..=1 and Cb, Cr in -0.5..=0.5
+        let (luma_scale, chroma_scale, luma_offset) = match self.range {
+            YuvRange::Limited => (255.0 / 219.0, 255.0 / 224.0, -16.0 / 219.0),
+            YuvRange::Full => (1.0, 1.0, 0.0),
+        };
+
+        let chroma_offset = -128.0 / 255.0 * chroma_scale;
+
+        let scale = Mat3::from_diagonal(Vec3::new(luma_scale, chroma_scale, chroma_scale));
+        let offset = Vec3::new(luma_offset, chroma_offset, chroma_offset);
+
+        let linear = mix * scale;
+
+        Mat4::from_cols(
+            linear.x_axis.extend(0.0),
+            linear.y_axis.extend(0.0),
+            linear.z_axis.extend(0.0),
+            (mix * offset).extend(1.0),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(matrix: YuvMatrix, range: YuvRange) -> ColorInfo {
+        ColorInfo {
+            matrix,
+            range,
+            transfer: Transfer::Bt1886,
+        }
+    }
+
+    /// 8 bit code values in, 0..=255 R'G'B' out
+    fn to_rgb(color: ColorInfo, yuv: [u8; 3]) -> Vec3 {
+        let [y, u, v] = yuv.map(|c| c as f32 / 255.0);
+
+        color.yuv_to_rgb_matrix().transform_point3(Vec3::new(y, u, v)) * 255.0
+    }
+
+    #[track_caller]
+    fn assert_converts(color: ColorInfo, cases: &[([u8; 3], [f32; 3])]) {
+        for &(yuv, rgb) in cases {
+            let converted = to_rgb(color, yuv);
+
+            // The reference triples are rounded to whole code values
+            assert!(
+                converted.abs_diff_eq(Vec3::from(rgb), 1.5),
+                "{color:?} {yuv:?}: expected {rgb:?}, got {converted}"
+            );
+        }
+    }
+
+    const WHITE: [f32; 3] = [255.0, 255.0, 255.0];
+    const BLACK: [f32; 3] = [0.0, 0.0, 0.0];
+    const RED: [f32; 3] = [255.0, 0.0, 0.0];
+    const GREEN: [f32; 3] = [0.0, 255.0, 0.0];
+    const BLUE: [f32; 3] = [0.0, 0.0, 255.0];
+
+    #[test]
+    fn bt601_limited() {
+        assert_converts(
+            info(YuvMatrix::Bt601, YuvRange::Limited),
+            &[
+                ([235, 128, 128], WHITE),
+                ([16, 128, 128], BLACK),
+                ([81, 90, 240], RED),
+                ([145, 54, 34], GREEN),
+                ([41, 240, 110], BLUE),
+            ],
+        );
+    }
+
+    #[test]
+    fn bt601_full() {
+        assert_converts(
+            info(YuvMatrix::Bt601, YuvRange::Full),
+            &[
+                ([255, 128, 128], WHITE),
+                ([0, 128, 128], BLACK),
+                ([76, 85, 255], RED),
+                ([150, 44, 21], GREEN),
+                ([29, 255, 107], BLUE),
+            ],
+        );
+    }
+
+    #[test]
+    fn bt709_limited() {
+        assert_converts(
+            info(YuvMatrix::Bt709, YuvRange::Limited),
+            &[
+                ([235, 128, 128], WHITE),
+                ([16, 128, 128], BLACK),
+                ([63, 102, 240], RED),
+                ([173, 42, 26], GREEN),
+                ([32, 240, 118], BLUE),
+            ],
+        );
+    }
+
+    #[test]
+    fn bt709_full() {
+        assert_converts(
+            info(YuvMatrix::Bt709, YuvRange::Full),
+            &[
+                ([255, 128, 128], WHITE),
+                ([0, 128, 128], BLACK),
+                ([54, 99, 255], RED),
+                ([182, 30, 12], GREEN),
+                ([18, 255, 116], BLUE),
+            ],
+        );
+    }
+
+    #[test]
+    fn bt2020_limited() {
+        assert_converts(
+            info(YuvMatrix::Bt2020, YuvRange::Limited),
+            &[
+                ([235, 128, 128], WHITE),
+                ([16, 128, 128], BLACK),
+                ([74, 97, 240], RED),
+                ([164, 47, 25], GREEN),
+                ([29, 240, 119], BLUE),
+            ],
+        );
+    }
+
+    #[test]
+    fn bt2020_full() {
+        assert_converts(
+            info(YuvMatrix::Bt2020, YuvRange::Full),
+            &[
+                ([255, 128, 128], WHITE),
+                ([0, 128, 128], BLACK),
+                ([67, 92, 255], RED),
+                ([173, 36, 11], GREEN),
+                ([15, 255, 118], BLUE),
+            ],
+        );
+    }
+}
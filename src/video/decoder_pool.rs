@@ -0,0 +1,172 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use crate::BENCHMARK_MODE;
+
+use super::{AudioControl, AudioOutputThread, AudioRingBuffer, AudioSink, DecoderJob, JobStatus};
+
+struct PoolShared {
+    jobs: Mutex<Vec<DecoderJob>>,
+    wake: Condvar,
+    close: AtomicBool,
+}
+
+impl PoolShared {
+    /// Takes the ready job with the emptiest buffer, so the source
+    /// closest to starving is always decoded first. Blocks while no
+    /// job is ready, until a job is put back or a handle wakes the pool.
+    fn take_job(&self) -> Option<DecoderJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        loop {
+            if self.close.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let next = jobs
+                .iter()
+                .enumerate()
+                .filter(|(_, job)| job.is_ready())
+                .min_by(|(_, a), (_, b)| a.buffer_health().total_cmp(&b.buffer_health()))
+                .map(|(index, _)| index);
+
+            if let Some(index) = next {
+                return Some(jobs.swap_remove(index));
+            }
+
+            jobs = self.wake.wait(jobs).unwrap();
+        }
+    }
+
+    fn put_back(&self, job: DecoderJob) {
+        self.jobs.lock().unwrap().push(job);
+        self.wake.notify_one();
+    }
+
+    fn wake_all(&self) {
+        // Taking the lock orders this after any readiness check in
+        // progress, that worker is already waiting and gets notified
+        let _jobs = self.jobs.lock().unwrap();
+        self.wake.notify_all();
+    }
+}
+
+fn run_worker(shared: Arc<PoolShared>) {
+    while let Some(mut job) = shared.take_job() {
+        match job.step() {
+            JobStatus::Running => shared.put_back(job),
+            JobStatus::Finished => {}
+        }
+    }
+}
+
+/// Handed to every `VideoHandle`, which wakes the idle workers
+/// whenever it takes frames or sends a command to its job
+#[derive(Clone)]
+pub struct PoolWaker {
+    shared: Arc<PoolShared>,
+}
+
+impl PoolWaker {
+    pub fn wake(&self) {
+        self.shared.wake_all();
+    }
+}
+
+/// A fixed set of threads decoding every open video, plus the one
+/// thread playing their audio.
+///
+/// Each source gets a frame budget, adjusted to how well its buffer keeps
+/// up and capped at its channel capacity, and stops being scheduled once
+/// that budget is full.
+pub struct DecoderPool {
+    shared: Arc<PoolShared>,
+    workers: Vec<JoinHandle<()>>,
+    max_frames_per_source: usize,
+    audio: AudioOutputThread,
+}
+
+impl DecoderPool {
+    pub fn new(worker_count: usize) -> Self {
+        let shared = Arc::new(PoolShared {
+            jobs: Mutex::new(Vec::new()),
+            wake: Condvar::new(),
+            close: AtomicBool::new(false),
+        });
+
+        let workers = (0..worker_count.max(1))
+            .map(|index| {
+                let shared = shared.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("decoder-{index}"))
+                    .spawn(move || run_worker(shared))
+                    .expect("Failed to spawn decoder worker")
+            })
+            .collect();
+
+        Self {
+            shared,
+            workers,
+            max_frames_per_source: if BENCHMARK_MODE { 512 } else { 32 },
+            audio: AudioOutputThread::spawn(),
+        }
+    }
+
+    /// One worker per core, leaving room for the render thread
+    pub fn with_default_workers() -> Self {
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2);
+
+        Self::new(cores.saturating_sub(1).clamp(1, 4))
+    }
+
+    pub fn worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// The channel capacity of each source, its budget never grows past it
+    pub fn max_frames_per_source(&self) -> usize {
+        self.max_frames_per_source
+    }
+
+    pub fn waker(&self) -> PoolWaker {
+        PoolWaker {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn submit(&self, job: DecoderJob) {
+        self.shared.put_back(job);
+    }
+
+    /// Plays `ring` into `sink` until `control` is closed
+    pub fn play_audio(
+        &self,
+        ring: Arc<AudioRingBuffer>,
+        control: Arc<AudioControl>,
+        sink: Box<dyn AudioSink>,
+    ) {
+        self.audio.add(ring, control, sink);
+    }
+}
+
+impl Drop for DecoderPool {
+    fn drop(&mut self) {
+        self.shared.close.store(true, Ordering::Relaxed);
+        self.shared.wake_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+
+        // Dropping the jobs disconnects their handles
+        self.shared.jobs.lock().unwrap().clear();
+    }
+}
@@ -1,6 +1,7 @@
-use crossbeam_channel::{Receiver, Sender};
-use ffmpeg_next::{format, frame, Rational};
+use crossbeam_channel::{Receiver, Sender, TryRecvError, TrySendError};
+use ffmpeg_next::{format, frame, rescale, software::scaling, Rational, Rescale};
 use std::{
+    collections::VecDeque,
     path::PathBuf,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -9,13 +10,27 @@ use std::{
     time::{Duration, Instant},
 };
 
-use super::{InitData, SeekCommand};
+use super::{
+    AudioDecoder, AudioRingBuffer, ColorInfo, FramePool, InitData, PlaneBuffer, PlaybackClock,
+    SeekCommand, SKIP_DURATION,
+};
+
+/// Frames the handle holds besides its channel, the next and the queued one
+const FRAMES_OUTSIDE_CHANNEL: usize = 2;
+
+/// Where the frame budget of a source starts, and the least it shrinks to
+const MIN_FRAME_BUDGET: usize = 4;
+
+/// Late frames skipped in a row before one is sent anyway, a decoder that
+/// can't keep up at all still shows something instead of a frozen picture
+const MAX_CONSECUTIVE_DROPS: u32 = 8;
 
 #[derive(Debug)]
 pub enum PipelineEvent<T> {
     Data(T),
     SeekAck,
     EOS,
+    Error(VideoError),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -26,41 +41,83 @@ pub struct Resolution {
 
 #[derive(Clone, Debug)]
 pub struct YuvPlanes {
-    pub y_plane: bytes::Bytes,
-    pub u_plane: bytes::Bytes,
-    pub v_plane: bytes::Bytes,
+    pub y_plane: PlaneBuffer,
+    pub u_plane: PlaneBuffer,
+    pub v_plane: PlaneBuffer,
 }
 
 #[derive(Debug, Clone)]
 pub enum FrameData {
     PlanarYuv420(YuvPlanes),
+    PlanarYuv422(YuvPlanes),
+    PlanarYuv444(YuvPlanes),
+}
+
+impl FrameData {
+    pub fn planes(&self) -> &YuvPlanes {
+        match self {
+            FrameData::PlanarYuv420(planes)
+            | FrameData::PlanarYuv422(planes)
+            | FrameData::PlanarYuv444(planes) => planes,
+        }
+    }
+
+    /// Size of the U and V planes for a frame of the given resolution
+    pub fn chroma_resolution(&self, resolution: Resolution) -> Resolution {
+        let (shift_w, shift_h) = match self {
+            FrameData::PlanarYuv420(_) => (1, 1),
+            FrameData::PlanarYuv422(_) => (1, 0),
+            FrameData::PlanarYuv444(_) => (0, 0),
+        };
+
+        Resolution {
+            width: (resolution.width + (1 << shift_w) - 1) >> shift_w,
+            height: (resolution.height + (1 << shift_h) - 1) >> shift_h,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub data: FrameData,
     pub resolution: Resolution,
+    pub color: ColorInfo,
     #[allow(unused)]
     pub pts: Duration,
     pub decode_cost: Duration,
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum InputInitError {
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum VideoError {
+    #[error("Cannot open {0}: {1}")]
+    Open(PathBuf, ffmpeg_next::Error),
+    #[error("No video stream found")]
+    NoVideoStream,
+    #[error("Failed to create the video decoder: {0}")]
+    Decoder(ffmpeg_next::Error),
+    #[error("Failed to decode a packet: {0}")]
+    Decode(ffmpeg_next::Error),
+    #[error("Failed to seek: {0}")]
+    Seek(ffmpeg_next::Error),
     #[error(transparent)]
-    FfmpegError(#[from] ffmpeg_next::Error),
+    Conversion(#[from] DecoderFrameConversionError),
+    #[error("Failed to spawn the decoder thread: {0}")]
+    Spawn(String),
+    #[error("The decoder thread stopped")]
+    Disconnected,
 }
 
-#[derive(Debug, thiserror::Error)]
-enum DecoderFrameConversionError {
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DecoderFrameConversionError {
     #[error("Unsupported pixel format: {0:?}")]
     UnsupportedPixelFormat(format::pixel::Pixel),
+    #[error("Failed to convert from {0:?}: {1}")]
+    ScalerFailed(format::pixel::Pixel, ffmpeg_next::Error),
 }
 
-fn copy_plane_from_av(decoded: &frame::Video, plane: usize) -> bytes::Bytes {
-    let mut output_buffer = bytes::BytesMut::with_capacity(
-        decoded.plane_width(plane) as usize * decoded.plane_height(plane) as usize,
-    );
+fn copy_plane_from_av(decoded: &frame::Video, plane: usize, pool: &FramePool) -> PlaneBuffer {
+    let mut output_buffer =
+        pool.take(decoded.plane_width(plane) as usize * decoded.plane_height(plane) as usize);
 
     decoded
         .data(plane)
@@ -68,31 +125,160 @@ fn copy_plane_from_av(decoded: &frame::Video, plane: usize) -> bytes::Bytes {
         .map(|chunk| &chunk[..decoded.plane_width(plane) as usize])
         .for_each(|chunk| output_buffer.extend_from_slice(chunk));
 
-    output_buffer.freeze()
+    pool.wrap(output_buffer)
+}
+
+/// First PTS of the stream, which plays at zero. Streams without one start at zero.
+pub(super) fn stream_start_time(stream: &format::stream::Stream) -> i64 {
+    match stream.start_time() {
+        ffmpeg_next::ffi::AV_NOPTS_VALUE => 0,
+        start_time => start_time,
+    }
+}
+
+/// Playback time of `pts`, counted from the stream's `start_time`.
+/// Durations rather than timestamps pass a `start_time` of zero.
+pub(super) fn pts_to_duration(pts: i64, start_time: i64, time_base: Rational) -> Duration {
+    Duration::from_secs_f64(((pts - start_time) as f64 * f64::from(time_base)).max(0.0))
+}
+
+pub(super) fn duration_to_pts(duration: Duration, start_time: i64, time_base: Rational) -> i64 {
+    start_time + (duration.as_micros() as i64).rescale(rescale::TIME_BASE, time_base)
+}
+
+fn copy_planes_from_av(decoded: &frame::Video, pool: &FramePool) -> YuvPlanes {
+    YuvPlanes {
+        y_plane: copy_plane_from_av(decoded, 0, pool),
+        u_plane: copy_plane_from_av(decoded, 1, pool),
+        v_plane: copy_plane_from_av(decoded, 2, pool),
+    }
+}
+
+/// Maps the formats we can upload as is, full range (J)
+/// variants share the layout of their limited range twins
+fn frame_data_from_av(decoded: &frame::Video, pool: &FramePool) -> Option<FrameData> {
+    let data = match decoded.format() {
+        format::Pixel::YUV420P | format::Pixel::YUVJ420P => {
+            FrameData::PlanarYuv420(copy_planes_from_av(decoded, pool))
+        }
+        format::Pixel::YUV422P | format::Pixel::YUVJ422P => {
+            FrameData::PlanarYuv422(copy_planes_from_av(decoded, pool))
+        }
+        format::Pixel::YUV444P | format::Pixel::YUVJ444P => {
+            FrameData::PlanarYuv444(copy_planes_from_av(decoded, pool))
+        }
+        _ => return None,
+    };
+
+    Some(data)
+}
+
+/// Picks the supported layout that keeps the most of the source chroma,
+/// RGB and 4:4:4 sources stay 4:4:4, NV12 and 10 bit 4:2:0 become YUV420P
+fn fallback_format(
+    format: format::Pixel,
+) -> Result<format::Pixel, DecoderFrameConversionError> {
+    let descriptor = format
+        .descriptor()
+        .ok_or(DecoderFrameConversionError::UnsupportedPixelFormat(format))?;
+
+    let target = match (descriptor.log2_chroma_w(), descriptor.log2_chroma_h()) {
+        (0, 0) => format::Pixel::YUV444P,
+        (_, 0) => format::Pixel::YUV422P,
+        _ => format::Pixel::YUV420P,
+    };
+
+    Ok(target)
+}
+
+/// Converts decoded frames through swscale when the decoder
+/// outputs a pixel format we can't upload directly
+pub(super) struct FrameConverter {
+    scaler: Option<scaling::Context>,
+    converted: Option<frame::Video>,
+    pool: FramePool,
+}
+
+impl FrameConverter {
+    pub(super) fn new(pool: FramePool) -> Self {
+        Self {
+            scaler: None,
+            converted: None,
+            pool,
+        }
+    }
+
+    /// The planes to upload and how to read their YUV, which
+    /// is no longer the source's once swscale converted it
+    pub(super) fn convert(
+        &mut self,
+        decoded: &frame::Video,
+    ) -> Result<(FrameData, ColorInfo), DecoderFrameConversionError> {
+        if let Some(data) = frame_data_from_av(decoded, &self.pool) {
+            return Ok((data, ColorInfo::from_av(decoded)));
+        }
+
+        let source = decoded.format();
+        let target = fallback_format(source)?;
+
+        let needs_new_scaler = match self.scaler {
+            Some(ref scaler) => {
+                let input = scaler.input();
+                input.format != source
+                    || input.width != decoded.width()
+                    || input.height != decoded.height()
+            }
+            None => true,
+        };
+
+        if needs_new_scaler {
+            let scaler = scaling::Context::get(
+                source,
+                decoded.width(),
+                decoded.height(),
+                target,
+                decoded.width(),
+                decoded.height(),
+                scaling::Flags::BILINEAR,
+            )
+            .map_err(|e| DecoderFrameConversionError::ScalerFailed(source, e))?;
+
+            self.scaler = Some(scaler);
+            self.converted = Some(frame::Video::empty());
+        }
+
+        let (Some(scaler), Some(converted)) = (self.scaler.as_mut(), self.converted.as_mut())
+        else {
+            return Err(DecoderFrameConversionError::UnsupportedPixelFormat(source));
+        };
+
+        scaler
+            .run(decoded, converted)
+            .map_err(|e| DecoderFrameConversionError::ScalerFailed(source, e))?;
+
+        let data = frame_data_from_av(converted, &self.pool)
+            .ok_or(DecoderFrameConversionError::UnsupportedPixelFormat(source))?;
+
+        Ok((data, ColorInfo::converted_by_swscale(decoded)))
+    }
+}
+
+/// The best effort PTS of a decoded frame, in the stream `time_base`.
+/// Frames without one are taken to be at the start of the stream.
+fn decoded_pts(decoded: &frame::Video, start_time: i64) -> i64 {
+    decoded.timestamp().or(decoded.pts()).unwrap_or(start_time)
 }
 
 fn frame_from_ffmpeg(
     decoded: &mut frame::Video,
+    converter: &mut FrameConverter,
+    start_time: i64,
     time_base: Rational,
     decode_start: Instant,
 ) -> Result<Frame, DecoderFrameConversionError> {
-    let data = match decoded.format() {
-        format::Pixel::YUV420P => FrameData::PlanarYuv420(YuvPlanes {
-            y_plane: copy_plane_from_av(decoded, 0),
-            u_plane: copy_plane_from_av(decoded, 1),
-            v_plane: copy_plane_from_av(decoded, 2),
-        }),
-        fmt => return Err(DecoderFrameConversionError::UnsupportedPixelFormat(fmt)),
-    };
-
-    let pts = decoded.pts().unwrap();
+    let (data, color) = converter.convert(decoded)?;
 
-    let pts: Duration = if pts != 0 {
-        let pts_in_time_base = pts as f64 / time_base.denominator() as f64;
-        Duration::from_secs_f64(pts_in_time_base)
-    } else {
-        Duration::from_secs_f64(0.0)
-    };
+    let pts = pts_to_duration(decoded_pts(decoded, start_time), start_time, time_base);
 
     let decode_end = Instant::now();
 
@@ -102,132 +288,485 @@ fn frame_from_ffmpeg(
             width: decoded.width().try_into().unwrap(),
             height: decoded.height().try_into().unwrap(),
         },
+        color,
         pts,
         decode_cost: decode_end - decode_start,
     })
 }
 
-pub fn run_decoder_thread(
-    file: PathBuf,
-    init_result_sender: Sender<Result<InitData, InputInitError>>,
-    frame_sender: Sender<PipelineEvent<Frame>>,
-    close_thread: Arc<AtomicBool>,
-    command_receiver: Receiver<SeekCommand>,
-) {
-    if let Ok(mut ictx) = ffmpeg_next::format::input(&file) {
-        let (params, video_stream_index, avg_frame_rate, total_duration) = {
-            let input = ictx
-                .streams()
-                .best(ffmpeg_next::media::Type::Video)
-                .ok_or(ffmpeg_next::Error::StreamNotFound)
-                .expect("No stream found");
+/// Where the decoder should land after a seek, in the stream `time_base`
+struct SeekTarget {
+    pts: i64,
+    /// `pts` is a frame PTS taken from the index
+    exact: bool,
+}
 
-            let video_stream_index = input.index();
+impl SeekTarget {
+    /// An exact target is covered by its own frame, a time target by the frame
+    /// whose PTS is the last at or before it. Without the next PTS at hand
+    /// that is estimated with the average frame duration, which is rounded
+    /// and off by a tick on irregular timestamps, so exact seeks don't use it
+    fn is_covered_by(&self, pts: i64, frame_duration: i64) -> bool {
+        if self.exact {
+            pts >= self.pts
+        } else {
+            pts + frame_duration > self.pts
+        }
+    }
+}
+
+struct OpenedInput {
+    ictx: format::context::Input,
+    decoder: ffmpeg_next::decoder::Video,
+    audio_decoder: Option<AudioDecoder>,
+    video_stream_index: usize,
+    avg_frame_rate: Rational,
+    start_time: i64,
+    time_base: Rational,
+    total_duration: Duration,
+}
+
+fn open_input(file: &PathBuf, audio_ring: Arc<AudioRingBuffer>) -> Result<OpenedInput, VideoError> {
+    let ictx =
+        ffmpeg_next::format::input(file).map_err(|e| VideoError::Open(file.clone(), e))?;
+
+    let (params, video_stream_index, avg_frame_rate, start_time, time_base, total_duration) = {
+        let input = ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or(VideoError::NoVideoStream)?;
+
+        let time_base = input.time_base();
+
+        (
+            input.parameters(),
+            input.index(),
+            input.avg_frame_rate(),
+            stream_start_time(&input),
+            time_base,
+            pts_to_duration(input.duration(), 0, time_base),
+        )
+    };
+
+    let decoder = ffmpeg_next::codec::context::Context::from_parameters(params)
+        .and_then(|context| context.decoder().video())
+        .map_err(VideoError::Decoder)?;
+
+    // The audio shares the video's zero, so both clocks agree
+    let audio_decoder =
+        AudioDecoder::open(&ictx, audio_ring, pts_to_duration(start_time, 0, time_base));
+
+    Ok(OpenedInput {
+        ictx,
+        decoder,
+        audio_decoder,
+        video_stream_index,
+        avg_frame_rate,
+        start_time,
+        time_base,
+        total_duration,
+    })
+}
 
-            let time_sabe = input.time_base();
-            let time_base_den = time_sabe.denominator() as f64 / time_sabe.numerator() as f64;
-            let total_duration = input.duration() as f64 / time_base_den;
+/// Everything holding a raw ffmpeg context: the demuxer, both
+/// decoders, the resampler and swscale
+struct FfmpegContexts {
+    input: OpenedInput,
+    converter: FrameConverter,
+}
+
+// SAFETY: ffmpeg-next leaves its wrappers `!Send` because they hold raw
+// pointers, not because the contexts are tied to a thread. libavformat,
+// libavcodec, libswscale and libswresample contexts may move between
+// threads as long as only one uses them at a time. These are created in
+// `open_input` and `FrameConverter::new`, owned only by this struct (none
+// of them shares an owner with another context) and only reached through
+// `&mut`, so whoever holds the `DecoderJob` is the only one using them.
+unsafe impl Send for FfmpegContexts {}
+
+/// Decoder state of an opened input, kept between steps
+struct InputDecoder {
+    ffmpeg: FfmpegContexts,
+    seek_target: Option<SeekTarget>,
+    // Duration of a single frame in the stream time_base
+    frame_duration: i64,
+    at_eos: bool,
+    clock: Arc<PlaybackClock>,
+    consecutive_drops: u32,
+}
+
+impl InputDecoder {
+    fn new(input: OpenedInput, pool: FramePool, clock: Arc<PlaybackClock>) -> Self {
+        let frame_duration = 1i64
+            .rescale(input.avg_frame_rate.invert(), input.time_base)
+            .max(1);
+
+        Self {
+            ffmpeg: FfmpegContexts {
+                input,
+                converter: FrameConverter::new(pool),
+            },
+            seek_target: None,
+            frame_duration,
+            at_eos: false,
+            clock,
+            consecutive_drops: 0,
+        }
+    }
+
+    /// Whether the frame's whole display interval already passed on the
+    /// playback clock, the handle would only dequeue it to drop it
+    fn is_late(&self, pts: i64) -> bool {
+        let Some(position) = self.clock.running_position() else {
+            return false;
+        };
+
+        let end = pts_to_duration(
+            pts + self.frame_duration,
+            self.ffmpeg.input.start_time,
+            self.ffmpeg.input.time_base,
+        );
+
+        end <= position && self.consecutive_drops < MAX_CONSECUTIVE_DROPS
+    }
 
-            let params = input.parameters();
+    fn seek(&mut self, command: SeekCommand, outbox: &mut VecDeque<PipelineEvent<Frame>>) {
+        let OpenedInput {
+            ref mut ictx,
+            ref mut decoder,
+            ref mut audio_decoder,
+            avg_frame_rate,
+            start_time,
+            time_base,
+            total_duration,
+            ..
+        } = self.ffmpeg.input;
+
+        let target_pts = match command {
+            SeekCommand::Stop => start_time,
+            SeekCommand::SkipForward(from) => duration_to_pts(
+                (from + SKIP_DURATION).min(total_duration),
+                start_time,
+                time_base,
+            ),
+            SeekCommand::SkipBackward(from) => {
+                duration_to_pts(from.saturating_sub(SKIP_DURATION), start_time, time_base)
+            }
+            SeekCommand::Seek(to) => duration_to_pts(to, start_time, time_base),
+            SeekCommand::SeekFrame(index) => {
+                start_time + index.max(0).rescale(avg_frame_rate.invert(), time_base)
+            }
+            SeekCommand::SeekExact { target, .. } => target,
+        };
 
-            let avg_frame_rate = input.avg_frame_rate();
+        outbox.push_back(PipelineEvent::SeekAck);
 
-            (params, video_stream_index, avg_frame_rate, total_duration)
+        // Land on the keyframe preceding the target, the
+        // frames in between are decoded and discarded in `drain`
+        let keyframe_pts = match command {
+            SeekCommand::SeekExact { keyframe, .. } => keyframe,
+            _ => target_pts,
         };
 
-        init_result_sender
-            .send(Ok(InitData {
-                fps: avg_frame_rate.into(),
-                total_duration: Duration::from_secs_f64(total_duration),
-            }))
-            .unwrap();
+        let seek_ts = keyframe_pts.rescale(time_base, rescale::TIME_BASE);
+
+        if let Err(e) = ictx.seek(seek_ts, ..seek_ts) {
+            outbox.push_back(PipelineEvent::Error(VideoError::Seek(e)));
+        }
+
+        decoder.flush();
+
+        if let Some(audio_decoder) = audio_decoder {
+            audio_decoder.seek(pts_to_duration(target_pts, start_time, time_base));
+        }
+
+        self.seek_target = Some(SeekTarget {
+            pts: target_pts,
+            exact: matches!(command, SeekCommand::SeekExact { .. }),
+        });
+        self.at_eos = false;
+    }
+
+    /// Feeds a single packet to the decoders, at the end of
+    /// the input flushes them and queues an EOS instead
+    fn decode_packet(&mut self, outbox: &mut VecDeque<PipelineEvent<Frame>>) {
+        let decode_start = Instant::now();
 
-        let context_decoder = ffmpeg_next::codec::context::Context::from_parameters(params.clone())
-            .expect("Failed to build context");
+        let input = &mut self.ffmpeg.input;
 
-        let mut decoder = context_decoder
-            .decoder()
-            .video()
-            .expect("Failed to create video decoder");
+        let Some((stream, packet)) = input.ictx.packets().next() else {
+            // Flush the frames still buffered in the decoders
+            let _ = input.decoder.send_eof();
 
-        let should_stop = || close_thread.load(Ordering::Relaxed);
+            if let Some(ref mut audio_decoder) = input.audio_decoder {
+                audio_decoder.send_eof();
+            }
+
+            self.drain(decode_start, outbox);
 
-        loop {
-            puffin::profile_scope!("Video Packet Processing");
+            outbox.push_back(PipelineEvent::EOS);
+            self.at_eos = true;
+            return;
+        };
 
-            if should_stop() {
+        if let Some(ref mut audio_decoder) = input.audio_decoder {
+            if stream.index() == audio_decoder.stream_index {
+                audio_decoder.send_packet(&packet);
                 return;
             }
+        }
 
-            let mut iter = ictx.packets();
-
-            let mut seek_to: Option<i64> = None;
-
-            let decode_start = Instant::now();
-            if let Some((stream, packet)) = iter.next() {
-                if stream.index() == video_stream_index {
-                    decoder
-                        .send_packet(&packet)
-                        .expect("Could not send the packet for some reason");
-
-                    let mut decoded = ffmpeg_next::util::frame::video::Video::empty();
-
-                    while decoder.receive_frame(&mut decoded).is_ok() {
-                        puffin::profile_scope!("Frame Receive");
-                        if should_stop() {
-                            break;
-                        }
-
-                        let time_sabe = stream.time_base();
-
-                        let frame = match frame_from_ffmpeg(&mut decoded, time_sabe, decode_start) {
-                            Ok(frame) => frame,
-                            Err(_) => {
-                                continue;
-                            }
-                        };
-
-                        if frame_sender.send(PipelineEvent::Data(frame)).is_err() {
-                            return;
-                        }
-
-                        if let Ok(command) = command_receiver.try_recv() {
-                            match command {
-                                SeekCommand::Seek(value) => {
-                                    if frame_sender.send(PipelineEvent::SeekAck).is_err() {
-                                        return;
-                                    }
-
-                                    seek_to = Some(
-                                        (stream.duration() as f64 * 62.29626645645534 * value)
-                                            .round() as i64,
-                                    );
-                                    decoder.flush();
-                                    break;
-                                }
-                                _ => {}
-                            }
-                        }
-                    }
+        if stream.index() != input.video_stream_index {
+            return;
+        }
 
-                    if let Some(seek_to) = seek_to.take() {
-                        let seek_range = ..seek_to;
-                        ictx.seek(seek_to, seek_range).unwrap();
-                    }
+        // A corrupt packet only costs us its frames, keep going
+        if let Err(e) = input.decoder.send_packet(&packet) {
+            outbox.push_back(PipelineEvent::Error(VideoError::Decode(e)));
+        }
 
-                    if should_stop() {
-                        break;
+        self.drain(decode_start, outbox);
+    }
+
+    /// Queues every frame the decoder has ready, skipping the ones
+    /// before a pending seek target and the ones already late
+    fn drain(&mut self, decode_start: Instant, outbox: &mut VecDeque<PipelineEvent<Frame>>) {
+        let mut decoded = ffmpeg_next::util::frame::video::Video::empty();
+
+        while self
+            .ffmpeg
+            .input
+            .decoder
+            .receive_frame(&mut decoded)
+            .is_ok()
+        {
+            puffin::profile_scope!("Frame Receive");
+
+            let pts = decoded_pts(&decoded, self.ffmpeg.input.start_time);
+
+            match self.seek_target {
+                Some(ref target) => {
+                    if !target.is_covered_by(pts, self.frame_duration) {
+                        continue;
                     }
+
+                    // The handle blocks on the frame it seeked to, never skip it
+                    self.seek_target = None;
                 }
-            } else {
-                break;
+                None if self.is_late(pts) => {
+                    self.consecutive_drops += 1;
+                    self.clock.record_decoder_drop();
+                    continue;
+                }
+                None => {}
+            }
+
+            self.consecutive_drops = 0;
+
+            let event = match frame_from_ffmpeg(
+                &mut decoded,
+                &mut self.ffmpeg.converter,
+                self.ffmpeg.input.start_time,
+                self.ffmpeg.input.time_base,
+                decode_start,
+            ) {
+                Ok(frame) => PipelineEvent::Data(frame),
+                Err(e) => PipelineEvent::Error(e.into()),
+            };
+
+            outbox.push_back(event);
+        }
+    }
+}
+
+/// What the worker should do with a job after stepping it
+pub enum JobStatus {
+    /// Queue it again
+    Running,
+    /// The handle is gone or the input could not be opened
+    Finished,
+}
+
+/// A single video source scheduled on the `DecoderPool`.
+///
+/// Each step decodes at most one packet, so a handful of
+/// workers can take turns on any number of sources.
+pub struct DecoderJob {
+    file: PathBuf,
+    init_result_sender: Sender<Result<InitData, VideoError>>,
+    frame_sender: Sender<PipelineEvent<Frame>>,
+    close_thread: Arc<AtomicBool>,
+    command_receiver: Receiver<SeekCommand>,
+    audio_ring: Arc<AudioRingBuffer>,
+    clock: Arc<PlaybackClock>,
+    decoder: Option<InputDecoder>,
+    // Events already decoded, waiting for room in the frame channel
+    outbox: VecDeque<PipelineEvent<Frame>>,
+    /// Frames this source may have decoded ahead, see `update_budget`
+    frame_budget: usize,
+    /// Fewest frames in flight seen since the budget was last filled
+    low_water: usize,
+}
+
+impl DecoderJob {
+    pub fn new(
+        file: PathBuf,
+        init_result_sender: Sender<Result<InitData, VideoError>>,
+        frame_sender: Sender<PipelineEvent<Frame>>,
+        close_thread: Arc<AtomicBool>,
+        command_receiver: Receiver<SeekCommand>,
+        audio_ring: Arc<AudioRingBuffer>,
+        clock: Arc<PlaybackClock>,
+    ) -> Self {
+        Self {
+            file,
+            init_result_sender,
+            frame_sender,
+            close_thread,
+            command_receiver,
+            audio_ring,
+            clock,
+            decoder: None,
+            outbox: VecDeque::new(),
+            frame_budget: MIN_FRAME_BUDGET,
+            low_water: MIN_FRAME_BUDGET,
+        }
+    }
+
+    /// Decoded events the handle hasn't taken yet
+    fn frames_in_flight(&self) -> usize {
+        self.frame_sender.len() + self.outbox.len()
+    }
+
+    /// How full the frame budget of this source is
+    pub fn buffer_health(&self) -> f64 {
+        self.frames_in_flight() as f64 / self.frame_budget.max(1) as f64
+    }
+
+    /// Doubles the budget when the handle ran the buffer dry while playing,
+    /// the source wasn't decoded far enough ahead. Takes one off when the
+    /// buffer filled up again without ever dropping below half, it holds
+    /// more frames than the handle needs. Capped at the channel capacity.
+    fn update_budget(&mut self) {
+        let in_flight = self.frames_in_flight();
+        let max_budget = self.frame_sender.capacity().unwrap_or(MIN_FRAME_BUDGET);
+
+        self.low_water = self.low_water.min(in_flight);
+
+        if in_flight == 0 && self.clock.running_position().is_some() {
+            self.frame_budget = (self.frame_budget * 2).min(max_budget);
+            self.low_water = self.frame_budget;
+        } else if in_flight >= self.frame_budget {
+            if self.low_water > self.frame_budget / 2 {
+                self.frame_budget = (self.frame_budget - 1).max(MIN_FRAME_BUDGET);
+            }
+
+            self.low_water = in_flight;
+        }
+    }
+
+    /// Whether stepping the job now would make any progress
+    pub fn is_ready(&self) -> bool {
+        if self.close_thread.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let Some(ref decoder) = self.decoder else {
+            return true;
+        };
+
+        if self.frames_in_flight() >= self.frame_budget {
+            return false;
+        }
+
+        // Nothing left to decode until the next seek
+        let idle = decoder.at_eos && self.outbox.is_empty();
+
+        !idle || !self.command_receiver.is_empty()
+    }
+
+    pub fn step(&mut self) -> JobStatus {
+        puffin::profile_scope!("Video Packet Processing");
+
+        if self.close_thread.load(Ordering::Relaxed) {
+            return JobStatus::Finished;
+        }
+
+        if !self.flush_outbox() {
+            return JobStatus::Finished;
+        }
+
+        self.update_budget();
+
+        // Over budget, wait for the handle to catch up
+        if !self.outbox.is_empty() || self.frames_in_flight() >= self.frame_budget {
+            return JobStatus::Running;
+        }
+
+        let Some(ref mut decoder) = self.decoder else {
+            return self.open();
+        };
+
+        match self.command_receiver.try_recv() {
+            Ok(command) => decoder.seek(command, &mut self.outbox),
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => return JobStatus::Finished,
+        }
+
+        // Any seek restarts decoding
+        if !decoder.at_eos {
+            decoder.decode_packet(&mut self.outbox);
+        }
+
+        if !self.flush_outbox() {
+            return JobStatus::Finished;
+        }
+
+        JobStatus::Running
+    }
+
+    fn open(&mut self) -> JobStatus {
+        let input = match open_input(&self.file, self.audio_ring.clone()) {
+            Ok(input) => input,
+            Err(e) => {
+                let _ = self.init_result_sender.send(Err(e));
+                return JobStatus::Finished;
             }
+        };
+
+        let init_data = InitData {
+            fps: input.avg_frame_rate.into(),
+            total_duration: input.total_duration,
+            has_audio: input.audio_decoder.is_some(),
+        };
+
+        if self.init_result_sender.send(Ok(init_data)).is_err() {
+            return JobStatus::Finished;
         }
 
-        decoder.send_eof().expect("EOF sending failed");
+        // Enough buffers for every frame the source can have in flight
+        let frame_budget = self.frame_sender.capacity().unwrap_or(1) + FRAMES_OUTSIDE_CHANNEL;
+        let pool = FramePool::new(3 * frame_budget);
+
+        self.decoder = Some(InputDecoder::new(input, pool, self.clock.clone()));
+
+        JobStatus::Running
     }
 
-    if frame_sender.send(PipelineEvent::EOS).is_err() {
-        println!("Failed to send EOS from H264 decoder. Channel closed.")
+    /// Moves queued events into the frame channel without
+    /// blocking. Returns false once the receiver is gone.
+    fn flush_outbox(&mut self) -> bool {
+        while let Some(event) = self.outbox.pop_front() {
+            match self.frame_sender.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    self.outbox.push_front(event);
+                    return true;
+                }
+                Err(TrySendError::Disconnected(_)) => return false,
+            }
+        }
+
+        true
     }
 }
@@ -0,0 +1,68 @@
+use crossbeam_channel::{Receiver, Sender};
+use std::{fmt, ops::Deref, sync::Arc};
+
+/// Plane buffers the decoder reuses once the render thread is done with them.
+///
+/// A `PlaneBuffer` sends its memory back when the last clone of it drops,
+/// after warming up a source decodes without allocating.
+#[derive(Clone)]
+pub struct FramePool {
+    returned: Sender<Vec<u8>>,
+    free: Receiver<Vec<u8>>,
+}
+
+impl FramePool {
+    /// Keeps at most `capacity` buffers around, any past that are freed
+    pub fn new(capacity: usize) -> Self {
+        let (returned, free) = crossbeam_channel::bounded(capacity);
+
+        Self { returned, free }
+    }
+
+    /// An empty buffer with room for `len` bytes, recycled if one is free
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        let mut buffer = self.free.try_recv().unwrap_or_default();
+
+        buffer.clear();
+        buffer.reserve(len);
+
+        buffer
+    }
+
+    pub fn wrap(&self, data: Vec<u8>) -> PlaneBuffer {
+        PlaneBuffer(Arc::new(PooledData {
+            data,
+            pool: self.returned.clone(),
+        }))
+    }
+}
+
+struct PooledData {
+    data: Vec<u8>,
+    pool: Sender<Vec<u8>>,
+}
+
+impl Drop for PooledData {
+    fn drop(&mut self) {
+        // A full pool or a finished decoder just frees it
+        let _ = self.pool.try_send(std::mem::take(&mut self.data));
+    }
+}
+
+/// Tightly packed rows of one plane, cheap to clone
+#[derive(Clone)]
+pub struct PlaneBuffer(Arc<PooledData>);
+
+impl Deref for PlaneBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+impl fmt::Debug for PlaneBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PlaneBuffer({} bytes)", self.len())
+    }
+}
@@ -0,0 +1,390 @@
+use crossbeam_channel::{Receiver, Sender};
+use ffmpeg_next::{frame, rescale, Rational, Rescale};
+use std::{
+    fs,
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::fs_utils::cache_dir;
+
+use super::{
+    decoder_thread::{pts_to_duration, stream_start_time},
+    thumbnail_from_av, SeekCommand, Thumbnail, VideoError,
+};
+
+/// How many thumbnails the filmstrip under the scrubber shows
+pub const FILMSTRIP_LENGTH: usize = 24;
+
+pub const FILMSTRIP_THUMBNAIL_WIDTH: u32 = 96;
+
+const THUMBNAIL_CACHE_DIR: &str = "thumbnails";
+
+// Version 2 counts thumbnail times from the stream's start_time
+const THUMBNAIL_CACHE_MAGIC: &[u8; 4] = b"THM2";
+
+/// Every frame and keyframe of the video stream, in the stream
+/// `time_base`, plus the thumbnails of the filmstrip
+pub struct VideoIndex {
+    pub time_base: Rational,
+    /// PTS that plays at zero
+    pub start_time: i64,
+    /// Presentation timestamps of every frame, sorted
+    pub frames: Vec<i64>,
+    /// Presentation timestamps of every keyframe, sorted
+    pub keyframes: Vec<i64>,
+    pub thumbnails: Vec<(Duration, Thumbnail)>,
+}
+
+impl VideoIndex {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn frame_time(&self, index: usize) -> Option<Duration> {
+        self.frames
+            .get(index)
+            .map(|&pts| pts_to_duration(pts, self.start_time, self.time_base))
+    }
+
+    /// The frame on screen at `time`, the last one starting at or before it.
+    /// Frame PTS go through the same conversion, so exact matches are exact.
+    pub fn frame_index_at(&self, time: Duration) -> usize {
+        self.frames
+            .partition_point(|&pts| pts_to_duration(pts, self.start_time, self.time_base) <= time)
+            .saturating_sub(1)
+    }
+
+    fn keyframe_before(&self, pts: i64) -> i64 {
+        let index = self.keyframes.partition_point(|&keyframe| keyframe <= pts);
+
+        // Without an earlier keyframe the demuxer picks the closest one
+        match index {
+            0 => pts,
+            _ => self.keyframes[index - 1],
+        }
+    }
+
+    /// A seek landing exactly on frame `index`, starting from its keyframe
+    pub fn seek_command(&self, index: usize) -> Option<SeekCommand> {
+        let target = *self.frames.get(index)?;
+
+        Some(SeekCommand::SeekExact {
+            keyframe: self.keyframe_before(target),
+            target,
+        })
+    }
+}
+
+struct IndexRequest {
+    file: PathBuf,
+    /// Set once the handle is closed, the index is no longer wanted
+    cancelled: Arc<AtomicBool>,
+    result: Sender<Result<VideoIndex, VideoError>>,
+}
+
+/// The one indexer thread, started with the first request. Indexing
+/// decodes a keyframe per thumbnail, so files opened together are
+/// indexed one after the other instead of all at once.
+fn indexer_queue() -> Result<&'static Sender<IndexRequest>, VideoError> {
+    static QUEUE: OnceLock<Result<Sender<IndexRequest>, String>> = OnceLock::new();
+
+    QUEUE
+        .get_or_init(|| {
+            let (sender, receiver) = crossbeam_channel::unbounded::<IndexRequest>();
+
+            std::thread::Builder::new()
+                .name("video-indexer".into())
+                .spawn(move || {
+                    for request in receiver {
+                        if request.cancelled.load(Ordering::Relaxed) {
+                            continue;
+                        }
+
+                        let _ = request.result.send(build_index(&request.file));
+                    }
+                })
+                .map(|_| sender)
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| VideoError::Spawn(e.clone()))
+}
+
+/// Queues `file` on the indexer thread, the receiver gets a single
+/// result once the walk is done. Skipped if `cancelled` is set by then.
+pub fn spawn_indexer(
+    file: PathBuf,
+    cancelled: Arc<AtomicBool>,
+) -> Receiver<Result<VideoIndex, VideoError>> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+
+    let queued = indexer_queue().and_then(|queue| {
+        queue
+            .send(IndexRequest {
+                file,
+                cancelled,
+                result: sender.clone(),
+            })
+            .map_err(|_| VideoError::Disconnected)
+    });
+
+    if let Err(e) = queued {
+        let _ = sender.send(Err(e));
+    }
+
+    receiver
+}
+
+pub(super) fn build_index(file: &PathBuf) -> Result<VideoIndex, VideoError> {
+    puffin::profile_function!();
+
+    let mut ictx =
+        ffmpeg_next::format::input(file).map_err(|e| VideoError::Open(file.clone(), e))?;
+
+    let (params, stream_index, start_time, time_base) = {
+        let input = ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or(VideoError::NoVideoStream)?;
+
+        (
+            input.parameters(),
+            input.index(),
+            stream_start_time(&input),
+            input.time_base(),
+        )
+    };
+
+    // Walking the packets is enough, no need to decode anything
+    let mut frames = vec![];
+    let mut keyframes = vec![];
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+
+        let Some(pts) = packet.pts().or(packet.dts()) else {
+            continue;
+        };
+
+        frames.push(pts);
+
+        if packet.is_key() {
+            keyframes.push(pts);
+        }
+    }
+
+    // Packets come in decode order
+    frames.sort_unstable();
+    keyframes.sort_unstable();
+
+    let thumbnails = match load_cached_thumbnails(file) {
+        Some(thumbnails) => thumbnails,
+        None => {
+            let mut decoder = ffmpeg_next::codec::context::Context::from_parameters(params)
+                .and_then(|context| context.decoder().video())
+                .map_err(VideoError::Decoder)?;
+
+            let thumbnails = decode_filmstrip(
+                &mut ictx,
+                &mut decoder,
+                stream_index,
+                start_time,
+                time_base,
+                &frames,
+                &keyframes,
+            )?;
+
+            if let Err(e) = store_cached_thumbnails(file, &thumbnails) {
+                eprintln!("Failed to cache thumbnails of {}: {e}", file.display());
+            }
+
+            thumbnails
+        }
+    };
+
+    Ok(VideoIndex {
+        time_base,
+        start_time,
+        frames,
+        keyframes,
+        thumbnails,
+    })
+}
+
+/// Decodes one thumbnail per slice of the video, from the keyframe
+/// nearest the middle of each slice since those decode on their own
+fn decode_filmstrip(
+    ictx: &mut ffmpeg_next::format::context::Input,
+    decoder: &mut ffmpeg_next::decoder::Video,
+    stream_index: usize,
+    start_time: i64,
+    time_base: Rational,
+    frames: &[i64],
+    keyframes: &[i64],
+) -> Result<Vec<(Duration, Thumbnail)>, VideoError> {
+    let (Some(&first), Some(&last)) = (frames.first(), frames.last()) else {
+        return Ok(vec![]);
+    };
+
+    let mut thumbnails: Vec<(Duration, Thumbnail)> = vec![];
+    let mut decoded = frame::Video::empty();
+
+    for slot in 0..FILMSTRIP_LENGTH {
+        let middle = first + (last - first) * (2 * slot as i64 + 1) / (2 * FILMSTRIP_LENGTH as i64);
+
+        let Some(&keyframe) = keyframes
+            .iter()
+            .min_by_key(|&&keyframe| (keyframe - middle).abs())
+        else {
+            break;
+        };
+
+        let time = pts_to_duration(keyframe, start_time, time_base);
+
+        // Long GOPs map several slices to the same keyframe
+        if let Some((previous, thumbnail)) = thumbnails.last() {
+            if *previous == time {
+                let thumbnail = thumbnail.clone();
+                thumbnails.push((time, thumbnail));
+                continue;
+            }
+        }
+
+        let seek_ts = keyframe.rescale(time_base, rescale::TIME_BASE);
+
+        ictx.seek(seek_ts, ..seek_ts).map_err(VideoError::Seek)?;
+        decoder.flush();
+
+        for (stream, packet) in ictx.packets() {
+            if stream.index() != stream_index || decoder.send_packet(&packet).is_err() {
+                continue;
+            }
+
+            if decoder.receive_frame(&mut decoded).is_ok() {
+                let thumbnail = thumbnail_from_av(&decoded, FILMSTRIP_THUMBNAIL_WIDTH)?;
+                thumbnails.push((time, thumbnail));
+                break;
+            }
+        }
+    }
+
+    Ok(thumbnails)
+}
+
+/// 64 bit FNV-1a, the cache keys have to stay the same across toolchains
+fn fnv1a(bytes: &[u8], hash: u64) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Cache files are keyed by path, size and modification time,
+/// so a file replaced in place gets new thumbnails
+fn cache_path(file: &Path) -> Option<PathBuf> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    hash = fnv1a(file.as_os_str().as_encoded_bytes(), hash);
+    hash = fnv1a(&metadata.len().to_le_bytes(), hash);
+    hash = fnv1a(&modified.as_secs().to_le_bytes(), hash);
+    hash = fnv1a(&modified.subsec_nanos().to_le_bytes(), hash);
+    hash = fnv1a(&(FILMSTRIP_LENGTH as u64).to_le_bytes(), hash);
+    hash = fnv1a(&FILMSTRIP_THUMBNAIL_WIDTH.to_le_bytes(), hash);
+
+    Some(cache_dir().join(THUMBNAIL_CACHE_DIR).join(format!("{hash:016x}.thumbs")))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Bytes of the header in front of every thumbnail: time, width, height
+const THUMBNAIL_HEADER_SIZE: u64 = 16;
+
+fn load_cached_thumbnails(file: &Path) -> Option<Vec<(Duration, Thumbnail)>> {
+    let cache = fs::File::open(cache_path(file)?).ok()?;
+    let file_size = cache.metadata().ok()?.len();
+    let mut reader = io::BufReader::new(cache);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).ok()?;
+
+    if &magic != THUMBNAIL_CACHE_MAGIC {
+        return None;
+    }
+
+    let count = read_u32(&mut reader).ok()?;
+
+    // Sizes are checked against what is left of the file before
+    // allocating, a corrupt header must not ask for gigabytes
+    let mut remaining = file_size.checked_sub(reader.stream_position().ok()?)?;
+
+    (0..count)
+        .map(|_| {
+            let corrupt = || io::Error::new(io::ErrorKind::InvalidData, "corrupt thumbnail cache");
+
+            remaining = remaining.checked_sub(THUMBNAIL_HEADER_SIZE).ok_or_else(corrupt)?;
+
+            let time = Duration::from_micros(read_u64(&mut reader)?);
+            let width = read_u32(&mut reader)? as u64;
+            let height = read_u32(&mut reader)? as u64;
+
+            let size = (width * height).checked_mul(4).ok_or_else(corrupt)?;
+            remaining = remaining.checked_sub(size).ok_or_else(corrupt)?;
+
+            let mut rgba = vec![0u8; size as usize];
+            reader.read_exact(&mut rgba)?;
+
+            Ok((
+                time,
+                Thumbnail {
+                    width: width as usize,
+                    height: height as usize,
+                    rgba,
+                },
+            ))
+        })
+        .collect::<io::Result<_>>()
+        .ok()
+}
+
+fn store_cached_thumbnails(file: &Path, thumbnails: &[(Duration, Thumbnail)]) -> io::Result<()> {
+    let Some(path) = cache_path(file) else {
+        return Ok(());
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+
+    writer.write_all(THUMBNAIL_CACHE_MAGIC)?;
+    writer.write_all(&(thumbnails.len() as u32).to_le_bytes())?;
+
+    for (time, thumbnail) in thumbnails {
+        writer.write_all(&(time.as_micros() as u64).to_le_bytes())?;
+        writer.write_all(&(thumbnail.width as u32).to_le_bytes())?;
+        writer.write_all(&(thumbnail.height as u32).to_le_bytes())?;
+        writer.write_all(&thumbnail.rgba)?;
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,172 @@
+use crossbeam_channel::{Receiver, TryRecvError};
+use std::path::PathBuf;
+
+use crate::fs_utils::{
+    config_dir, list_files_with_extensions, read_path_list, videos_dir, write_path_list,
+};
+
+use super::{probe_file, AudioOutput, MediaInfo, VideoError};
+
+pub const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mov", "mkv", "webm", "avi", "ts"];
+
+/// Where the recently opened files are kept between runs, in `config_dir`
+const RECENT_FILES_NAME: &str = "recent_media.txt";
+
+/// Where the scanned directories are kept between runs, in `config_dir`
+const DIRECTORIES_NAME: &str = "media_directories.txt";
+
+const MAX_RECENT_FILES: usize = 16;
+
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    pub info: Result<MediaInfo, VideoError>,
+}
+
+/// Video files found in a set of directories, plus the recently opened ones.
+///
+/// Probing runs on a background thread, `poll` collects the results.
+pub struct MediaLibrary {
+    directories: Vec<PathBuf>,
+    /// Used by the videos opened from now on
+    pub audio_output: AudioOutput,
+    entries: Vec<LibraryEntry>,
+    recent: Vec<PathBuf>,
+    scan_receiver: Option<Receiver<LibraryEntry>>,
+    open_requests: Vec<PathBuf>,
+}
+
+impl MediaLibrary {
+    /// Restores the directories and recent files of the last run. The
+    /// first run scans the user's videos folder, if there is one.
+    pub fn load() -> Self {
+        let directories_path = config_dir().join(DIRECTORIES_NAME);
+
+        let directories = if directories_path.exists() {
+            read_path_list(directories_path)
+        } else {
+            videos_dir().into_iter().collect()
+        };
+
+        let mut library = Self {
+            directories,
+            audio_output: AudioOutput::Mute,
+            entries: vec![],
+            recent: read_path_list(config_dir().join(RECENT_FILES_NAME)),
+            scan_receiver: None,
+            open_requests: vec![],
+        };
+
+        library.rescan();
+        library
+    }
+
+    /// Drops the current entries and probes every directory again,
+    /// a scan still in flight stops once its receiver is gone
+    pub fn rescan(&mut self) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        let directories = self.directories.clone();
+
+        let spawned = std::thread::Builder::new()
+            .name("media-library-scan".into())
+            .spawn(move || {
+                for path in directories
+                    .iter()
+                    .flat_map(|dir| list_files_with_extensions(dir, &VIDEO_EXTENSIONS))
+                {
+                    let info = probe_file(&path);
+
+                    if sender.send(LibraryEntry { path, info }).is_err() {
+                        return;
+                    }
+                }
+            });
+
+        self.entries.clear();
+        self.scan_receiver = spawned.ok().map(|_| receiver);
+    }
+
+    /// Collects the files probed since the last call
+    pub fn poll(&mut self) {
+        let Some(ref receiver) = self.scan_receiver else {
+            return;
+        };
+
+        let finished = loop {
+            match receiver.try_recv() {
+                Ok(entry) => self.entries.push(entry),
+                Err(TryRecvError::Empty) => break false,
+                // The sender is dropped once every directory was walked
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        if finished {
+            self.scan_receiver = None;
+        }
+    }
+
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Adds `directory`, saves the list and scans again
+    pub fn add_directory(&mut self, directory: PathBuf) {
+        if self.directories.contains(&directory) {
+            return;
+        }
+
+        self.directories.push(directory);
+        self.save_directories();
+        self.rescan();
+    }
+
+    /// Removes the directory at `index`, saves the list and scans again
+    pub fn remove_directory(&mut self, index: usize) {
+        if index >= self.directories.len() {
+            return;
+        }
+
+        self.directories.remove(index);
+        self.save_directories();
+        self.rescan();
+    }
+
+    fn save_directories(&self) {
+        if let Err(e) = write_path_list(config_dir().join(DIRECTORIES_NAME), &self.directories) {
+            eprintln!("Failed to save media directories: {e}");
+        }
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan_receiver.is_some()
+    }
+
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    pub fn recent(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    /// Asks the application to open `path` as a new video source
+    pub fn request_open(&mut self, path: PathBuf) {
+        self.open_requests.push(path);
+    }
+
+    pub fn take_open_requests(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.open_requests)
+    }
+
+    /// Moves `path` to the top of the recent files and saves the list
+    pub fn add_recent(&mut self, path: PathBuf) {
+        self.recent.retain(|recent| recent != &path);
+        self.recent.insert(0, path);
+        self.recent.truncate(MAX_RECENT_FILES);
+
+        if let Err(e) = write_path_list(config_dir().join(RECENT_FILES_NAME), &self.recent) {
+            eprintln!("Failed to save recent files: {e}");
+        }
+    }
+}
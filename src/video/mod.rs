@@ -1,4 +1,23 @@
+mod audio;
+pub use audio::*;
+mod clock;
+pub use clock::*;
+mod color;
+pub use color::*;
+mod decoder_pool;
+pub use decoder_pool::*;
 mod decoder_thread;
 pub use decoder_thread::*;
+mod frame_pool;
+pub use frame_pool::*;
+mod indexer;
+pub use indexer::*;
+mod library;
+pub use library::*;
+mod probe;
+pub use probe::*;
 mod video_handle;
 pub use video_handle::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,132 @@
+use ffmpeg_next::{format::Pixel, frame, rescale, software::scaling};
+use std::{path::PathBuf, time::Duration};
+
+use super::{decoder_thread::pts_to_duration, DecoderFrameConversionError, Resolution, VideoError};
+
+/// Width of the previews shown by the media library
+pub const THUMBNAIL_WIDTH: u32 = 160;
+
+/// A small RGBA preview of a single frame
+#[derive(Clone)]
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+/// What the media library shows about a file without playing it
+#[derive(Clone)]
+pub struct MediaInfo {
+    pub path: PathBuf,
+    pub duration: Duration,
+    pub resolution: Resolution,
+    pub codec: &'static str,
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// Scales a decoded frame of any pixel format down
+/// to an RGBA thumbnail `width` pixels wide
+pub fn thumbnail_from_av(
+    decoded: &frame::Video,
+    width: u32,
+) -> Result<Thumbnail, DecoderFrameConversionError> {
+    let source = decoded.format();
+    let height =
+        (decoded.height() as u64 * width as u64 / decoded.width().max(1) as u64).max(1) as u32;
+
+    let mut scaler = scaling::Context::get(
+        source,
+        decoded.width(),
+        decoded.height(),
+        Pixel::RGBA,
+        width,
+        height,
+        scaling::Flags::AREA,
+    )
+    .map_err(|e| DecoderFrameConversionError::ScalerFailed(source, e))?;
+
+    let mut scaled = frame::Video::empty();
+
+    scaler
+        .run(decoded, &mut scaled)
+        .map_err(|e| DecoderFrameConversionError::ScalerFailed(source, e))?;
+
+    // Drop the row padding
+    let stride = scaled.stride(0);
+    let row = width as usize * 4;
+    let data = scaled.data(0);
+
+    let rgba = (0..height as usize)
+        .flat_map(|y| &data[y * stride..y * stride + row])
+        .copied()
+        .collect();
+
+    Ok(Thumbnail {
+        width: width as usize,
+        height: height as usize,
+        rgba,
+    })
+}
+
+/// Opens `path` and reads its duration, resolution and codec,
+/// then decodes a thumbnail from a tenth of the way in, since
+/// the very first frames are often black
+pub fn probe_file(path: &PathBuf) -> Result<MediaInfo, VideoError> {
+    let mut ictx =
+        ffmpeg_next::format::input(path).map_err(|e| VideoError::Open(path.clone(), e))?;
+
+    let (params, stream_index, duration) = {
+        let input = ictx
+            .streams()
+            .best(ffmpeg_next::media::Type::Video)
+            .ok_or(VideoError::NoVideoStream)?;
+
+        // Some containers only know the duration of the whole file
+        let duration = if input.duration() > 0 {
+            pts_to_duration(input.duration(), 0, input.time_base())
+        } else {
+            pts_to_duration(ictx.duration().max(0), 0, rescale::TIME_BASE)
+        };
+
+        (input.parameters(), input.index(), duration)
+    };
+
+    let codec = params.id().name();
+
+    let mut decoder = ffmpeg_next::codec::context::Context::from_parameters(params)
+        .and_then(|context| context.decoder().video())
+        .map_err(VideoError::Decoder)?;
+
+    let resolution = Resolution {
+        width: decoder.width() as usize,
+        height: decoder.height() as usize,
+    };
+
+    // In rescale::TIME_BASE, microseconds
+    let seek_ts = (duration / 10).as_micros().min(i64::MAX as u128) as i64;
+
+    // Not every input can seek, the first frame will do then
+    let _ = ictx.seek(seek_ts, ..seek_ts);
+
+    let mut decoded = frame::Video::empty();
+    let mut thumbnail = None;
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index || decoder.send_packet(&packet).is_err() {
+            continue;
+        }
+
+        if decoder.receive_frame(&mut decoded).is_ok() {
+            thumbnail = Some(thumbnail_from_av(&decoded, THUMBNAIL_WIDTH)?);
+            break;
+        }
+    }
+
+    Ok(MediaInfo {
+        path: path.clone(),
+        duration,
+        resolution,
+        codec,
+        thumbnail,
+    })
+}
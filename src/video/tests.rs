@@ -0,0 +1,348 @@
+//! Decoder tests against short clips rendered with the ffmpeg CLI
+
+use crossbeam_channel::{Receiver, Sender};
+use std::{
+    path::PathBuf,
+    process::Command,
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
+
+use crate::shared::Shared;
+
+use super::{
+    decoder_thread::FrameConverter, indexer::build_index, AudioRingBuffer, ColorInfo, DecoderJob,
+    DecoderPool, Frame, FramePool, InitData, NullSink, PipelineEvent, PlayState, PlaybackClock,
+    SeekCommand, VideoHandle, YuvMatrix, YuvRange,
+};
+
+const FPS: u32 = 25;
+const SECONDS: u32 = 4;
+const FRAME_COUNT: i64 = (FPS * SECONDS) as i64;
+
+/// Renders the lavfi test pattern into `name` in the temp directory,
+/// `extra_args` go right before the output path
+fn test_clip(name: &str, extra_args: &[&str]) -> PathBuf {
+    ffmpeg_next::init().unwrap();
+
+    let dir = std::env::temp_dir().join("pony-renderer-tests");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join(name);
+
+    // A keyframe every second, seeks have frames to decode forward through
+    let status = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "lavfi", "-i"])
+        .arg(format!(
+            "testsrc=duration={SECONDS}:size=160x120:rate={FPS}"
+        ))
+        .args([
+            "-c:v",
+            "mpeg4",
+            "-g",
+            &FPS.to_string(),
+            "-pix_fmt",
+            "yuv420p",
+        ])
+        .args(extra_args)
+        .arg(&path)
+        .status()
+        .expect("ffmpeg must be on the PATH to render the test clips");
+
+    assert!(status.success(), "ffmpeg failed to render {name}");
+
+    path
+}
+
+fn frame_time(index: i64) -> f64 {
+    index as f64 / FPS as f64
+}
+
+#[track_caller]
+fn assert_at(pts: Duration, seconds: f64) {
+    assert!(
+        (pts.as_secs_f64() - seconds).abs() < 1e-6,
+        "expected a frame at {seconds}s, got {pts:?}"
+    );
+}
+
+/// A `DecoderJob` stepped on the test thread instead of a pool
+struct SteppedDecoder {
+    job: DecoderJob,
+    commands: Sender<SeekCommand>,
+    frames: Receiver<PipelineEvent<Frame>>,
+    init: InitData,
+}
+
+impl SteppedDecoder {
+    fn open(file: PathBuf) -> Self {
+        let (init_sender, init_receiver) = crossbeam_channel::bounded(1);
+        let (frame_sender, frames) = crossbeam_channel::bounded(4);
+        let (commands, command_receiver) = crossbeam_channel::bounded(1);
+
+        let mut job = DecoderJob::new(
+            file,
+            init_sender,
+            frame_sender,
+            Arc::new(AtomicBool::new(false)),
+            command_receiver,
+            Arc::new(AudioRingBuffer::new()),
+            Arc::new(PlaybackClock::default()),
+        );
+
+        // The first step opens the input
+        job.step();
+
+        let init = init_receiver
+            .try_recv()
+            .expect("opening sends a result")
+            .expect("the clip opens");
+
+        Self {
+            job,
+            commands,
+            frames,
+            init,
+        }
+    }
+
+    fn next_event(&mut self) -> PipelineEvent<Frame> {
+        for _ in 0..100_000 {
+            if let Ok(event) = self.frames.try_recv() {
+                return event;
+            }
+
+            self.job.step();
+        }
+
+        panic!("the decoder stopped making progress");
+    }
+
+    /// `None` at the end of the stream
+    fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            match self.next_event() {
+                PipelineEvent::Data(frame) => return Some(frame),
+                PipelineEvent::EOS => return None,
+                PipelineEvent::SeekAck => {}
+                PipelineEvent::Error(e) => panic!("{e}"),
+            }
+        }
+    }
+
+    /// The first frame sent after the seek is acknowledged
+    fn seek(&mut self, command: SeekCommand) -> Frame {
+        self.commands.send(command).unwrap();
+
+        // Whatever was decoded before the command got picked up
+        loop {
+            match self.next_event() {
+                PipelineEvent::SeekAck => break,
+                PipelineEvent::Error(e) => panic!("{e}"),
+                PipelineEvent::Data(_) | PipelineEvent::EOS => {}
+            }
+        }
+
+        self.next_frame().expect("a frame after the seek")
+    }
+}
+
+#[test]
+fn seek_to_time_lands_on_the_covering_frame() {
+    let mut decoder = SteppedDecoder::open(test_clip("seek_time.mp4", &[]));
+
+    for millis in [0, 1000, 1530, 2999, 3970] {
+        let target = Duration::from_millis(millis);
+        let frame = decoder.seek(SeekCommand::Seek(target));
+
+        let covering = (target.as_secs_f64() * FPS as f64 + 1e-9).floor() as i64;
+
+        assert_at(frame.pts, frame_time(covering));
+    }
+}
+
+#[test]
+fn seek_to_frame_lands_on_that_frame() {
+    let mut decoder = SteppedDecoder::open(test_clip("seek_frame.mp4", &[]));
+
+    for index in [0, 13, 50, 24, FRAME_COUNT - 1] {
+        let frame = decoder.seek(SeekCommand::SeekFrame(index));
+
+        assert_at(frame.pts, frame_time(index));
+    }
+}
+
+#[test]
+fn decoding_ends_with_eos_after_the_last_frame() {
+    let mut decoder = SteppedDecoder::open(test_clip("eos.mp4", &[]));
+
+    assert_at(decoder.init.total_duration, SECONDS as f64);
+
+    let first = decoder.seek(SeekCommand::SeekFrame(FRAME_COUNT - 5));
+    let mut last = first.pts;
+    let mut count = 1;
+
+    while let Some(frame) = decoder.next_frame() {
+        assert!(frame.pts > last);
+        last = frame.pts;
+        count += 1;
+    }
+
+    assert_eq!(count, 5);
+    assert_at(last, frame_time(FRAME_COUNT - 1));
+}
+
+#[test]
+fn exact_seeks_land_on_the_indexed_frame() {
+    // Matroska keeps timestamps in milliseconds, at 24 fps
+    // frames are 41 or 42 ticks apart instead of the average 42
+    let file = test_clip("exact.mkv", &["-r", "24"]);
+    let index = build_index(&file).expect("the clip indexes");
+    let mut decoder = SteppedDecoder::open(file);
+
+    for frame_index in [1, 2, 5, 23, 47, 70, index.frame_count() - 1] {
+        let command = index.seek_command(frame_index).unwrap();
+        let frame = decoder.seek(command);
+
+        assert_eq!(Some(frame.pts), index.frame_time(frame_index));
+    }
+}
+
+#[test]
+fn seeks_count_from_the_stream_start_time() {
+    // Every timestamp in the file is two seconds in
+    let mut decoder =
+        SteppedDecoder::open(test_clip("start_time.mkv", &["-output_ts_offset", "2"]));
+
+    let first = decoder.next_frame().expect("a first frame");
+    assert_at(first.pts, 0.0);
+
+    let frame = decoder.seek(SeekCommand::Seek(Duration::from_secs(1)));
+    assert_at(frame.pts, 1.0);
+
+    let frame = decoder.seek(SeekCommand::SeekFrame(60));
+    assert_at(frame.pts, frame_time(60));
+
+    let frame = decoder.seek(SeekCommand::Stop);
+    assert_at(frame.pts, 0.0);
+}
+
+/// Opens `file` paused, with the index built so seeks are exact
+fn open_paused(pool: &DecoderPool, file: PathBuf) -> Shared<VideoHandle> {
+    let handle =
+        VideoHandle::create_with_sink(pool, file, Box::new(NullSink)).expect("the clip opens");
+    handle.pause();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while handle.is_indexing() {
+        assert!(Instant::now() < deadline, "indexing the clip timed out");
+
+        handle.tick();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    handle
+}
+
+/// Ticks until the seek sent last presents its frame
+fn seeked_frame_pts(handle: &Shared<VideoHandle>) -> Duration {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    loop {
+        handle.tick();
+
+        if let Some(pts) = handle.get_frame_pts() {
+            return pts;
+        }
+
+        assert!(Instant::now() < deadline, "the seek never presented a frame");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn paused_handle_steps_one_frame_at_a_time() {
+    let pool = DecoderPool::new(1);
+    let handle = open_paused(&pool, test_clip("step.mp4", &[]));
+
+    handle.seek_frame(10);
+    assert_at(seeked_frame_pts(&handle), frame_time(10));
+
+    handle.step_forward();
+    handle.step_forward();
+    assert_at(handle.get_frame_pts().unwrap(), frame_time(12));
+    assert_eq!(handle.get_frame_index(), 12);
+
+    handle.step_backward();
+    assert_at(seeked_frame_pts(&handle), frame_time(11));
+
+    // Stepping never starts playback
+    assert_eq!(handle.get_play_state(), PlayState::Paused);
+}
+
+#[test]
+fn playing_handle_completes_at_eos() {
+    let pool = DecoderPool::new(1);
+    let handle = open_paused(&pool, test_clip("handle_eos.mp4", &[]));
+
+    handle.seek_frame(FRAME_COUNT - 3);
+    seeked_frame_pts(&handle);
+    handle.play();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while handle.get_play_state() != PlayState::Completed {
+        assert!(Instant::now() < deadline, "playback never completed");
+
+        handle.tick();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_at(handle.get_frame_pts().unwrap(), frame_time(FRAME_COUNT - 1));
+}
+
+/// A blank `format` frame, tagged `space` and `range`, through `FrameConverter`
+fn convert_tagged(
+    format: ffmpeg_next::format::Pixel,
+    space: ffmpeg_next::color::Space,
+    range: ffmpeg_next::color::Range,
+) -> ColorInfo {
+    ffmpeg_next::init().unwrap();
+
+    let mut decoded = ffmpeg_next::frame::Video::new(format, 64, 48);
+    decoded.set_color_space(space);
+    decoded.set_color_range(range);
+
+    let (_, color) = FrameConverter::new(FramePool::new(4))
+        .convert(&decoded)
+        .expect("swscale can convert the test frame");
+
+    color
+}
+
+#[test]
+fn converted_yuv_keeps_its_matrix() {
+    use ffmpeg_next::{color, format::Pixel};
+
+    let nv12 = convert_tagged(Pixel::NV12, color::Space::BT709, color::Range::MPEG);
+    assert_eq!(nv12.matrix, YuvMatrix::Bt709);
+    assert_eq!(nv12.range, YuvRange::Limited);
+
+    let p010 = convert_tagged(Pixel::P010LE, color::Space::BT2020NCL, color::Range::JPEG);
+    assert_eq!(p010.matrix, YuvMatrix::Bt2020);
+    assert_eq!(p010.range, YuvRange::Full);
+}
+
+#[test]
+fn converted_rgb_and_full_range_formats_are_retagged() {
+    use ffmpeg_next::{color, format::Pixel};
+
+    let rgb = convert_tagged(Pixel::RGB24, color::Space::RGB, color::Range::JPEG);
+    assert_eq!(rgb.matrix, YuvMatrix::Bt601);
+    assert_eq!(rgb.range, YuvRange::Limited);
+
+    let yuvj = convert_tagged(Pixel::YUVJ440P, color::Space::BT709, color::Range::JPEG);
+    assert_eq!(yuvj.matrix, YuvMatrix::Bt709);
+    assert_eq!(yuvj.range, YuvRange::Limited);
+}
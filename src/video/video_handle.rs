@@ -1,7 +1,8 @@
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, TryRecvError};
 
 use std::{
     path::PathBuf,
+    rc::Rc,
     sync::{atomic::AtomicBool, Arc},
     time::{Duration, Instant},
 };
@@ -9,25 +10,35 @@ use std::{
 use crate::{
     shared::Shared,
     thread_utils::custom_beams::{self, LooseSender},
-    video::{run_decoder_thread, InputInitError},
-    BENCHMARK_MODE,
+    video::{
+        spawn_indexer, AudioControl, AudioRingBuffer, AudioSink, DecoderJob, DecoderPool,
+        PlaybackClock, PoolWaker, VideoError, VideoIndex,
+    },
 };
 
 use super::{Frame, PipelineEvent};
 
 const MAX_PLAY_SPEED_SCALAR: f32 = 10.0;
 
+pub const SKIP_DURATION: Duration = Duration::from_secs(10);
+
 pub enum SeekCommand {
+    // Go back to the start
     Stop,
-    // Go +10 seconds
-    SkipForward,
-    // Go -10 seconds
-    SkipBackward,
-    // Go to a specific time (convert duration to sample_id)
-    Seek(f64),
+    // Go +10 seconds from the given position
+    SkipForward(Duration),
+    // Go -10 seconds from the given position
+    SkipBackward(Duration),
+    // Go to a specific time, the decoder lands on the frame covering it
+    Seek(Duration),
+    // Go to a specific frame index, based on the average frame rate
+    SeekFrame(i64),
+    // Go to the frame with the `target` PTS, decoding from the `keyframe`
+    // PTS before it. Both come from the `VideoIndex`, in the stream time_base
+    SeekExact { keyframe: i64, target: i64 },
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum PlayState {
     // Just created or stopped back to start
     Stopped,
@@ -39,7 +50,7 @@ pub enum PlayState {
     Completed,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum PlaySpeed {
     /// User needs to press for next frame
     Stopped,
@@ -57,9 +68,57 @@ pub enum PlaySpeed {
     Fastest,
 }
 
+impl PlaySpeed {
+    pub const ALL: [PlaySpeed; 7] = [
+        PlaySpeed::Stopped,
+        PlaySpeed::Slower,
+        PlaySpeed::Slow,
+        PlaySpeed::Normal,
+        PlaySpeed::Fast,
+        PlaySpeed::Faster,
+        PlaySpeed::Fastest,
+    ];
+
+    pub fn scalar(&self) -> f32 {
+        match self {
+            PlaySpeed::Stopped => 0.0,
+            PlaySpeed::Slower => 0.25,
+            PlaySpeed::Slow => 0.5,
+            PlaySpeed::Normal => 1.0,
+            PlaySpeed::Fast => 1.5,
+            PlaySpeed::Faster => 2.0,
+            PlaySpeed::Fastest => MAX_PLAY_SPEED_SCALAR,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlaySpeed::Stopped => "Frame by frame",
+            PlaySpeed::Slower => "x0.25",
+            PlaySpeed::Slow => "x0.5",
+            PlaySpeed::Normal => "x1.0",
+            PlaySpeed::Fast => "x1.5",
+            PlaySpeed::Faster => "x2.0",
+            PlaySpeed::Fastest => "Fastest",
+        }
+    }
+}
+
+/// Where the seek sent to the decoder is at, the frame on screen stays
+/// until the decoder has the new one
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum SeekState {
+    Idle,
+    /// Frames before the `SeekAck` are from the old position
+    AwaitingAck,
+    /// The first frame after the ack is the one seeked to
+    AwaitingFrame,
+}
+
 pub struct InitData {
     pub fps: f64,
     pub total_duration: Duration,
+    pub has_audio: bool,
 }
 
 pub struct VideoHandle {
@@ -78,13 +137,28 @@ pub struct VideoHandle {
     current_timestamp: Duration,
     last_decode_cost: Option<Duration>,
     frame_timestamp: Option<Duration>,
+    error_count: u64,
+    last_error: Option<VideoError>,
+    audio_ring: Arc<AudioRingBuffer>,
+    audio_control: Arc<AudioControl>,
+    clock: Arc<PlaybackClock>,
+    has_audio: bool,
     eos: bool,
+    index: Option<Rc<VideoIndex>>,
+    index_receiver: Option<Receiver<Result<VideoIndex, VideoError>>>,
+    seek_state: SeekState,
+    /// Sent once the pending seek is acknowledged, only the latest is kept
+    deferred_seek: Option<SeekCommand>,
+    /// Taking frames or sending commands can make the decoder job ready
+    pool_waker: PoolWaker,
 }
 
 impl Drop for VideoHandle {
     fn drop(&mut self) {
         self.close_thread
             .store(true, std::sync::atomic::Ordering::Relaxed);
+        self.audio_control.close();
+        self.pool_waker.wake();
     }
 }
 
@@ -95,62 +169,256 @@ fn scale_duration(duration: Duration, scale: f32) -> Duration {
 }
 
 impl VideoHandle {
-    pub fn create(file: PathBuf) -> Shared<VideoHandle> {
+    /// Takes one frame from the decoder, when `block` is set waits for
+    /// the decoder to produce it instead of giving up on an empty channel
+    fn take_one_frame(&mut self, block: bool) -> Option<Frame> {
+        loop {
+            let event = if block {
+                self.yuv_frame_receiver
+                    .recv()
+                    .map_err(|_| TryRecvError::Disconnected)
+            } else {
+                self.yuv_frame_receiver.try_recv()
+            };
+
+            if event.is_ok() {
+                self.pool_waker.wake();
+            }
+
+            match event {
+                Ok(PipelineEvent::Data(frame)) => return Some(frame),
+                Ok(PipelineEvent::EOS) => {
+                    self.eos = true;
+                    return None;
+                }
+                Ok(PipelineEvent::Error(error)) => self.record_error(error),
+                Ok(PipelineEvent::SeekAck) | Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.eos {
+                        self.record_error(VideoError::Disconnected);
+                    }
+                    self.eos = true;
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn record_error(&mut self, error: VideoError) {
+        self.error_count += 1;
+        self.last_error = Some(error);
+    }
+
+    /// Presents a frame right away, moving the clock to its PTS
+    fn present(&mut self, frame: Frame) {
+        if self.queued_frame.is_some() {
+            self.dropped_frames += 1;
+        }
+
+        self.current_timestamp = frame.pts;
+        self.frame_timestamp = Some(frame.pts);
+        self.last_decode_cost = Some(frame.decode_cost);
+        self.queued_frame = Some(frame);
+    }
+
+    /// The audio drives the playback clock while there is audio left to play
+    fn audio_clock(&self) -> Option<Duration> {
+        if !self.has_audio {
+            return None;
+        }
+
+        self.audio_ring.clock()
+    }
+
+    fn frame_index(&self) -> i64 {
+        let timestamp = self.frame_timestamp.unwrap_or(self.current_timestamp);
+
+        match self.index {
+            Some(ref index) => index.frame_index_at(timestamp) as i64,
+            None => (timestamp.as_secs_f64() * self.fps).round() as i64,
+        }
+    }
+
+    /// Picks up the index once the background indexer is done with it
+    fn poll_index(&mut self) {
+        let Some(ref receiver) = self.index_receiver else {
+            return;
+        };
+
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(VideoError::Disconnected),
+        };
+
+        self.index_receiver = None;
+
+        match result {
+            Ok(index) => self.index = Some(Rc::new(index)),
+            Err(error) => self.record_error(error),
+        }
+    }
+
+    /// Hands a seek to the decoder, one at a time so every ack
+    /// is matched with the command it answers
+    fn send_command(&mut self, command: SeekCommand) {
+        if self.seek_state == SeekState::AwaitingAck {
+            self.deferred_seek = Some(command);
+            return;
+        }
+
+        if self.command_sender.loosely_send(command).is_err() {
+            self.record_error(VideoError::Disconnected);
+            return;
+        }
+
+        self.pool_waker.wake();
+        self.seek_state = SeekState::AwaitingAck;
+    }
+
+    /// Drops the frames decoded before the pending seek and presents the
+    /// first one after it, without waiting on the decoder
+    fn poll_seek(&mut self) {
+        while self.seek_state != SeekState::Idle {
+            let event = match self.yuv_frame_receiver.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.record_error(VideoError::Disconnected);
+                    self.eos = true;
+                    self.seek_state = SeekState::Idle;
+                    return;
+                }
+            };
+
+            self.pool_waker.wake();
+
+            match (self.seek_state, event) {
+                (_, PipelineEvent::Error(error)) => self.record_error(error),
+                (SeekState::AwaitingAck, PipelineEvent::SeekAck) => {
+                    self.seek_state = SeekState::AwaitingFrame;
+
+                    if let Some(command) = self.deferred_seek.take() {
+                        self.send_command(command);
+                    }
+                }
+                (SeekState::AwaitingAck, PipelineEvent::Data(_)) => {
+                    self.dropped_frames += 1;
+                }
+                // Expected when seeking after the decoder reached the end
+                (SeekState::AwaitingAck, PipelineEvent::EOS) => {}
+                // The decoder already skipped the frames between
+                // the keyframe and the target
+                (SeekState::AwaitingFrame, PipelineEvent::Data(frame)) => {
+                    self.present(frame);
+                    self.seek_state = SeekState::Idle;
+                    self.last_update = Instant::now();
+                }
+                (SeekState::AwaitingFrame, PipelineEvent::EOS) => {
+                    self.eos = true;
+                    self.seek_state = SeekState::Idle;
+                }
+                (_, PipelineEvent::SeekAck) | (SeekState::Idle, _) => {}
+            }
+        }
+    }
+
+    /// Opens `file` and schedules its decoding on `pool`,
+    /// the decoded audio is played into `sink`
+    pub fn create_with_sink(
+        pool: &DecoderPool,
+        file: PathBuf,
+        sink: Box<dyn AudioSink>,
+    ) -> Result<Shared<VideoHandle>, VideoError> {
         let (command_sender, command_receiver) = custom_beams::loose::<SeekCommand>(1);
 
+        // The channel capacity caps the frame budget of this source
         let (yuv_frame_sender, yuv_frame_receiver) =
-            crossbeam_channel::bounded(if BENCHMARK_MODE { 512 } else { 16 });
+            crossbeam_channel::bounded(pool.max_frames_per_source());
 
         let (init_result_sender, init_result_receiver) =
-            crossbeam_channel::bounded::<Result<InitData, InputInitError>>(0);
+            crossbeam_channel::bounded::<Result<InitData, VideoError>>(1);
 
         let close_thread = Arc::new(AtomicBool::new(false));
 
         let close_thread_clone = close_thread.clone();
 
-        std::thread::Builder::new()
-            .spawn(move || {
-                run_decoder_thread(
-                    file,
-                    init_result_sender,
-                    yuv_frame_sender,
-                    close_thread_clone,
-                    command_receiver,
-                )
-            })
-            .unwrap();
-
-        if let Ok(data) = init_result_receiver
+        let audio_ring = Arc::new(AudioRingBuffer::new());
+        let audio_control = Arc::new(AudioControl::new());
+
+        let audio_ring_clone = audio_ring.clone();
+
+        let clock = Arc::new(PlaybackClock::default());
+
+        let index_receiver = spawn_indexer(file.clone(), close_thread.clone());
+
+        pool.submit(DecoderJob::new(
+            file,
+            init_result_sender,
+            yuv_frame_sender,
+            close_thread_clone,
+            command_receiver,
+            audio_ring_clone,
+            clock.clone(),
+        ));
+
+        let data = init_result_receiver
             .recv()
-            .expect("Failed to read from ffmpeg thread")
-        {
-            return Shared::new(VideoHandle {
-                close_thread,
-                fps: data.fps,
-                total_duration: data.total_duration,
-                progress: 0.0,
-                play_state: PlayState::Playing,
-                play_speed: 1.0,
-                command_sender,
-                last_update: Instant::now(),
-                yuv_frame_receiver,
-                next_frame: None,
-                dropped_frames: 0,
-                queued_frame: None,
-                frame_timestamp: None,
-                current_timestamp: Duration::from_millis(0),
-                last_decode_cost: None,
-                eos: false,
-            });
+            .map_err(|_| VideoError::Disconnected)??;
+
+        let handle = VideoHandle {
+            close_thread,
+            fps: data.fps,
+            total_duration: data.total_duration,
+            progress: 0.0,
+            play_state: PlayState::Playing,
+            play_speed: 1.0,
+            command_sender,
+            last_update: Instant::now(),
+            yuv_frame_receiver,
+            next_frame: None,
+            dropped_frames: 0,
+            queued_frame: None,
+            frame_timestamp: None,
+            current_timestamp: Duration::from_millis(0),
+            last_decode_cost: None,
+            error_count: 0,
+            last_error: None,
+            audio_ring: audio_ring.clone(),
+            audio_control: audio_control.clone(),
+            clock,
+            has_audio: data.has_audio,
+            eos: false,
+            index: None,
+            index_receiver: Some(index_receiver),
+            seek_state: SeekState::Idle,
+            deferred_seek: None,
+            pool_waker: pool.waker(),
+        };
+
+        if data.has_audio {
+            pool.play_audio(audio_ring, audio_control, sink);
         }
 
-        panic!("Failed to create FFMPEG thred")
+        Ok(Shared::new(handle))
     }
 }
 
 impl Shared<VideoHandle> {
     pub fn play(&self) {
+        let completed = self.with(|this| {
+            this.last_update = Instant::now();
+            this.play_state == PlayState::Completed
+        });
+
+        if completed {
+            self.stop();
+        }
+
         self.with(|this| {
+            if this.play_speed == 0.0 {
+                this.play_speed = PlaySpeed::Normal.scalar();
+            }
             this.play_state = PlayState::Playing;
         })
     }
@@ -159,7 +427,84 @@ impl Shared<VideoHandle> {
             this.play_state = PlayState::Paused;
         })
     }
-    pub fn stop(&self) {}
+    pub fn stop(&self) {
+        self.send_seek(SeekCommand::Stop, Duration::ZERO);
+
+        self.with(|this| {
+            this.play_state = PlayState::Stopped;
+        })
+    }
+
+    pub fn skip_forward(&self) {
+        let (from, to) = self.with_ref(|this| {
+            let from = this.current_timestamp;
+            (from, (from + SKIP_DURATION).min(this.total_duration))
+        });
+
+        self.send_seek(SeekCommand::SkipForward(from), to);
+    }
+
+    pub fn skip_backward(&self) {
+        let from = self.get_pts();
+
+        self.send_seek(SeekCommand::SkipBackward(from), from.saturating_sub(SKIP_DURATION));
+    }
+
+    /// Presents the next decoded frame and stays paused. While a seek is
+    /// pending its frame comes first, the step is dropped.
+    pub fn step_forward(&self) {
+        self.with(|this| {
+            this.play_state = PlayState::Paused;
+
+            if this.seek_state != SeekState::Idle {
+                return;
+            }
+
+            let frame = match this.next_frame.take() {
+                Some(frame) => Some(frame),
+                None if this.eos => None,
+                None => this.take_one_frame(true),
+            };
+
+            if let Some(frame) = frame {
+                let pts = frame.pts;
+                this.present(frame);
+
+                // Like after a seek, the audio and the clock
+                // continue from the frame on screen
+                this.audio_ring.skip_to(pts);
+                this.clock.publish(pts, false);
+                this.last_update = Instant::now();
+            }
+        })
+    }
+
+    /// Seeks to the frame before the one on screen and stays paused
+    pub fn step_backward(&self) {
+        let index = self.with(|this| {
+            this.play_state = PlayState::Paused;
+            this.frame_index() - 1
+        });
+
+        if index >= 0 {
+            self.seek_frame(index);
+        }
+    }
+
+    pub fn get_play_state(&self) -> PlayState {
+        self.with_ref(|this| this.play_state)
+    }
+
+    pub fn set_play_speed(&self, speed: PlaySpeed) {
+        self.with(|this| {
+            this.play_speed = speed.scalar();
+
+            // The user steps through frames by hand from here
+            if speed == PlaySpeed::Stopped {
+                this.play_state = PlayState::Paused;
+            }
+        })
+    }
 
     pub fn get_pts(&self) -> Duration {
         self.with(|this| this.current_timestamp)
@@ -173,6 +518,21 @@ impl Shared<VideoHandle> {
         self.with(|this| this.dropped_frames)
     }
 
+    /// Late frames the decoder skipped, they never reached `get_dropped_frames`
+    pub fn get_decoder_dropped_frames(&self) -> u64 {
+        self.with_ref(|this| this.clock.decoder_drops())
+    }
+
+    /// Errors reported by the decoder thread since the video was opened
+    pub fn get_errors(&self) -> (u64, Option<VideoError>) {
+        self.with_ref(|this| (this.error_count, this.last_error.clone()))
+    }
+
+    /// How much decoded audio is waiting to be played, `None` without an audio stream
+    pub fn get_audio_buffered(&self) -> Option<Duration> {
+        self.with_ref(|this| this.has_audio.then(|| this.audio_ring.buffered()))
+    }
+
     pub fn get_next_pts(&self) -> Option<Duration> {
         self.with_ref(|this| {
             if let Some(ref frame) = this.next_frame {
@@ -224,93 +584,106 @@ impl Shared<VideoHandle> {
         len as f64 / cap as f64
     }
 
+    /// The frame index of the file, once the background indexer built it
+    pub fn get_index(&self) -> Option<Rc<VideoIndex>> {
+        self.with_ref(|this| this.index.clone())
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.with_ref(|this| this.index_receiver.is_some())
+    }
+
+    /// Index of the frame on screen
+    pub fn get_frame_index(&self) -> i64 {
+        self.with_ref(|this| this.frame_index())
+    }
+
+    /// Lands on the frame covering `to`, exactly once the index is built
     pub fn seek(&self, to: Duration) {
-        self.with(|this| {
-            let total = this.total_duration.as_millis_f64();
-            let target = to.as_millis_f64();
+        match self.get_index() {
+            Some(index) => self.seek_frame(index.frame_index_at(to) as i64),
+            None => self.send_seek(SeekCommand::Seek(to), to),
+        }
+    }
+
+    pub fn seek_frame(&self, index: i64) {
+        let exact = self.get_index().and_then(|video_index| {
+            let frame = index.max(0) as usize;
+
+            Some((
+                video_index.seek_command(frame)?,
+                video_index.frame_time(frame)?,
+            ))
+        });
 
-            // Due to incomprehensible problems
-            // the seek thread accepts 0.0 .. 1.0 range
-            // instead of a Duration
-            let norm = target / total;
+        if let Some((command, to)) = exact {
+            self.send_seek(command, to);
+            return;
+        }
+
+        let to = self.with_ref(|this| Duration::from_secs_f64(index.max(0) as f64 / this.fps));
 
+        self.send_seek(SeekCommand::SeekFrame(index), to);
+    }
+
+    /// Moves the playhead to `to` right away, the frame on screen
+    /// is replaced once the decoder catches up, see `poll_seek`
+    fn send_seek(&self, command: SeekCommand, to: Duration) {
+        self.with(|this| {
             this.current_timestamp = to;
             this.frame_timestamp = None;
 
-            this.command_sender
-                .loosely_send(SeekCommand::Seek(norm))
-                .expect("Sending commands should always succed");
+            // Before the command, frames after the target must not look late
+            this.clock
+                .publish(to, this.play_state == PlayState::Playing);
+
+            // The decoder resets it too, this keeps the old
+            // position from driving the clock until it does
+            this.audio_ring.reset();
+
+            if this.play_state == PlayState::Completed {
+                this.play_state = PlayState::Paused;
+            }
 
             if this.next_frame.is_some() {
                 this.dropped_frames += 1;
                 this.next_frame = None;
             }
 
-            // Drain old frames until a SeekAck
-            for item in this.yuv_frame_receiver.iter() {
-                match item {
-                    PipelineEvent::Data(_) => {
-                        this.dropped_frames += 1;
-                    }
-                    PipelineEvent::SeekAck => {
-                        break;
-                    }
-                    PipelineEvent::EOS => {
-                        return;
-                    }
-                }
-            }
+            this.eos = false;
+            this.send_command(command);
 
-            // Then drain frames from the keyframe
-            // until the frame we want
-            for item in this.yuv_frame_receiver.iter() {
-                match item {
-                    PipelineEvent::Data(frame) => {
-                        if frame.pts >= to {
-                            if this.queued_frame.is_some() {
-                                this.dropped_frames += 1;
-                            }
-
-                            this.queued_frame = Some(frame);
-
-                            break;
-                        }
-                        this.dropped_frames += 1;
-                    }
-                    PipelineEvent::SeekAck => {
-                        panic!("Cannot have two seek acks in the same streams");
-                    }
-                    PipelineEvent::EOS => {
-                        return;
-                    }
-                }
-            }
+            this.last_update = Instant::now();
         });
     }
     pub fn tick(&self) {
         self.with(|this| {
-            if this.play_state != PlayState::Playing {
+            this.poll_index();
+            this.poll_seek();
+
+            // The audio of the new position waits for its first frame
+            let seeking = this.seek_state != SeekState::Idle;
+
+            this.audio_control
+                .set_playing(this.play_state == PlayState::Playing && !seeking);
+            this.audio_control.set_speed(this.play_speed);
+
+            if this.play_state != PlayState::Playing || seeking {
+                this.clock.publish(
+                    this.current_timestamp,
+                    this.play_state == PlayState::Playing,
+                );
                 this.last_update = Instant::now();
                 return;
             }
 
-            let has_frames = || !this.yuv_frame_receiver.is_empty();
-
-            let take_one_frame = || {
-                this.yuv_frame_receiver
-                    .try_recv()
-                    .map_or(None, |a| match a {
-                        PipelineEvent::Data(frame) => Some(frame),
-                        PipelineEvent::EOS => None,
-                        PipelineEvent::SeekAck => None,
-                    })
-            };
-
             if this.next_frame.is_none() {
-                if has_frames() {
-                    this.next_frame = take_one_frame();
-                    this.eos = this.next_frame.is_none();
-                } else {
+                this.next_frame = this.take_one_frame(false);
+
+                if this.next_frame.is_none() {
+                    if this.eos {
+                        this.play_state = PlayState::Completed;
+                    }
                     return;
                 }
             }
@@ -318,10 +691,17 @@ impl Shared<VideoHandle> {
             let now = Instant::now();
             let delta = now - this.last_update;
             this.last_update = now;
-            this.current_timestamp += scale_duration(delta, this.play_speed);
 
-            // Frame dropping is being expensive, move it to the decoder thread thread
+            match this.audio_clock() {
+                Some(clock) => this.current_timestamp = clock,
+                None => this.current_timestamp += scale_duration(delta, this.play_speed),
+            }
+
+            this.clock.publish(this.current_timestamp, true);
 
+            // Most late frames never get here, the decoder skips them
+            // against the clock. These are the ones it sent in time
+            // that the render thread didn't present before the next.
             loop {
                 // Next frame is in the future
                 // we can grab it inext farme
@@ -355,12 +735,11 @@ impl Shared<VideoHandle> {
                 }
 
                 // Set next frame
-                this.next_frame = take_one_frame();
-                this.eos = this.next_frame.is_none();
+                this.next_frame = this.take_one_frame(false);
 
                 // There isn't a new frame, break
                 // and leave processing for the next tick instead.
-                if this.eos {
+                if this.next_frame.is_none() {
                     break;
                 }
             }